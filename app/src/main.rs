@@ -1,44 +1,285 @@
-use fsh_engine::{eval, State};
-use fsh_parser::Parser;
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    process,
+};
+
+use fsh_common::{Error, ErrorKind};
+use fsh_engine::{
+    eval, eval_file, eval_pending_traps, exit_shell, signal, ShVars, State,
+    RESERVEDWORD_SHELL_VARIABLE_LAST_BACKGROUND, RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS,
+    RESERVEDWORD_SHELL_VARIABLE_SCRIPT_NAME, RESERVEDWORD_SHELL_VARIABLE_STATUS,
+};
+use fsh_parser::{investigate::Investigator, Parser};
 use fsh_terminal::{prompt, Terminal};
 
 #[inline]
-fn rep() {
-    let mut state = State::new();
-    state.vars_mut().inherit(std::env::vars());
+fn print_error(err: &Error) {
+    eprintln!("fsh: {}: {}", err.kind().as_str2(), err.message());
+}
+
+/// Check if the source needs more lines to be a complete program.
+#[inline]
+fn is_incomplete(source: &str) -> bool {
+    match Parser::new(source).parse() {
+        Err(err) => err.kind() == &ErrorKind::Incomplete,
+        Ok(_) => false,
+    }
+}
+
+#[inline]
+fn rep(terminal: &mut Terminal, state: &mut State, sh_vars: &mut ShVars) {
+    if let Err(err) = eval_pending_traps(state, sh_vars) {
+        print_error(&err);
+    }
+
+    // the background jobs that are done are reported before the prompt.
+    for notice in state.reap_jobs() {
+        eprintln!("{notice}");
+    }
 
     // read
-    let mut terminal = Terminal::new();
+    terminal.set_prompt(prompt(sh_vars.get_prompt().unwrap_or("\\W$ ")));
+
+    terminal.set_continuation_prompt(prompt(sh_vars.get_prompt2().unwrap_or("> ")));
 
-    terminal.set_prompt(
-        prompt(state
-            .vars()
-            .get("FSH_PROMPT")
-            .unwrap_or("\\W$ ")
-            .to_string()),
-    );
+    let source = match terminal.read_until(|source| !is_incomplete(source)) {
+        Ok(source) => source,
+        // Ctrl-C discards the input.
+        Err(err) if err.kind() == &ErrorKind::Interrupted => return,
+        // the end of the input, or Ctrl-D, exits the shell.
+        Err(err) if err.kind() == &ErrorKind::EndOfInput => {
+            let code = state.status().code();
 
-    let line = terminal.read_line().unwrap_or_default();
+            exit_shell(code, state, sh_vars)
+        }
+        Err(err) => {
+            print_error(&err);
+            return;
+        }
+    };
 
     // eval and print
-    let ast = match Parser::new(&line).parse() {
+    let ast = match Parser::new(&source).parse() {
         Ok(ast) => ast,
-        Err(e) => {
-            todo!()
+        Err(err) => {
+            print_error(&err);
+            return;
         }
     };
 
-    if let Err(err) = eval(ast, &mut state) {
-        todo!()
+    match eval(ast, state, sh_vars) {
+        // a process killed by a signal, such as "Segmentation fault (core dumped)".
+        Ok(status) => {
+            if let Some(message) = status.message() {
+                eprintln!("{message}");
+            }
+        }
+        Err(err) => print_error(&err),
     }
 }
 
+/// The state and the shell variables of a new shell, in the current directory and environment.
+fn init() -> (State, ShVars) {
+    let mut state = State::new();
+
+    if let Ok(current_dir) = std::env::current_dir() {
+        *state.current_dir_mut() = current_dir;
+    }
+
+    let mut sh_vars = ShVars::new();
+
+    sh_vars.inherit(std::env::vars());
+
+    // no pipeline has run yet, `$?` and `$PIPESTATUS` are set as after a success.
+    sh_vars.insert(RESERVEDWORD_SHELL_VARIABLE_STATUS, "0");
+    sh_vars.insert(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS, "0");
+
+    (state, sh_vars)
+}
+
 fn repl() {
+    let (mut state, mut sh_vars) = init();
+
+    if let Err(err) = state.jobs_mut().init_terminal() {
+        print_error(&err);
+    }
+
+    signal::init();
+
+    let mut terminal = Terminal::new();
+
     loop {
-        rep();
+        rep(&mut terminal, &mut state, &mut sh_vars);
     }
 }
 
+/// Run a script, the arguments are its positional parameters.
+///
+/// The script is not interactive, it has no job control and SIGINT kills it.
+/// The shell exits with the exit status of the last pipeline, 1 on an error, after the EXIT trap.
+fn script(path: &str, args: &[String]) -> ! {
+    let (mut state, mut sh_vars) = init();
+
+    sh_vars.insert(RESERVEDWORD_SHELL_VARIABLE_SCRIPT_NAME, path);
+
+    sh_vars.set_args(args);
+
+    let code = match eval_file(Path::new(path), &mut state, &mut sh_vars) {
+        Ok(status) => {
+            if let Some(message) = status.message() {
+                eprintln!("{message}");
+            }
+
+            status.code()
+        }
+        Err(err) => {
+            print_error(&err);
+            1
+        }
+    };
+
+    exit_shell(code, &mut state, &mut sh_vars)
+}
+
+/// Check scripts for errors without running them.
+///
+/// All errors are reported at once, as `path:line:column: kind: message`.
+/// Warnings of the static analysis follow, as `path:line:column: warning[code]: message`.
+///
+/// # Returns
+/// The exit code, 1 if any error is found. Warnings do not change the exit code.
+fn check(paths: &[String]) -> i32 {
+    let mut code = 0;
+
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("fsh: {path}: {err}");
+                code = 1;
+                continue;
+            }
+        };
+
+        let mut parser = Parser::new(&source);
+
+        let (ast, diagnostics) = parser.parse_with_diagnostics();
+
+        for diagnostic in &diagnostics {
+            eprintln!("{path}:{diagnostic}");
+        }
+
+        let mut investigator = Investigator::from_source(&source);
+
+        std::env::vars().for_each(|(name, _)| {
+            investigator.define(name);
+        });
+
+        investigator
+            .define(RESERVEDWORD_SHELL_VARIABLE_STATUS)
+            .define(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS)
+            .define(RESERVEDWORD_SHELL_VARIABLE_LAST_BACKGROUND);
+
+        for warning in investigator.investigate(&ast) {
+            eprintln!(
+                "{path}:{}: {warning}",
+                parser.locations()[warning.statement()]
+            );
+        }
+
+        if !diagnostics.is_empty() {
+            code = 1;
+        }
+    }
+
+    code
+}
+
+/// Format the source, or report its errors as `name:line:column: kind: message`.
+fn format_source(name: &str, source: &str) -> Option<String> {
+    match fsh_parser::format(source) {
+        Ok(formatted) => Some(formatted),
+        Err(err) => {
+            let (_, diagnostics) = Parser::new(source).parse_with_diagnostics();
+
+            for diagnostic in &diagnostics {
+                eprintln!("{name}:{diagnostic}");
+            }
+
+            // the source parses, but can not be formatted without losing a part of it.
+            if diagnostics.is_empty() {
+                eprintln!("{name}: {}: {}", err.kind().as_str2(), err.message());
+            }
+
+            None
+        }
+    }
+}
+
+/// Format scripts in the canonical style.
+///
+/// The files are rewritten in place. Without paths, the script is read from stdin and written to stdout.
+///
+/// # Returns
+/// The exit code, 1 if any script can not be formatted.
+fn fmt(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        let mut source = String::new();
+
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("fsh: stdin: {err}");
+            return 1;
+        }
+
+        return match format_source("<stdin>", &source) {
+            Some(formatted) => {
+                print!("{formatted}");
+                0
+            }
+            None => 1,
+        };
+    }
+
+    let mut code = 0;
+
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("fsh: {path}: {err}");
+                code = 1;
+                continue;
+            }
+        };
+
+        let formatted = match format_source(path, &source) {
+            Some(formatted) => formatted,
+            None => {
+                code = 1;
+                continue;
+            }
+        };
+
+        if formatted != source {
+            if let Err(err) = fs::write(path, formatted) {
+                eprintln!("fsh: {path}: {err}");
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
+
 fn main() {
-    repl();
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    match args.first().map(String::as_str) {
+        Some("--check") => process::exit(check(&args[1..])),
+        Some("--fmt") => process::exit(fmt(&args[1..])),
+        Some(path) => script(path, &args[1..]),
+        None => repl(),
+    }
 }
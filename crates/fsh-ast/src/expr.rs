@@ -5,8 +5,13 @@ use super::FshAst;
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Expr {
     String(String),
+    /// A string used as written, it is never globbed.
+    Literal(String),
     Ident(String),
-    Number(usize),
+    /// A `NAME=$VALUE` argument, the name and the variable of the value.
+    KeyIdent(String, String),
+    Number(i64),
+    Float(f64),
     FD(i32),
 }
 
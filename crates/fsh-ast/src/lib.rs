@@ -19,6 +19,8 @@ pub enum Ast {
     Semicolon(VecDeque<Ast>),
     Pipe(Pipe),
     Statement(Statement),
+    /// `! pipeline`, the exit status of the pipeline is inverted.
+    Negated(Box<Ast>),
 }
 
 impl Ast {
@@ -48,6 +50,12 @@ impl Ast {
     }
 }
 
+impl Default for Ast {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FshAst for Ast {
     fn to_json(&self, is_pretty: bool) -> String {
         if is_pretty {
@@ -18,6 +18,10 @@ impl Pipe {
         self.commands.is_empty()
     }
 
+    pub fn commands(&self) -> &VecDeque<Command> {
+        &self.commands
+    }
+
     pub fn push_back(&mut self, command: Command) {
         self.commands.push_back(command);
     }
@@ -27,6 +31,12 @@ impl Pipe {
     }
 }
 
+impl Default for Pipe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<VecDeque<Command>> for Pipe {
     fn from(commands: VecDeque<Command>) -> Self {
         Pipe { commands }
@@ -1,4 +1,4 @@
-use super::{FshAst, expr::*};
+use super::{expr::*, Ast, FshAst};
 use serde::Serialize;
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -38,6 +38,7 @@ impl FshAst for Redirect {
 pub enum RedirectOperator {
     Gt,
     Lt,
+    HereDoc,
 }
 
 impl FshAst for RedirectOperator {
@@ -56,6 +57,8 @@ pub struct Command {
     pub args: Vec<Expr>,
     pub redirects: Vec<Redirect>,
     pub background: bool,
+    /// The stderr is piped to the next command with the stdout, as `|&`.
+    pub pipe_stderr: bool,
 }
 
 impl FshAst for Command {
@@ -68,11 +71,118 @@ impl FshAst for Command {
     }
 }
 
+/// A pattern of a match arm.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Pattern {
+    /// A glob pattern, `*.rs`.
+    Glob(String),
+    /// A regular expression, `~ '^v[0-9]+$'`.
+    Regex(String),
+    /// The pattern that matches anything, `_`.
+    Wildcard,
+}
+
+impl FshAst for Pattern {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchArm {
+    pub patterns: Vec<Pattern>,
+    pub body: Ast,
+}
+
+impl FshAst for MatchArm {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
+
+/// `match $x { a | b => { ... } _ => { ... } }`, only the first matching arm runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Match {
+    pub expr: Expr,
+    pub arms: Vec<MatchArm>,
+}
+
+impl FshAst for Match {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
+
+/// `import "lib/git.fsh"`, the variables of the module are accessible as `$git::name`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Import {
+    pub path: Expr,
+}
+
+impl FshAst for Import {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
+
+/// `catch $err { ... }`, the variable is optional.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Catch {
+    pub ident: Option<Expr>,
+    pub body: Box<Ast>,
+}
+
+impl FshAst for Catch {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
+
+/// `try { ... } catch $err { ... } finally { ... }`, at least one of `catch` and `finally` is present.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Try {
+    pub body: Box<Ast>,
+    pub catch: Option<Catch>,
+    pub finally: Option<Box<Ast>>,
+}
+
+impl FshAst for Try {
+    fn to_json(&self, is_pretty: bool) -> String {
+        if is_pretty {
+            serde_json::to_string_pretty(&self).unwrap()
+        } else {
+            serde_json::to_string(&self).unwrap()
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Statement {
     Command(Command),
     Assign(Assign),
+    Match(Match),
+    Import(Import),
+    Try(Try),
 }
 
 impl FshAst for Statement {
@@ -80,6 +190,9 @@ impl FshAst for Statement {
         match self {
             Statement::Command(command) => command.to_json(is_pretty),
             Statement::Assign(assign) => assign.to_json(is_pretty),
+            Statement::Match(r#match) => r#match.to_json(is_pretty),
+            Statement::Import(import) => import.to_json(is_pretty),
+            Statement::Try(r#try) => r#try.to_json(is_pretty),
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorKind {
     Dummy,
     Internal,
@@ -16,6 +16,8 @@ pub enum ErrorKind {
     EngineError,
     BrokenPipe,
     InvalidInput,
+    Incomplete,
+    EndOfInput,
 }
 
 impl ErrorKind {
@@ -37,6 +39,8 @@ impl ErrorKind {
             ErrorKind::EngineError => "EngineError",
             ErrorKind::BrokenPipe => "BrokenPipe",
             ErrorKind::InvalidInput => "InvalidInput",
+            ErrorKind::Incomplete => "Incomplete",
+            ErrorKind::EndOfInput => "EndOfInput",
         }
     }
 
@@ -58,6 +62,8 @@ impl ErrorKind {
             ErrorKind::EngineError => "engine error",
             ErrorKind::BrokenPipe => "broken pipe",
             ErrorKind::InvalidInput => "invalid input",
+            ErrorKind::Incomplete => "incomplete input",
+            ErrorKind::EndOfInput => "end of input",
         }
     }
 }
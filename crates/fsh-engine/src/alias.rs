@@ -0,0 +1,216 @@
+use fsh_common::{Error, ErrorKind, Result};
+use std::collections::{BTreeMap, HashSet};
+
+/// An alias, its value as written and the command it is parsed to.
+#[derive(Debug, Clone)]
+struct Alias {
+    value: String,
+    command: fsh_ast::Command,
+}
+
+/// The aliases of the session, by name.
+///
+/// An alias replaces the first word of a command. if its value ends with a space, the word after it is expanded too, as with `alias sudo='sudo '`.
+#[derive(Debug, Clone, Default)]
+pub struct Aliases(BTreeMap<String, Alias>);
+
+impl Aliases {
+    /// Create a new empty aliases
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define an alias, it replaces the alias of the same name.
+    ///
+    /// # Errors
+    /// `ErrorKind::InvalidInput` - If the value is not a simple command, as `ls | wc`.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        let (name, value) = (name.into(), value.into());
+
+        let command = parse(&name, &value)?;
+
+        self.0.insert(name, Alias { value, command });
+
+        Ok(())
+    }
+
+    /// Remove an alias
+    ///
+    /// # Returns
+    /// The value of the alias, `None` if there is no alias with the name.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name).map(|alias| alias.value)
+    }
+
+    /// Get the value of an alias
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|alias| alias.value.as_str())
+    }
+
+    /// Get all aliases, in alphabetical order.
+    pub fn entries(&self) -> Vec<(&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, alias)| (name.as_str(), alias.value.as_str()))
+            .collect()
+    }
+
+    /// Remove all aliases
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Check if there is no alias
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the number of aliases
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Expand the aliases of a command.
+    ///
+    /// The first word is expanded until it is not an alias. an alias is expanded once per command, so `alias ls='ls -F'` does not loop.
+    /// The redirects of an alias come before the redirects of the command.
+    pub fn expand(&self, mut command: fsh_ast::Command) -> fsh_ast::Command {
+        if self.0.is_empty() {
+            return command;
+        }
+
+        let mut words = vec![command.expr.to_owned()];
+
+        words.append(&mut command.args);
+
+        let mut redirects = Vec::new();
+
+        let mut expanded = HashSet::new();
+
+        // the word to expand, and the word after the alias with a trailing space.
+        let (mut position, mut next) = (0, None);
+
+        loop {
+            let alias = match words.get(position) {
+                Some(fsh_ast::Expr::String(name)) if !expanded.contains(name) => {
+                    self.0.get(name).map(|alias| (name.to_owned(), alias))
+                }
+                _ => None,
+            };
+
+            let (name, alias) = match alias {
+                Some(alias) => alias,
+                None => match next.take() {
+                    Some(word) => {
+                        position = word;
+                        continue;
+                    }
+                    None => break,
+                },
+            };
+
+            let mut replacement = vec![alias.command.expr.to_owned()];
+
+            replacement.extend(alias.command.args.iter().cloned());
+
+            let len = replacement.len();
+
+            words.splice(position..position + 1, replacement);
+
+            redirects.extend(alias.command.redirects.iter().cloned());
+
+            command.background |= alias.command.background;
+
+            // the words of a nested alias shift the word after the outer alias.
+            next = if alias.value.ends_with(char::is_whitespace) {
+                Some(position + len)
+            } else {
+                next.map(|word| word + len - 1)
+            };
+
+            expanded.insert(name);
+        }
+
+        redirects.append(&mut command.redirects);
+
+        let mut words = words.into_iter();
+
+        command.expr = words.next().unwrap_or(command.expr);
+        command.args = words.collect();
+        command.redirects = redirects;
+
+        command
+    }
+}
+
+/// Parse the value of an alias, it is a simple command.
+fn parse(name: &str, value: &str) -> Result<fsh_ast::Command> {
+    let ast = fsh_parser::Parser::new(value).parse().map(|ast| match ast {
+        fsh_ast::Ast::Semicolon(mut statements) if statements.len() == 1 => statements.pop_front(),
+        ast => Some(ast),
+    });
+
+    match ast {
+        Ok(Some(fsh_ast::Ast::Statement(fsh_ast::Statement::Command(command)))) => Ok(command),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            &format!("alias {name}: the value is not a simple command: {value}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(source: &str) -> fsh_ast::Command {
+        parse("command", source).unwrap()
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut aliases = Aliases::new();
+
+        aliases.insert("ll", "ls -la").unwrap();
+        aliases.insert("g", "git").unwrap();
+
+        assert_eq!(aliases.entries(), [("g", "git"), ("ll", "ls -la")]);
+
+        assert_eq!(
+            aliases.insert("x", "ls | wc").unwrap_err().kind(),
+            &ErrorKind::InvalidInput
+        );
+        assert!(aliases.insert("x", "").is_err());
+
+        assert_eq!(aliases.remove("g").as_deref(), Some("git"));
+        assert!(aliases.remove("g").is_none());
+        assert_eq!(aliases.len(), 1);
+    }
+
+    #[test]
+    fn test_aliases_expand() {
+        let mut aliases = Aliases::new();
+
+        aliases.insert("ll", "ls -la").unwrap();
+        aliases.insert("ls", "ls -F").unwrap();
+        aliases.insert("a", "b").unwrap();
+        aliases.insert("b", "a").unwrap();
+        aliases.insert("sudo", "sudo ").unwrap();
+        aliases.insert("run", "sudo ll").unwrap();
+        aliases.insert("out", "echo hi > out.txt").unwrap();
+
+        // recursive, and an alias is not expanded twice.
+        assert_eq!(aliases.expand(command("ll src")), command("ls -F -la src"));
+        assert_eq!(aliases.expand(command("a x")), command("a x"));
+
+        // a trailing space expands the next word, only the first word otherwise.
+        assert_eq!(aliases.expand(command("sudo ll /")), command("sudo ls -F -la /"));
+        assert_eq!(aliases.expand(command("run ll")), command("sudo ls -F -la ll"));
+        assert_eq!(aliases.expand(command("echo ll")), command("echo ll"));
+
+        assert_eq!(
+            aliases.expand(command("out @2 > @1")),
+            command("echo hi > out.txt @2 > @1")
+        );
+    }
+}
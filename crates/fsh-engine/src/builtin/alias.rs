@@ -0,0 +1,129 @@
+use super::{
+    super::{ExitStatus, ShVars, State},
+    write_error, Builtin, Io,
+};
+use fsh_common::Result;
+
+/// `alias [NAME[=VALUE]...]`, defines the aliases, or prints them.
+///
+/// Lists all aliases without NAME. the status is 1 if a name to print is not an alias.
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        if args.is_empty() {
+            for (name, value) in state.aliases().entries() {
+                writeln!(io.stdout, "alias {name}={}", quote(value)).map_err(write_error)?;
+            }
+
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+
+        for arg in args {
+            if let Some((name, value)) = arg.split_once('=') {
+                state.aliases_mut().insert(name, value)?;
+
+                continue;
+            }
+
+            match state.aliases().get(arg) {
+                Some(value) => {
+                    writeln!(io.stdout, "alias {arg}={}", quote(value)).map_err(write_error)?
+                }
+                None => {
+                    writeln!(io.stderr, "alias: {arg}: not found").map_err(write_error)?;
+
+                    status = ExitStatus::FAILURE;
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn help(&self) -> &str {
+        "Define or list aliases"
+    }
+}
+
+/// `unalias [-a] NAME...`, removes the aliases, all of them with `-a`.
+///
+/// The status is 1 if a name is not an alias.
+pub struct Unalias;
+
+impl Builtin for Unalias {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        if args.first().is_some_and(|arg| arg == "-a") {
+            state.aliases_mut().clear();
+
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+
+        for name in args {
+            if state.aliases_mut().remove(name).is_none() {
+                writeln!(io.stderr, "unalias: {name}: not found").map_err(write_error)?;
+
+                status = ExitStatus::FAILURE;
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn help(&self) -> &str {
+        "Remove aliases"
+    }
+}
+
+/// Quote the value of an alias, so that the listing can be run again.
+fn quote(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{value}\"")
+    } else {
+        format!("'{value}'")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(builtin: impl Builtin, args: &[&str], state: &mut State) -> (ExitStatus, String, String) {
+        super::super::run(builtin, args, state, &mut ShVars::new()).unwrap()
+    }
+
+    #[test]
+    fn test_alias() {
+        let mut state = State::new();
+
+        run(Alias, &["ll=ls -la", "g=git"], &mut state);
+
+        assert_eq!(
+            run(Alias, &[], &mut state).1,
+            "alias g='git'\nalias ll='ls -la'\n"
+        );
+
+        let (status, stdout, stderr) = run(Alias, &["ll", "x"], &mut state);
+
+        assert_eq!(status, ExitStatus::FAILURE);
+        assert_eq!(stdout, "alias ll='ls -la'\n");
+        assert_eq!(stderr, "alias: x: not found\n");
+    }
+
+    #[test]
+    fn test_unalias() {
+        let mut state = State::new();
+
+        run(Alias, &["ll=ls -la", "g=git", "v=vim"], &mut state);
+
+        assert_eq!(run(Unalias, &["g", "x"], &mut state).0, ExitStatus::FAILURE);
+        assert_eq!(state.aliases().len(), 2);
+
+        assert_eq!(run(Unalias, &["-a"], &mut state).0, ExitStatus::SUCCESS);
+        assert!(state.aliases().is_empty());
+    }
+}
@@ -1,9 +1,346 @@
-use std::process;
-
-pub fn exit(code: i32) {
-    process::exit(code)
-}
+use super::{
+    super::{eval_file, exit_shell, ExitStatus, ShVars, State},
+    write_error, Builtin, Io,
+};
+use fsh_common::{Error, ErrorKind, Result};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+};
 
 pub fn abort() {
     process::abort()
 }
+
+/// `exit [CODE]`, the exit status of the last pipeline without CODE.
+///
+/// A CODE that is not a number exits with the status 2.
+pub struct Exit;
+
+impl Builtin for Exit {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        let code = match args.first().map(|arg| (arg, arg.parse::<i32>())) {
+            Some((_, Ok(code))) => code,
+            Some((arg, Err(_))) => {
+                writeln!(io.stderr, "exit: {arg}: numeric argument required").map_err(write_error)?;
+
+                2
+            }
+            None => state.status().code(),
+        };
+
+        exit_shell(code, state, sh_vars)
+    }
+
+    fn help(&self) -> &str {
+        "Exit the shell"
+    }
+}
+
+/// `abort`
+pub struct Abort;
+
+impl Builtin for Abort {
+    fn run(&self, _: &[String], _: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        abort();
+
+        Ok(ExitStatus::FAILURE)
+    }
+
+    fn help(&self) -> &str {
+        "Abort the shell"
+    }
+}
+
+/// `echo [-n] [-e] [ARG...]`, `-n` omits the newline, `-e` interprets the backslash escapes.
+pub struct Echo;
+
+impl Builtin for Echo {
+    fn run(&self, args: &[String], io: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        let (mut newline, mut escapes) = (true, false);
+
+        // the flags are the leading arguments made of `n` and `e` only, as `-ne`.
+        let mut args = args.iter().peekable();
+
+        while let Some(flags) = args
+            .peek()
+            .and_then(|arg| arg.strip_prefix('-'))
+            .filter(|flags| !flags.is_empty() && flags.chars().all(|c| c == 'n' || c == 'e'))
+        {
+            newline &= !flags.contains('n');
+            escapes |= flags.contains('e');
+
+            args.next();
+        }
+
+        let mut output = args.map(String::as_str).collect::<Vec<&str>>().join(" ");
+
+        if escapes {
+            let (unescaped, stop) = unescape(&output);
+
+            output = unescaped;
+            newline &= !stop;
+        }
+
+        if newline {
+            output.push('\n');
+        }
+
+        io.stdout.write_all(output.as_bytes()).map_err(write_error)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Write the arguments to the stdout"
+    }
+}
+
+/// Interpret the backslash escapes of `echo -e`.
+///
+/// # Returns
+/// The string, and if `\c` stopped the output.
+fn unescape(string: &str) -> (String, bool) {
+    let mut unescaped = String::with_capacity(string.len());
+
+    let mut chars = string.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('a') => unescaped.push('\x07'),
+            Some('b') => unescaped.push('\x08'),
+            Some('e') => unescaped.push('\x1b'),
+            Some('f') => unescaped.push('\x0c'),
+            Some('v') => unescaped.push('\x0b'),
+            Some('\\') => unescaped.push('\\'),
+            Some('c') => return (unescaped, true),
+            // `\0nnn`, up to 3 octal digits.
+            Some('0') => {
+                let mut code = 0;
+
+                for _ in 0..3 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+
+                unescaped.push(char::from_u32(code).unwrap_or_default());
+            }
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    (unescaped, false)
+}
+
+/// `pwd`, the current directory of the shell.
+pub struct Pwd;
+
+impl Builtin for Pwd {
+    fn run(&self, _: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        writeln!(io.stdout, "{}", state.current_dir().display()).map_err(write_error)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Print the current directory"
+    }
+}
+
+/// `true`, always succeeds.
+pub struct True;
+
+impl Builtin for True {
+    fn run(&self, _: &[String], _: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Do nothing, successfully"
+    }
+}
+
+/// `false`, always fails.
+pub struct False;
+
+impl Builtin for False {
+    fn run(&self, _: &[String], _: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        Ok(ExitStatus::FAILURE)
+    }
+
+    fn help(&self) -> &str {
+        "Do nothing, unsuccessfully"
+    }
+}
+
+/// `source FILE [ARG...]`, or `. FILE [ARG...]`, runs the file in the current shell.
+///
+/// The arguments are the positional parameters of the file, the previous ones are set back after it.
+pub struct Source;
+
+impl Builtin for Source {
+    fn run(&self, args: &[String], _: &mut Io, state: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        let (path, args) = args.split_first().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "source: a file name is required")
+        })?;
+
+        if args.is_empty() {
+            return eval_file(Path::new(path), state, sh_vars);
+        }
+
+        let saved = sh_vars.args();
+
+        sh_vars.set_args(args);
+
+        let result = eval_file(Path::new(path), state, sh_vars);
+
+        sh_vars.set_args(&saved);
+
+        result
+    }
+
+    fn help(&self) -> &str {
+        "Run a file in the current shell"
+    }
+}
+
+/// `help [NAME...]`, lists the builtins of the registry without NAME.
+pub struct Help;
+
+impl Builtin for Help {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        let builtins = state.builtins();
+
+        let names = if args.is_empty() {
+            builtins.names()
+        } else {
+            args.iter().map(String::as_str).collect()
+        };
+
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+
+        for name in names {
+            let builtin = builtins.get(name).ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, &format!("{name}: no such builtin"))
+            })?;
+
+            writeln!(io.stdout, "{name:<width$}  {}", builtin.help()).map_err(write_error)?;
+        }
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "List the builtins"
+    }
+}
+
+/// `type NAME...`, tells if each name is an alias, a builtin or a command in `$PATH`.
+///
+/// The status is 1 if a name is not found.
+pub struct Type;
+
+impl Builtin for Type {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        let mut status = ExitStatus::SUCCESS;
+
+        for name in args {
+            if let Some(value) = state.aliases().get(name) {
+                writeln!(io.stdout, "{name} is aliased to `{value}`").map_err(write_error)?;
+            } else if state.builtins().contains(name) {
+                writeln!(io.stdout, "{name} is a shell builtin").map_err(write_error)?;
+            } else if let Some(path) = find_command(name, sh_vars) {
+                writeln!(io.stdout, "{name} is {}", path.display()).map_err(write_error)?;
+            } else {
+                writeln!(io.stderr, "type: {name}: not found").map_err(write_error)?;
+
+                status = ExitStatus::FAILURE;
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn help(&self) -> &str {
+        "Tell if a name is an alias, a builtin or a command"
+    }
+}
+
+/// Find a command in `$PATH`, as the process would be spawned.
+fn find_command(name: &str, sh_vars: &ShVars) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name)).filter(|path| path.is_file());
+    }
+
+    let paths = match sh_vars.get("PATH") {
+        Ok(paths) => paths.to_string(),
+        Err(_) => env::var("PATH").ok()?,
+    };
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(builtin: impl Builtin, args: &[&str], state: &mut State) -> (ExitStatus, String) {
+        let (status, stdout, _) = super::super::run(builtin, args, state, &mut ShVars::new()).unwrap();
+
+        (status, stdout)
+    }
+
+    #[test]
+    fn test_echo() {
+        let mut state = State::new();
+
+        assert_eq!(run(Echo, &["a", "b"], &mut state).1, "a b\n");
+        assert_eq!(run(Echo, &[], &mut state).1, "\n");
+        assert_eq!(run(Echo, &["-n", "a"], &mut state).1, "a");
+        assert_eq!(run(Echo, &["-e", "a\\tb\\\\"], &mut state).1, "a\tb\\\n");
+        assert_eq!(run(Echo, &["-ne", "\\0101\\n"], &mut state).1, "A\n");
+        assert_eq!(run(Echo, &["-e", "a\\cb"], &mut state).1, "a");
+        // escapes are kept without -e, and a word that is not a flag ends the flags.
+        assert_eq!(run(Echo, &["-x", "a\\n"], &mut state).1, "-x a\\n\n");
+        assert_eq!(run(Echo, &["a", "-n"], &mut state).1, "a -n\n");
+    }
+
+    #[test]
+    fn test_pwd() {
+        let mut state = State::new();
+
+        *state.current_dir_mut() = PathBuf::from("/tmp");
+
+        assert_eq!(run(Pwd, &[], &mut state), (ExitStatus::SUCCESS, "/tmp\n".to_string()));
+    }
+
+    #[test]
+    fn test_true_false() {
+        let mut state = State::new();
+
+        assert_eq!(run(True, &[], &mut state).0, ExitStatus::SUCCESS);
+        assert_eq!(run(False, &[], &mut state).0, ExitStatus::FAILURE);
+    }
+}
@@ -1,4 +1,83 @@
+use fsh_common::{Error, ErrorKind};
+
+mod registry;
+
+pub mod alias;
+
 pub mod common;
 
-#[cfg(any(target_os = "linux", target_os = "macos", target_os = "unix"))]
-pub mod unix;
\ No newline at end of file
+pub mod vars;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod unix;
+
+pub use registry::*;
+
+/// The builtins of the shell, registered in a new state.
+pub(crate) fn defaults() -> Builtins {
+    let mut builtins = Builtins::new();
+
+    builtins.register("exit", common::Exit);
+    builtins.register("abort", common::Abort);
+    builtins.register("help", common::Help);
+    builtins.register("type", common::Type);
+    builtins.register("echo", common::Echo);
+    builtins.register("pwd", common::Pwd);
+    builtins.register("true", common::True);
+    builtins.register("false", common::False);
+    builtins.register("source", common::Source);
+    builtins.register(".", common::Source);
+    builtins.register("export", vars::Export);
+    builtins.register("unset", vars::Unset);
+    builtins.register("set", vars::Set);
+    builtins.register("env", vars::Env);
+    builtins.register("alias", alias::Alias);
+    builtins.register("unalias", alias::Unalias);
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        builtins.register("cd", unix::Cd);
+        builtins.register("jobs", unix::Jobs);
+        builtins.register("fg", unix::Fg);
+        builtins.register("bg", unix::Bg);
+        builtins.register("disown", unix::Disown);
+        builtins.register("trap", unix::TrapBuiltin);
+    }
+
+    builtins
+}
+
+/// The error of a builtin that fails to write its output, as when the reader of its pipe is gone.
+pub(crate) fn write_error(_: std::io::Error) -> Error {
+    Error::new(ErrorKind::BrokenPipe, "Failed to write the output")
+}
+
+/// Run a builtin with the arguments, as a test.
+///
+/// # Returns
+/// The exit status, the stdout and the stderr of the builtin
+#[cfg(test)]
+fn run(
+    builtin: impl Builtin,
+    args: &[&str],
+    state: &mut super::State,
+    sh_vars: &mut super::ShVars,
+) -> fsh_common::Result<(super::ExitStatus, String, String)> {
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+
+    let mut io = Io {
+        stdin: &mut std::io::empty(),
+        stdout: &mut stdout,
+        stderr: &mut stderr,
+    };
+
+    let status = builtin.run(&args, &mut io, state, sh_vars)?;
+
+    Ok((
+        status,
+        String::from_utf8(stdout).unwrap(),
+        String::from_utf8(stderr).unwrap(),
+    ))
+}
@@ -0,0 +1,193 @@
+use fsh_common::Result;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    rc::Rc,
+};
+
+use super::super::{ExitStatus, ShVars, State};
+
+/// The stdio of a builtin.
+///
+/// The stdio is the redirects of the command, the pipe of its stage, or the stdio of the shell.
+pub struct Io<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+/// A builtin command, it runs in the shell instead of a process.
+///
+/// # Example
+/// ```
+/// use fsh_common::Result;
+/// use fsh_engine::{builtin::{Builtin, Io}, ExitStatus, ShVars, State};
+///
+/// struct Hello;
+///
+/// impl Builtin for Hello {
+///     fn run(&self, args: &[String], io: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+///         writeln!(io.stdout, "hello {}", args.join(" ")).ok();
+///
+///         Ok(ExitStatus::SUCCESS)
+///     }
+///
+///     fn help(&self) -> &str {
+///         "Say hello"
+///     }
+/// }
+///
+/// let mut state = State::new();
+///
+/// state.builtins_mut().register("hello", Hello);
+///
+/// assert!(state.builtins().contains("hello"));
+/// ```
+pub trait Builtin {
+    /// Run the builtin.
+    ///
+    /// # Arguments
+    /// `args` - The arguments, without the name of the builtin
+    ///
+    /// `io` - The stdin, the stdout and the stderr of the builtin
+    ///
+    /// `state` - The current fsh state
+    ///
+    /// `sh_vars` - The shell variables
+    ///
+    /// # Returns
+    /// The exit status of the builtin, an error is reported by the shell and has the status 1.
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut Io,
+        state: &mut State,
+        sh_vars: &mut ShVars,
+    ) -> Result<ExitStatus>;
+
+    /// A one-line description, as listed by `help`.
+    fn help(&self) -> &str {
+        ""
+    }
+}
+
+/// The builtin registry, the builtins by name.
+///
+/// A builtin takes precedence over a command of the same name.
+#[derive(Default, Clone)]
+pub struct Builtins {
+    builtins: BTreeMap<String, Rc<dyn Builtin>>,
+}
+
+impl std::fmt::Debug for Builtins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.builtins.keys()).finish()
+    }
+}
+
+impl Builtins {
+    /// Create a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a builtin, it replaces the builtin of the same name.
+    ///
+    /// # Returns
+    /// The replaced builtin
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        builtin: impl Builtin + 'static,
+    ) -> Option<Rc<dyn Builtin>> {
+        self.builtins.insert(name.into(), Rc::new(builtin))
+    }
+
+    /// Remove a builtin, the name runs a command again.
+    pub fn unregister(&mut self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.remove(name)
+    }
+
+    /// Get a builtin
+    ///
+    /// The builtin is shared, so it can run with the state that holds the registry.
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.get(name).cloned()
+    }
+
+    /// Check if a name is a builtin
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    /// Get the names of the builtins, in alphabetical order.
+    pub fn names(&self) -> Vec<&str> {
+        self.builtins.keys().map(String::as_str).collect()
+    }
+
+    /// Get all builtins, in alphabetical order.
+    pub fn entries(&self) -> Vec<(&str, &dyn Builtin)> {
+        self.builtins
+            .iter()
+            .map(|(name, builtin)| (name.as_str(), builtin.as_ref()))
+            .collect()
+    }
+
+    /// Check if the registry is empty
+    pub fn is_empty(&self) -> bool {
+        self.builtins.is_empty()
+    }
+
+    /// Get the number of builtins
+    pub fn len(&self) -> usize {
+        self.builtins.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Status(i32);
+
+    impl Builtin for Status {
+        fn run(&self, _: &[String], _: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+            Ok(ExitStatus::new(self.0))
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let mut builtins = Builtins::new();
+
+        assert!(builtins.is_empty());
+
+        assert!(builtins.register("b", Status(1)).is_none());
+        assert!(builtins.register("a", Status(2)).is_none());
+        assert!(builtins.register("b", Status(3)).is_some());
+
+        assert_eq!(builtins.names(), ["a", "b"]);
+        assert_eq!(builtins.entries()[1].1.help(), "");
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+
+        let mut io = Io {
+            stdin: &mut std::io::empty(),
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+        };
+
+        let status = builtins
+            .get("b")
+            .unwrap()
+            .run(&[], &mut io, &mut State::new(), &mut ShVars::new())
+            .unwrap();
+
+        assert_eq!(status.code(), 3);
+
+        builtins.unregister("a");
+
+        assert!(!builtins.contains("a"));
+        assert_eq!(builtins.len(), 1);
+    }
+}
@@ -1,6 +1,97 @@
-use super::super::State;
+use super::{
+    super::{job::JobState, signal::Trap, ExitStatus, ShVars, State},
+    write_error, Builtin, Io,
+};
 use fsh_common::*;
-use std::ffi::OsStr;
+use std::{ffi::OsStr, io::Write};
+
+/// `cd [DIR]`, the root directory without DIR.
+pub struct Cd;
+
+impl Builtin for Cd {
+    fn run(&self, args: &[String], _: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        cd(args.first().map_or("/", String::as_str), state)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Change the current directory"
+    }
+}
+
+/// `jobs`
+pub struct Jobs;
+
+impl Builtin for Jobs {
+    fn run(&self, _: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        jobs(state, io.stdout)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "List the jobs"
+    }
+}
+
+/// `fg [JOB]`
+pub struct Fg;
+
+impl Builtin for Fg {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        fg(args.first().map(String::as_str), state, io.stdout)
+    }
+
+    fn help(&self) -> &str {
+        "Continue a job in the foreground"
+    }
+}
+
+/// `bg [JOB]`
+pub struct Bg;
+
+impl Builtin for Bg {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        bg(args.first().map(String::as_str), state, io.stdout)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Continue a stopped job in the background"
+    }
+}
+
+/// `disown [JOB]`
+pub struct Disown;
+
+impl Builtin for Disown {
+    fn run(&self, args: &[String], _: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        disown(args.first().map(String::as_str), state)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Remove a job from the job table"
+    }
+}
+
+/// `trap [COMMAND] [SIGNAL...]`
+pub struct TrapBuiltin;
+
+impl Builtin for TrapBuiltin {
+    fn run(&self, args: &[String], io: &mut Io, state: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+        trap(args, state, io.stdout)?;
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Run a command on a signal, on exit or on error"
+    }
+}
 
 /// Change the current directory
 ///
@@ -28,6 +119,147 @@ pub fn cd<S: AsRef<OsStr> + ?Sized>(p: &S, state: &mut State) -> fsh_common::Res
     Ok(())
 }
 
+/// List the jobs, the jobs that are done are listed once, then removed.
+///
+/// # Errors
+/// `Kind::BrokenPipe` - If the list can not be written
+pub fn jobs(state: &mut State, out: &mut dyn Write) -> Result<()> {
+    state.jobs_mut().update();
+
+    let ids = state
+        .jobs()
+        .entries()
+        .iter()
+        .map(|job| job.id())
+        .collect::<Vec<usize>>();
+
+    for id in ids {
+        if let Some(line) = state.jobs().format(id) {
+            writeln!(out, "{line}").map_err(write_error)?;
+        }
+
+        if state.jobs().is_done(id) {
+            state.remove_job(id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Continue a job in the foreground, and wait for it.
+///
+/// # Arguments
+/// `spec` - The job spec, `%n`, the current job if `None`
+///
+/// `state` - The current fsh state
+///
+/// `out` - The stdout of the builtin
+///
+/// # Errors
+/// `Kind::NotFound` - If there is no such job
+pub fn fg(spec: Option<&str>, state: &mut State, out: &mut dyn Write) -> Result<ExitStatus> {
+    let id = state.jobs().find(spec)?;
+
+    if let Some(job) = state.jobs().get(id) {
+        writeln!(out, "{}", job.command()).map_err(write_error)?;
+    }
+
+    state.jobs_mut().resume(id, true)?;
+
+    let job_state = state.jobs_mut().wait(id).unwrap_or(JobState::Done(ExitStatus::FAILURE));
+
+    if let JobState::Done(_) = job_state {
+        state.remove_job(id);
+    }
+
+    Ok(job_state.status())
+}
+
+/// Continue a stopped job in the background.
+///
+/// # Arguments
+/// `spec` - The job spec, `%n`, the current job if `None`
+///
+/// `state` - The current fsh state
+///
+/// `out` - The stdout of the builtin
+///
+/// # Errors
+/// `Kind::NotFound` - If there is no such job
+pub fn bg(spec: Option<&str>, state: &mut State, out: &mut dyn Write) -> Result<()> {
+    let id = state.jobs().find(spec)?;
+
+    state.jobs_mut().resume(id, false)?;
+
+    if let Some(job) = state.jobs().get(id) {
+        writeln!(out, "[{id}]+ {} &", job.command().trim_end_matches(" &")).map_err(write_error)?;
+    }
+
+    Ok(())
+}
+
+/// Remove a job from the job table, the job keeps running.
+///
+/// # Arguments
+/// `spec` - The job spec, `%n`, the current job if `None`
+///
+/// `state` - The current fsh state
+///
+/// # Errors
+/// `Kind::NotFound` - If there is no such job
+pub fn disown(spec: Option<&str>, state: &mut State) -> Result<()> {
+    let id = state.jobs().find(spec)?;
+
+    state.remove_job(id);
+
+    Ok(())
+}
+
+/// Set, reset or list the traps.
+///
+/// - `trap` lists the traps.
+/// - `trap 'command' SIGNAL...` runs the command when the shell receives the signals, or on `EXIT` and `ERR`.
+/// - `trap '' SIGNAL...` ignores the signals.
+/// - `trap - SIGNAL...` or `trap SIGNAL` resets the signals.
+///
+/// # Arguments
+/// `args` - The arguments of the builtin
+///
+/// `state` - The current fsh state
+///
+/// `out` - The stdout of the builtin
+///
+/// # Errors
+/// `Kind::InvalidInput` - If a signal is invalid, or can not be trapped
+pub fn trap(args: &[String], state: &mut State, out: &mut dyn Write) -> Result<()> {
+    let (command, names) = match args {
+        [] => {
+            for (trap, command) in state.traps().entries() {
+                writeln!(out, "trap -- '{}' {}", command.replace('\'', "'\\''"), trap.name())
+                    .map_err(write_error)?;
+            }
+
+            return Ok(());
+        }
+        [name] => ("-", std::slice::from_ref(name)),
+        [command, names @ ..] => (command.as_str(), names),
+    };
+
+    for name in names {
+        let trap = Trap::parse(name).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, &format!("{name}: invalid signal specification"))
+        })?;
+
+        if command == "-" {
+            state.traps_mut().reset(trap);
+        } else {
+            state.traps_mut().set(trap, command)?;
+        }
+    }
+
+    Ok(())
+}
+
 
 /// Analyze the path
 fn analyze<A: AsRef<OsStr> + ?Sized, B: AsRef<OsStr> + ?Sized>(
@@ -38,7 +270,7 @@ fn analyze<A: AsRef<OsStr> + ?Sized, B: AsRef<OsStr> + ?Sized>(
 
     let target = std::path::Path::new(target);
 
-    if current.is_dir() == false {
+    if !current.is_dir() {
         Err(Error::new(ErrorKind::NotADirectory, "not a directory"))?
     }
 
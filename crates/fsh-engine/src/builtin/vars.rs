@@ -0,0 +1,165 @@
+use super::{
+    super::{ExitStatus, ShVars, State},
+    write_error, Builtin, Io,
+};
+use fsh_common::{Error, ErrorKind, Result};
+
+/// `export [NAME[=VALUE]...]`, exports the variables to the environment of the commands.
+///
+/// Lists the exported variables without NAME.
+pub struct Export;
+
+impl Builtin for Export {
+    fn run(&self, args: &[String], io: &mut Io, _: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        if args.is_empty() {
+            for (key, value) in sh_vars.exports() {
+                writeln!(io.stdout, "export {key}={value}").map_err(write_error)?;
+            }
+
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        for arg in args {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (arg.as_str(), None),
+            };
+
+            if !is_name(key) {
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    &format!("export: {arg}: not a valid name"),
+                ))?
+            }
+
+            if let Some(value) = value {
+                sh_vars.insert(key, value);
+            }
+
+            sh_vars.export(key);
+        }
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Export variables to the environment"
+    }
+}
+
+/// Check if the key is the name of a variable, a letter or `_`, then letters, digits or `_`.
+fn is_name(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `unset NAME...`, removes the variables.
+pub struct Unset;
+
+impl Builtin for Unset {
+    fn run(&self, args: &[String], _: &mut Io, _: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        for key in args {
+            sh_vars.remove(key);
+        }
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Remove variables"
+    }
+}
+
+/// `set`, lists all the variables, in order of name.
+pub struct Set;
+
+impl Builtin for Set {
+    fn run(&self, args: &[String], io: &mut Io, _: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        if let Some(arg) = args.first() {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("set: {arg}: invalid option"),
+            ))?
+        }
+
+        let mut entries = sh_vars.entries().into_iter().collect::<Vec<(&String, &String)>>();
+
+        entries.sort();
+
+        for (key, value) in entries {
+            writeln!(io.stdout, "{key}={value}").map_err(write_error)?;
+        }
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "List the variables"
+    }
+}
+
+/// `env`, prints the environment of the commands, the exported variables.
+pub struct Env;
+
+impl Builtin for Env {
+    fn run(&self, _: &[String], io: &mut Io, _: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+        for (key, value) in sh_vars.exports() {
+            writeln!(io.stdout, "{key}={value}").map_err(write_error)?;
+        }
+
+        Ok(ExitStatus::SUCCESS)
+    }
+
+    fn help(&self) -> &str {
+        "Print the exported environment"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(builtin: impl Builtin, args: &[&str], sh_vars: &mut ShVars) -> Result<String> {
+        super::super::run(builtin, args, &mut State::new(), sh_vars).map(|(_, stdout, _)| stdout)
+    }
+
+    #[test]
+    fn test_export_unset() {
+        let mut sh_vars = ShVars::new();
+
+        sh_vars.insert("b", "2");
+
+        run(Export, &["a=1", "b", "c"], &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("a").unwrap(), "1");
+        assert!(sh_vars.is_exported("c"));
+
+        assert_eq!(run(Export, &[], &mut sh_vars).unwrap(), "export a=1\nexport b=2\n");
+        assert_eq!(run(Env, &[], &mut sh_vars).unwrap(), "a=1\nb=2\n");
+
+        run(Unset, &["a", "d"], &mut sh_vars).unwrap();
+
+        assert!(!sh_vars.exists("a"));
+        assert_eq!(run(Env, &[], &mut sh_vars).unwrap(), "b=2\n");
+
+        for arg in ["=1", "x y", "/root", "1a=1", "a-b", "é=1"] {
+            assert_eq!(
+                run(Export, &[arg], &mut sh_vars).unwrap_err().kind(),
+                &ErrorKind::InvalidInput
+            );
+        }
+
+        assert_eq!(run(Env, &[], &mut sh_vars).unwrap(), "b=2\n");
+    }
+
+    #[test]
+    fn test_set() {
+        let mut sh_vars = ShVars::new();
+
+        sh_vars.insert("b", "2");
+        sh_vars.insert("a", "1");
+
+        assert_eq!(run(Set, &[], &mut sh_vars).unwrap(), "a=1\nb=2\n");
+        assert!(run(Set, &["-x"], &mut sh_vars).is_err());
+    }
+}
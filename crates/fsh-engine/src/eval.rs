@@ -1,20 +1,41 @@
 use fsh_common::{Error, ErrorKind, Result};
 
 use std::{
-    fs,
+    env, fs,
+    io::{self, Seek, Write},
     os::{
-        fd::IntoRawFd,
-        unix::{
-            io::{AsRawFd, FromRawFd},
-            process::CommandExt,
-        },
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::{fs::OpenOptionsExt, process::CommandExt},
     },
+    path::Path,
     process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{
+    builtin::{Builtin, Io}, extract::*, module,
+    signal::{self, Trap}, pipe::{Pipe, Stdio}, ExitStatus, ShVars, State,
+    RESERVEDWORD_SHELL_VARIABLE_FSH_PATH, RESERVEDWORD_SHELL_VARIABLE_LAST_BACKGROUND,
+    RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS, RESERVEDWORD_SHELL_VARIABLE_STATUS,
 };
 
-use super::{extract::*, pipe::Pipe, ShVars, State};
+/// Evaluate the AST.
+///
+/// An engine error is a failure of the statement, `$?` is set to 1.
+///
+/// # Returns
+/// The exit status of the last pipeline.
+pub fn eval(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+    if let Err(err) = eval_ast(ast, state, sh_vars) {
+        set_pipe_status(state, sh_vars, vec![ExitStatus::FAILURE]);
+
+        Err(err)?
+    }
+
+    Ok(state.status())
+}
 
-pub fn eval(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+fn eval_ast(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
     match ast {
         //
         //
@@ -28,16 +49,31 @@ pub fn eval(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Resul
         //
         //
         //
-        fsh_ast::Ast::Pipe(mut pipe) => {
-            *state.pipe_mut() = Pipe::open();
+        fsh_ast::Ast::Pipe(pipe) => {
+            eval_pipe(pipe, state, sh_vars)?;
+
+            eval_err_trap(state, sh_vars)?;
+        }
 
-            while let Some(command) = pipe.pop_front() {
-                eval_command(command, state, sh_vars, pipe.is_empty())?;
+        //
+        //
+        //
+        fsh_ast::Ast::Negated(ast) => {
+            // a negated pipeline does not run the ERR trap.
+            match *ast {
+                fsh_ast::Ast::Pipe(pipe) => eval_pipe(pipe, state, sh_vars)?,
+                fsh_ast::Ast::Statement(fsh_ast::Statement::Command(command)) => {
+                    eval_pipe(fsh_ast::Pipe::from([command].as_slice()), state, sh_vars)?
+                }
+                ast => {
+                    eval(ast, state, sh_vars)?;
+                }
             }
 
-            state.pipe_mut().close()?;
+            // $PIPESTATUS keeps the statuses of the commands.
+            let status = state.status().negate();
 
-            state.handler_mut().wait();
+            set_status(state, sh_vars, status);
         }
 
         //
@@ -48,9 +84,9 @@ pub fn eval(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Resul
             //
             //
             fsh_ast::Statement::Command(command) => {
-                eval_command(command, state, sh_vars, true)?;
+                eval_pipe(fsh_ast::Pipe::from([command].as_slice()), state, sh_vars)?;
 
-                state.handler_mut().wait();
+                eval_err_trap(state, sh_vars)?;
             }
 
             //
@@ -59,206 +95,1678 @@ pub fn eval(ast: fsh_ast::Ast, state: &mut State, sh_vars: &mut ShVars) -> Resul
             fsh_ast::Statement::Assign(assign) => {
                 eval_assign(assign, sh_vars)?;
             }
+
+            //
+            //
+            //
+            fsh_ast::Statement::Match(r#match) => {
+                eval_match(r#match, state, sh_vars)?;
+            }
+
+            //
+            //
+            //
+            fsh_ast::Statement::Import(import) => {
+                eval_import(import, state, sh_vars)?;
+            }
+
+            //
+            //
+            //
+            fsh_ast::Statement::Try(r#try) => {
+                eval_try(r#try, state, sh_vars)?;
+            }
         },
     }
 
     Ok(())
 }
 
-fn eval_command(
-    command: fsh_ast::Command,
-    state: &mut State,
-    sh_vars: &mut ShVars,
-    is_last: bool,
-) -> Result<()> {
-    let name = extract_command_name(&command, sh_vars)?;
-
-    let args = extract_command_args(&command, sh_vars)?;
-
-    let redirects = command.redirects;
+/// Run the body of the first arm with a pattern matching the value.
+fn eval_match(r#match: fsh_ast::Match, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let value = extract_match_value(&r#match, sh_vars)?;
 
-    eval_builtin_command(&name, &args, state).or_else(|_| {
-        eval_process_command(
-            name,
-            args,
-            redirects,
-            command.background,
-            state,
-            sh_vars,
-            is_last,
-        )
-    })?;
+    for arm in r#match.arms {
+        for pattern in &arm.patterns {
+            if is_match(pattern, &value)? {
+                return eval(arm.body, state, sh_vars).map(|_| ());
+            }
+        }
+    }
 
     Ok(())
 }
 
-//
-//
-//
-fn eval_builtin_command(name: &String, args: &Vec<String>, state: &mut State) -> Result<()> {
-    match name.as_ref() {
-        //
-        // Unix builtins
-        //
-        "cd" => {
-            let arg = match args.first() {
-                Some(arg) => arg,
-                None => "/",
-            };
+/// Why a try block failed.
+enum Failure {
+    Error(Error),
+    Status(i32),
+}
+
+/// Run the try block, the catch block if it failed, then the finally block.
+///
+/// The try block fails on an engine error, or on a statement that exits with a non-zero status.
+/// The variable of the catch block is the message, with `$err::kind`, `$err::message` and `$err::status`.
+///
+/// Without a catch block, an engine error is returned after the finally block.
+fn eval_try(r#try: fsh_ast::Try, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let statements = match *r#try.body {
+        fsh_ast::Ast::Semicolon(statements) => statements,
+        ast => [ast].into(),
+    };
+
+    let mut failure = None;
+
+    for statement in statements {
+        set_status(state, sh_vars, ExitStatus::SUCCESS);
 
-            super::builtin::unix::cd(arg, state)?;
+        if let Err(err) = eval(statement, state, sh_vars) {
+            failure = Some(Failure::Error(err));
+            break;
         }
 
-        //
-        // Common builtins
-        //
-        "abort" => {
-            super::builtin::common::abort();
+        if !state.status().success() {
+            failure = Some(Failure::Status(state.status().code()));
+            break;
         }
+    }
 
-        "exit" => {
-            let code = match args.first() {
-                Some(arg) => arg.parse().unwrap_or(0),
-                None => 0,
+    let result = match (failure, r#try.catch) {
+        (Some(failure), Some(catch)) => {
+            let (kind, message, status) = match failure {
+                Failure::Error(err) => (*err.kind(), err.message().to_string(), 1),
+                Failure::Status(status) => (
+                    ErrorKind::Failure,
+                    format!("Command exited with status {status}"),
+                    status,
+                ),
             };
 
-            super::builtin::common::exit(code);
+            if let Some(fsh_ast::Expr::Ident(name)) = catch.ident {
+                sh_vars.insert(&name, &message);
+                sh_vars.insert(format!("{name}::kind"), kind.as_str());
+                sh_vars.insert(format!("{name}::message"), message);
+                sh_vars.insert(format!("{name}::status"), status.to_string());
+            }
+
+            set_status(state, sh_vars, ExitStatus::SUCCESS);
+
+            eval(*catch.body, state, sh_vars).map(|_| ())
+        }
+        (Some(Failure::Error(err)), None) => Err(err),
+        _ => Ok(()),
+    };
+
+    if let Some(finally) = r#try.finally {
+        eval(*finally, state, sh_vars)?;
+    }
+
+    result
+}
+
+/// Import a module, once per session.
+///
+/// The module runs with a copy of the shell variables, the variables it assigns are copied back as `namespace::name`.
+fn eval_import(import: fsh_ast::Import, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let path = extract_import_path(&import, sh_vars)?;
+
+    let path = state.modules().resolve(
+        &path,
+        state.current_dir(),
+        sh_vars.get(RESERVEDWORD_SHELL_VARIABLE_FSH_PATH).ok(),
+    )?;
+
+    if state.modules().is_imported(&path) {
+        return Ok(());
+    }
+
+    state.modules_mut().enter(path.to_owned())?;
+
+    let result = eval_module(&path, state, sh_vars);
+
+    state.modules_mut().leave(result.is_ok());
+
+    result
+}
+
+fn eval_module(path: &Path, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let source = fs::read_to_string(path).map_err(|_| {
+        Error::new(
+            ErrorKind::NotFound,
+            &format!("Failed to read the module: {}", path.display()),
+        )
+    })?;
+
+    let ast = parse_file(&source, path)?;
+
+    let names = module::variables(&ast);
+
+    let mut module_vars = sh_vars.to_owned();
+
+    eval(ast, state, &mut module_vars)?;
+
+    let namespace = module::namespace(path);
+
+    // the modules imported by the module are imported for the whole session.
+    for (key, value) in module_vars.entries() {
+        if key.contains("::") {
+            sh_vars.insert(key.to_string(), value.to_string());
         }
+    }
 
-        _ => Err(Error::INTERNAL)?,
+    for name in names {
+        if let Ok(value) = module_vars.get(&name) {
+            sh_vars.insert(format!("{namespace}::{name}"), value);
+        }
     }
 
     Ok(())
 }
 
-//
-//
-//
-fn eval_process_command(
-    name: String,
-    args: Vec<String>,
-    redirects: Vec<fsh_ast::Redirect>,
-    is_background: bool,
-    state: &mut State,
-    sh_vars: &mut ShVars,
-    is_last: bool,
-) -> Result<()> {
-    // create a new process command
-    let mut ps_command = process::Command::new(&name);
-    
-    // set the arguments
-    ps_command.args(args);
+/// Run a script file in the current state and shell variables, as `source`.
+///
+/// A relative path is relative to the current directory, a shebang line is ignored.
+/// The imports of the script are relative to its directory.
+///
+/// # Returns
+/// The exit status of the last pipeline of the script.
+///
+/// # Errors
+/// `ErrorKind::NotFound` - If the file can not be read
+///
+/// The first syntax error of the script, as `path:line:column: message`.
+pub fn eval_file(path: &Path, state: &mut State, sh_vars: &mut ShVars) -> Result<ExitStatus> {
+    let path = state.current_dir().join(path);
+
+    let mut source = fs::read_to_string(&path).map_err(|_| {
+        Error::new(
+            ErrorKind::NotFound,
+            &format!("Failed to read the script: {}", path.display()),
+        )
+    })?;
 
-    // set the stdin, stdout, and stderr
-    let (stdin, stdout, stderr) = set_command_stdio(state);
+    // the newline is kept, so the lines of the diagnostics are the lines of the file.
+    if source.starts_with("#!") {
+        source.replace_range(..source.find('\n').unwrap_or(source.len()), "");
+    }
 
-    ps_command.stdin(stdin).stdout(stdout).stderr(stderr);
+    let ast = parse_file(&source, &path)?;
 
-    if is_last {
-        ps_command.stdout(process::Stdio::inherit());
+    state
+        .modules_mut()
+        .enter_script(path.canonicalize().unwrap_or(path));
+
+    let result = eval(ast, state, sh_vars);
+
+    state.modules_mut().leave(false);
+
+    result
+}
+
+/// Parse the source of a file, the first syntax error is reported as `path:line:column: message`.
+fn parse_file(source: &str, path: &Path) -> Result<fsh_ast::Ast> {
+    let (ast, diagnostics) = fsh_parser::Parser::new(source).parse_with_diagnostics();
+
+    if let Some(diagnostic) = diagnostics.into_iter().next() {
+        Err(Error::new(
+            *diagnostic.error().kind(),
+            &format!(
+                "{}:{}: {}",
+                path.display(),
+                diagnostic.location(),
+                diagnostic.error().message()
+            ),
+        ))?
     }
 
-    // set the environment variables
-    ps_command.envs(sh_vars.entries());
+    Ok(ast)
+}
 
-    // set the current directory
-    ps_command.current_dir(state.current_dir());
+/// Run the commands of the traps of the signals received since the last call.
+pub fn eval_pending_traps(state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let signals = state
+        .traps()
+        .entries()
+        .into_iter()
+        .filter_map(|(trap, _)| match trap {
+            Trap::Signal(signal) => Some(signal),
+            _ => None,
+        })
+        .collect::<Vec<libc::c_int>>();
 
-    // set the pre-execution closure
-    unsafe {
-        ps_command.pre_exec(move || {
-            for redirect in redirects.to_owned() {
-                let left = match redirect.left.to_owned() {
-                    fsh_ast::Expr::FD(fd) => fd,
-                    _ => {
-                        todo!()
-                    }
-                };
-
-                let right = match redirect.right.to_owned() {
-                    fsh_ast::Expr::String(string) => fs::File::options()
-                        .create(true)
-                        .write(true)
-                        .read(true)
-                        .open(string)
-                        .unwrap()
-                        .into_raw_fd(),
-
-                    fsh_ast::Expr::Ident(_) => {
-                        todo!()
-                    }
-                    fsh_ast::Expr::Number(number) => fs::File::options()
-                        .create(true)
-                        .write(true)
-                        .read(true)
-                        .open(number.to_string())
-                        .unwrap()
-                        .into_raw_fd(),
-                    fsh_ast::Expr::FD(fd) => fd,
-                };
-
-                match redirect.operator {
-                    fsh_ast::RedirectOperator::Gt => {
-                        libc::dup2(right, left);
-                    }
-                    fsh_ast::RedirectOperator::Lt => {
-                        libc::dup2(right, left);
-                    }
-                }
-            }
+    for signal in signals {
+        if signal::take(signal) {
+            eval_trap(Trap::Signal(signal), state, sh_vars)?;
+        }
+    }
 
-            Ok(())
-        });
+    Ok(())
+}
+
+/// Run the command of the ERR trap, if the last pipeline failed.
+fn eval_err_trap(state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    if !state.status().success() {
+        eval_trap(Trap::Err, state, sh_vars)?;
+    }
+
+    eval_pending_traps(state, sh_vars)
+}
+
+/// Exit the shell with the code, after the EXIT trap.
+///
+/// The end of a script, the end of the input of the interactive shell and `exit` all exit here.
+/// An error of the trap is written to the stderr, `exit` in the trap sets the code.
+pub fn exit_shell(code: i32, state: &mut State, sh_vars: &mut ShVars) -> ! {
+    if let Err(err) = eval_exit_trap(state, sh_vars) {
+        eprintln!("fsh: {}: {}", err.kind().as_str2(), err.message());
+    }
+
+    process::exit(code)
+}
+
+/// Run the EXIT trap, it is reset so it runs once.
+fn eval_exit_trap(state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let result = eval_trap(Trap::Exit, state, sh_vars);
+
+    state.traps_mut().reset(Trap::Exit);
+
+    result
+}
+
+/// Run the command of a trap.
+///
+/// The command does not change `$?`, and the traps do not run while a trap command runs.
+pub(crate) fn eval_trap(trap: Trap, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let command = match state.traps().get(trap) {
+        Some(command) if !command.is_empty() => command.to_string(),
+        _ => return Ok(()),
     };
 
-    // spawn the process
-    let child = ps_command.spawn().map_err(|err| {
-        if err.kind() == std::io::ErrorKind::NotFound {
-            Error::new(ErrorKind::NotFound, "Command not found")
-        } else {
-            Error::new(ErrorKind::EngineError, "Command execution failed")
+    if !state.traps_mut().enter() {
+        return Ok(());
+    }
+
+    let status = state.status();
+
+    let result = fsh_parser::Parser::new(&command)
+        .parse()
+        .and_then(|ast| eval(ast, state, sh_vars));
+
+    state.traps_mut().leave();
+
+    set_status(state, sh_vars, status);
+
+    result.map(|_| ())
+}
+
+/// How a command of a pipeline ran.
+enum Stage {
+    /// A process of the job of the pipeline.
+    Process(u32),
+
+    /// A builtin, with its status.
+    Done(ExitStatus),
+}
+
+/// Evaluate a pipeline, as a job.
+///
+/// The processes of the pipeline run in one process group, led by the first process.
+/// A foreground job is waited for until it is done or stopped, a background job has the status 0.
+fn eval_pipe(mut pipe: fsh_ast::Pipe, state: &mut State, sh_vars: &mut ShVars) -> Result<()> {
+    let is_background = pipe
+        .commands()
+        .back()
+        .is_some_and(|command| command.background);
+
+    let text = extract_pipe_text(&pipe);
+
+    *state.pipe_mut() = Pipe::open();
+
+    let mut stages = Vec::with_capacity(pipe.commands().len());
+
+    let mut pgid = None;
+
+    while let Some(command) = pipe.pop_front() {
+        let stage = state
+            .pipe_mut()
+            .stage(pipe.is_empty(), command.pipe_stderr)
+            .and_then(|stdio| eval_command(command, stdio, state, sh_vars, is_background, &mut pgid));
+
+        match stage {
+            Ok(stage) => stages.push(stage),
+            Err(err) => {
+                state.pipe_mut().close();
+
+                teardown_pipe(&stages, pgid, text, state);
+
+                return Err(err);
+            }
         }
-    })?;
+    }
 
-    // push the process to the handler
-    let pid = state.handler_mut().push(child, is_background);
+    state.pipe_mut().close();
+
+    let pids = stages
+        .iter()
+        .filter_map(|stage| match stage {
+            Stage::Process(pid) => Some(*pid),
+            Stage::Done(_) => None,
+        })
+        .collect::<Vec<u32>>();
+
+    let last_pid = pids.last().copied();
+
+    let job = pgid.map(|pgid| state.jobs_mut().push(pgid, pids, text));
+
+    if let (Some(id), true) = (job, is_background) {
+        if let Some(pid) = last_pid {
+            sh_vars.insert(RESERVEDWORD_SHELL_VARIABLE_LAST_BACKGROUND, pid.to_string());
+        }
 
-    // send the stdout to the pipe
-    if let Some(child) = state.handler().get(pid) {
-        if let Some(stdout) = child.stdout.as_ref() {
-            let fd = stdout.as_raw_fd();
-            state.pipe_mut().send(fd).unwrap();
+        if state.jobs().is_interactive() {
+            eprintln!("[{id}] {}", pgid.unwrap_or_default());
         }
     }
 
+    if let (Some(id), false) = (job, is_background) {
+        state.jobs_mut().wait(id);
+    }
+
+    let statuses = stages
+        .iter()
+        .map(|stage| match stage {
+            Stage::Process(_) if is_background => ExitStatus::SUCCESS,
+            Stage::Process(pid) => state
+                .jobs()
+                .process_state(*pid)
+                .map(|state| state.status())
+                .unwrap_or_default(),
+            Stage::Done(status) => *status,
+        })
+        .collect();
+
+    // a foreground job that is done is no longer a job.
+    if let (Some(id), false) = (job, is_background) {
+        if state.jobs().is_done(id) {
+            state.remove_job(id);
+        }
+    }
+
+    set_pipe_status(state, sh_vars, statuses);
+
     Ok(())
 }
 
-fn set_command_stdio(state: &mut State) -> (process::Stdio, process::Stdio, process::Stdio) {
-    // stdin
-    let stdin = if state.pipe().is_recvable() {
-        unsafe { process::Stdio::from_raw_fd(state.pipe_mut().recv().unwrap()) }
-    } else {
-        process::Stdio::inherit()
+/// Kill the stages of a pipeline that failed to start, and wait for them.
+///
+/// The stages already spawned would otherwise wait for the stages that are missing.
+fn teardown_pipe(stages: &[Stage], pgid: Option<u32>, text: String, state: &mut State) {
+    let Some(pgid) = pgid else {
+        return;
     };
 
-    // stdout
-    let stdout = if state.pipe().is_sendable() {
-        process::Stdio::piped()
-    } else {
-        process::Stdio::inherit()
+    let pids = stages
+        .iter()
+        .filter_map(|stage| match stage {
+            Stage::Process(pid) => Some(*pid),
+            Stage::Done(_) => None,
+        })
+        .collect::<Vec<u32>>();
+
+    let id = state.jobs_mut().push(pgid, pids, text);
+
+    unsafe { libc::kill(-(pgid as libc::pid_t), libc::SIGKILL) };
+
+    // the wait also gives the terminal back to the shell.
+    state.jobs_mut().wait(id);
+
+    state.remove_job(id);
+}
+
+/// Set the exit status of the last pipeline, as `$?`.
+fn set_status(state: &mut State, sh_vars: &mut ShVars, status: ExitStatus) {
+    *state.status_mut() = status;
+
+    sh_vars.insert(RESERVEDWORD_SHELL_VARIABLE_STATUS, status.code().to_string());
+}
+
+/// Set the exit statuses of the commands of the last pipeline, as `$PIPESTATUS`, and its exit status from the last command.
+fn set_pipe_status(state: &mut State, sh_vars: &mut ShVars, statuses: Vec<ExitStatus>) {
+    let status = statuses.last().copied().unwrap_or_default();
+
+    sh_vars.insert(
+        RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS,
+        statuses
+            .iter()
+            .map(|status| status.code().to_string())
+            .collect::<Vec<String>>()
+            .join(" "),
+    );
+
+    *state.pipe_status_mut() = statuses;
+
+    set_status(state, sh_vars, status);
+}
+
+/// Evaluate a command of a pipeline, with the stdio of its stage.
+///
+/// The first process sets the process group of the pipeline, `pgid`.
+fn eval_command(
+    command: fsh_ast::Command,
+    stdio: Stdio,
+    state: &mut State,
+    sh_vars: &mut ShVars,
+    is_background: bool,
+    pgid: &mut Option<u32>,
+) -> Result<Stage> {
+    let command = state.aliases().expand(command);
+
+    let name = extract_command_name(&command, sh_vars)?;
+
+    let args = extract_command_args(&command, sh_vars)?;
+
+    let redirects = command.redirects;
+
+    if let Some(builtin) = state.builtins().get(&name) {
+        return eval_builtin_stage(
+            builtin.as_ref(),
+            &args,
+            &redirects,
+            stdio,
+            is_background,
+            pgid,
+            state,
+            sh_vars,
+        );
+    }
+
+    let pid = eval_process_command(
+        name,
+        args,
+        redirects,
+        stdio,
+        is_background,
+        pgid,
+        state,
+        sh_vars,
+    )?;
+
+    Ok(Stage::Process(pid))
+}
+
+/// Evaluate a builtin as a stage of a pipeline.
+///
+/// The last stage runs in the shell, its stdout and stderr are the redirects, the pipe of its stage, or the stdio of the shell.
+/// A builtin that is not the last stage runs in a subshell, a process of the job forked by the shell,
+/// so `exit 3 | cat` or `cd / | cat` do not change the shell.
+///
+/// The stdin of the last stage is closed, builtins do not read it.
+#[allow(clippy::too_many_arguments)]
+fn eval_builtin_stage(
+    builtin: &dyn Builtin,
+    args: &[String],
+    redirects: &[fsh_ast::Redirect],
+    stdio: Stdio,
+    is_background: bool,
+    pgid: &mut Option<u32>,
+    state: &mut State,
+    sh_vars: &mut ShVars,
+) -> Result<Stage> {
+    let is_last = stdio.stdout.is_none();
+
+    let duplicate = |fd: Option<&OwnedFd>, shell: RawFd| {
+        match fd {
+            Some(fd) => fd.try_clone(),
+            None => unsafe { BorrowedFd::borrow_raw(shell) }.try_clone_to_owned(),
+        }
+        .map_err(|_| Error::new(ErrorKind::BrokenPipe, "Failed to duplicate the file descriptor"))
     };
 
-    // stderr
-    let stderr = process::Stdio::inherit();
+    // the stdin, the stdout and the stderr, `None` is the stdio of the shell.
+    let mut fds = [
+        open_here_document(redirects)?.map(OwnedFd::from).or(stdio.stdin),
+        stdio.stdout,
+        stdio.stderr,
+    ];
+
+    for (fd, target) in open_redirects(redirects, state, sh_vars)? {
+        if !(0..3).contains(&fd) {
+            continue;
+        }
+
+        fds[fd as usize] = match target {
+            RedirectTarget::File(file) => Some(file),
+            RedirectTarget::Fd(target @ 0..3) => Some(duplicate(fds[target as usize].as_ref(), target)?),
+            RedirectTarget::Fd(fd) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("@{fd}: bad file descriptor"),
+            ))?,
+        };
+    }
+
+    if is_last {
+        let [mut stdin, mut stdout, mut stderr] = fds.map(|fd| fd.map(fs::File::from));
+
+        let mut io = Io {
+            stdin: match stdin.as_mut() {
+                Some(file) => file,
+                None => &mut io::stdin(),
+            },
+            stdout: match stdout.as_mut() {
+                Some(file) => file,
+                None => &mut io::stdout(),
+            },
+            stderr: match stderr.as_mut() {
+                Some(file) => file,
+                None => &mut io::stderr(),
+            },
+        };
+
+        return builtin.run(args, &mut io, state, sh_vars).map(Stage::Done);
+    }
+
+    // as in `eval_process_command`, the first process leads the process group of the pipeline.
+    let group = pgid.unwrap_or(0) as libc::pid_t;
+
+    let is_foreground = !is_background && state.jobs().is_interactive();
+
+    // the output buffered by the shell would be written twice, by the shell and by the subshell.
+    let _ = io::stdout().flush();
+
+    // the subshell runs the builtin after `fork`, which is only sound while the shell has no other thread,
+    // a lock held by another thread would never be released in the subshell.
+    let pid = unsafe { libc::fork() };
+
+    if pid < 0 {
+        Err(Error::new(ErrorKind::EngineError, "Failed to fork a subshell"))?
+    }
+
+    if pid == 0 {
+        let status = eval_subshell(builtin, args, fds, group, is_foreground, state, sh_vars);
+
+        unsafe { libc::_exit(status.code()) };
+    }
+
+    // set the process group from the shell too, whichever of the shell and the subshell runs first.
+    let pgid = *pgid.get_or_insert(pid as u32);
 
-    (stdin, stdout, stderr)
+    unsafe { libc::setpgid(pid, pgid as libc::pid_t) };
+
+    let pid = state.handler_mut().push_subshell(pid as u32, is_background);
+
+    Ok(Stage::Process(pid))
 }
 
-fn eval_assign(assign: fsh_ast::Assign, sh_vars: &mut ShVars) -> Result<()> {
-    let (key, value) = extract_assign(assign)?;
+/// Run a builtin in the subshell of its stage, after `fork`.
+///
+/// The subshell has the stdio of the stage, and the signals and traps of a spawned process.
+///
+/// # Returns
+/// The exit status of the subshell, 1 if the builtin failed, its error is written to the stderr.
+fn eval_subshell(
+    builtin: &dyn Builtin,
+    args: &[String],
+    stdio: [Option<OwnedFd>; 3],
+    group: libc::pid_t,
+    is_foreground: bool,
+    state: &mut State,
+    sh_vars: &mut ShVars,
+) -> ExitStatus {
+    let ignored = state.traps().ignored();
 
-    sh_vars.insert(key, value);
+    // the traps of the shell do not run in the subshell, except the signals they ignore.
+    let traps = state
+        .traps()
+        .entries()
+        .into_iter()
+        .filter(|(_, command)| !command.is_empty())
+        .map(|(trap, _)| trap)
+        .collect::<Vec<Trap>>();
 
-    Ok(())
+    for trap in traps {
+        state.traps_mut().reset(trap);
+    }
+
+    // the subshell does not keep the pipe of the next stage open.
+    state.pipe_mut().close();
+
+    unsafe {
+        libc::setpgid(0, group);
+
+        if is_foreground {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        }
+
+        signal::reset(&ignored);
+
+        for (fd, target) in stdio.iter().enumerate() {
+            if let Some(target) = target {
+                libc::dup2(target.as_raw_fd(), fd as libc::c_int);
+            }
+        }
+    }
+
+    drop(stdio);
+
+    let mut io = Io {
+        stdin: &mut io::stdin(),
+        stdout: &mut io::stdout(),
+        stderr: &mut io::stderr(),
+    };
+
+    let status = builtin.run(args, &mut io, state, sh_vars).unwrap_or_else(|err| {
+        eprintln!("fsh: {}: {}", err.kind().as_str2(), err.message());
+
+        ExitStatus::FAILURE
+    });
+
+    let _ = io::stdout().flush();
+
+    status
+}
+
+/// Open the body of the last here-document of the redirects, as a file to read from the start.
+///
+/// The body is written to an unlinked temporary file, not to a pipe, so no thread writes it while the command runs:
+/// a subshell forked while a thread holds a lock would deadlock on it.
+///
+/// # Errors
+/// `Kind::EngineError` - If the temporary file can not be written
+fn open_here_document(redirects: &[fsh_ast::Redirect]) -> Result<Option<fs::File>> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let Some(body) = redirects.iter().rev().find_map(|redirect| match (&redirect.operator, &redirect.right) {
+        (fsh_ast::RedirectOperator::HereDoc, fsh_ast::Expr::String(body)) => Some(body),
+        _ => None,
+    }) else {
+        return Ok(None);
+    };
+
+    let path = env::temp_dir().join(format!(
+        "fsh-here-document-{}-{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let write = || -> io::Result<fs::File> {
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+
+        fs::remove_file(&path)?;
+
+        file.write_all(body.as_bytes())?;
+        file.rewind()?;
+
+        Ok(file)
+    };
+
+    write()
+        .map(Some)
+        .map_err(|_| Error::new(ErrorKind::EngineError, "Failed to write the here-document"))
+}
+
+/// The target of a redirect, opened in the shell before the command runs.
+enum RedirectTarget {
+    File(OwnedFd),
+    /// A file descriptor of the command, as `@2 > @1`.
+    Fd(libc::c_int),
+}
+
+/// Open the targets of the redirects, in order. here-documents are not redirects of a file.
+///
+/// The targets are expanded and opened in the shell, the command only duplicates them with `dup2`.
+///
+/// # Returns
+/// The file descriptors of the command, and their targets
+///
+/// # Errors
+/// `Kind::NotFound` - If the file of `<` does not exist
+///
+/// `Kind::PermissionDenied` - If the file can not be opened
+///
+/// `Kind::InvalidInput` - If a glob matches more than one path
+fn open_redirects(
+    redirects: &[fsh_ast::Redirect],
+    state: &State,
+    sh_vars: &ShVars,
+) -> Result<Vec<(libc::c_int, RedirectTarget)>> {
+    let mut targets = Vec::with_capacity(redirects.len());
+
+    for redirect in redirects {
+        let fd = match (&redirect.operator, &redirect.left) {
+            (fsh_ast::RedirectOperator::HereDoc, _) => continue,
+            (_, fsh_ast::Expr::FD(fd)) => *fd,
+            _ => Err(Error::new(
+                ErrorKind::EngineError,
+                "Open redirect error: Invalid abstract syntax tree",
+            ))?,
+        };
+
+        let target = match &redirect.right {
+            fsh_ast::Expr::FD(fd) => RedirectTarget::Fd(*fd),
+            expr => {
+                let path = state.current_dir().join(extract_redirect_target(expr, sh_vars)?);
+
+                let file = match redirect.operator {
+                    fsh_ast::RedirectOperator::Lt => fs::File::open(&path),
+                    _ => fs::File::create(&path),
+                }
+                .map_err(|err| open_error(err, &path.to_string_lossy()))?;
+
+                // the target is moved above the small file descriptors,
+                // so a `dup2` in the command does not overwrite a target that is not duplicated yet.
+                let fd = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 10) };
+
+                if fd < 0 {
+                    Err(open_error(io::Error::last_os_error(), &path.to_string_lossy()))?
+                }
+
+                RedirectTarget::File(unsafe { OwnedFd::from_raw_fd(fd) })
+            }
+        };
+
+        targets.push((fd, target));
+    }
+
+    Ok(targets)
+}
+
+/// The error of a redirect target that can not be opened.
+fn open_error(err: io::Error, path: &str) -> Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => Error::new(ErrorKind::NotFound, &format!("{path}: No such file or directory")),
+        io::ErrorKind::PermissionDenied => {
+            Error::new(ErrorKind::PermissionDenied, &format!("{path}: Permission denied"))
+        }
+        _ => Error::new(ErrorKind::Failure, &format!("{path}: Failed to open the file")),
+    }
+}
+
+//
+//
+//
+#[allow(clippy::too_many_arguments)]
+fn eval_process_command(
+    name: String,
+    args: Vec<String>,
+    redirects: Vec<fsh_ast::Redirect>,
+    stdio: Stdio,
+    is_background: bool,
+    pgid: &mut Option<u32>,
+    state: &mut State,
+    sh_vars: &mut ShVars,
+) -> Result<u32> {
+    // create a new process command
+    let mut ps_command = process::Command::new(&name);
+    
+    // set the arguments
+    ps_command.args(args);
+
+    // set the stdin, stdout, and stderr of the stage,
+    // the ends are closed in the shell when the command is dropped.
+    let Stdio {
+        stdin,
+        stdout,
+        stderr,
+    } = stdio;
+
+    ps_command
+        .stdin(stdin.map_or_else(process::Stdio::inherit, process::Stdio::from))
+        .stdout(stdout.map_or_else(process::Stdio::inherit, process::Stdio::from))
+        .stderr(stderr.map_or_else(process::Stdio::inherit, process::Stdio::from));
+
+    // set the here-document as the stdin
+    if let Some(file) = open_here_document(&redirects)? {
+        ps_command.stdin(file);
+    }
+
+    // the environment is the exported variables only, an unset variable is not inherited.
+    ps_command.env_clear().envs(sh_vars.exports());
+
+    // set the current directory
+    ps_command.current_dir(state.current_dir());
+
+    // the redirects are opened in the shell, the command only duplicates them,
+    // the targets stay open until the command is spawned.
+    let targets = open_redirects(&redirects, state, sh_vars)?;
+
+    let dups = targets
+        .iter()
+        .map(|(fd, target)| match target {
+            RedirectTarget::File(file) => (*fd, file.as_raw_fd()),
+            RedirectTarget::Fd(target) => (*fd, *target),
+        })
+        .collect::<Vec<(libc::c_int, libc::c_int)>>();
+
+    // the first process leads the process group of the pipeline,
+    // a foreground job takes the terminal if the shell controls it.
+    let group = pgid.unwrap_or(0) as libc::pid_t;
+
+    let is_foreground = !is_background && state.jobs().is_interactive();
+
+    // the signals handled by the shell are reset, except the signals ignored by traps.
+    let ignored = state.traps().ignored();
+
+    // set the pre-execution closure
+    unsafe {
+        ps_command.pre_exec(move || {
+            libc::setpgid(0, group);
+
+            if is_foreground {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            }
+
+            signal::reset(&ignored);
+
+            for (fd, target) in dups.iter() {
+                if fd == target {
+                    // dup2 does nothing, the target is only kept open across exec.
+                    libc::fcntl(*target, libc::F_SETFD, 0);
+                } else if libc::dup2(*target, *fd) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            Ok(())
+        });
+    };
+
+    // spawn the process
+    let child = ps_command.spawn().map_err(|err| {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            Error::new(ErrorKind::NotFound, "Command not found")
+        } else {
+            Error::new(ErrorKind::EngineError, "Command execution failed")
+        }
+    })?;
+
+    // set the process group from the shell too, whichever of the shell and the process runs first.
+    let pgid = *pgid.get_or_insert(child.id());
+
+    unsafe { libc::setpgid(child.id() as libc::pid_t, pgid as libc::pid_t) };
+
+    // push the process to the handler
+    let pid = state.handler_mut().push(child, is_background);
+
+    Ok(pid)
+}
+
+fn eval_assign(assign: fsh_ast::Assign, sh_vars: &mut ShVars) -> Result<()> {
+    let (key, value) = extract_assign(assign)?;
+
+    sh_vars.insert(key, value);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str) -> fsh_ast::Ast {
+        fsh_ast::Ast::Statement(fsh_ast::Statement::Command(fsh_ast::Command {
+            expr: fsh_ast::Expr::String(name.to_string()),
+            args: Vec::new(),
+            redirects: Vec::new(),
+            background: false,
+            pipe_stderr: false,
+        }))
+    }
+
+    fn state() -> State {
+        let mut state = State::new();
+
+        *state.current_dir_mut() = std::env::current_dir().unwrap();
+
+        state
+    }
+
+    #[test]
+    fn test_eval_status() {
+        let mut state = state();
+
+        eval(command("false"), &mut state, &mut ShVars::new()).unwrap();
+
+        assert_eq!(state.status().code(), 1);
+
+        eval(command("true"), &mut state, &mut ShVars::new()).unwrap();
+
+        assert_eq!(state.status().code(), 0);
+    }
+
+    #[test]
+    fn test_eval_pipe_status() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let ast = fsh_parser::Parser::new("false | sh -c 'exit 2' | true").parse().unwrap();
+
+        let status = eval(ast, &mut state, &mut sh_vars).unwrap();
+
+        assert!(status.success());
+        assert_eq!(state.pipe_status(), [ExitStatus::FAILURE, ExitStatus::new(2), ExitStatus::SUCCESS]);
+        assert_eq!(sh_vars.get("PIPESTATUS").unwrap(), "1 2 0");
+        assert_eq!(sh_vars.get("?").unwrap(), "0");
+
+        let ast = fsh_parser::Parser::new("! sh -c 'exit 3'").parse().unwrap();
+
+        eval(ast, &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("PIPESTATUS").unwrap(), "3");
+        assert_eq!(sh_vars.get("?").unwrap(), "0");
+
+        let err = eval(command("fsh-command-not-found"), &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+        assert_eq!(sh_vars.get("?").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_eval_pipe_stderr() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let path = std::env::temp_dir().join(format!("fsh-test-pipe-stderr-{}", std::process::id()));
+
+        let source = format!("sh -c 'echo out; echo err >&2' |& sort | cat > {}", path.display());
+
+        let status = eval(fsh_parser::Parser::new(&source).parse().unwrap(), &mut state, &mut sh_vars).unwrap();
+
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "err\nout\n");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_eval_pipe_teardown() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let start = std::time::Instant::now();
+
+        let ast = fsh_parser::Parser::new("sleep 10 | fsh-command-not-found").parse().unwrap();
+
+        let err = eval(ast, &mut state, &mut sh_vars).unwrap_err();
+
+        // the stage already spawned is killed, not waited for until it is done.
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert!(state.jobs().is_empty());
+        assert!(state.handler().is_empty());
+        assert_eq!(sh_vars.get("?").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_eval_builtin_stage() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-builtin-stage-{}", std::process::id()));
+
+        run("sleep 10 &", &mut state, &mut sh_vars).unwrap();
+
+        let line = "[1]+  Running                 sleep 10 &\n";
+
+        // the builtin writes to the pipe of its stage.
+        run(&format!("jobs | cat > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), line);
+
+        // the redirect of the stdout replaces the file.
+        run(&format!("jobs > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), line);
+
+        fs::remove_file(&path).unwrap();
+
+        // the error of a builtin that is not the last stage is the status of its subshell.
+        run("cd fsh-directory-not-found | cat", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS).unwrap(), "1 0");
+        assert_eq!(state.handler().len(), 1);
+
+        let pgid = state.jobs().get(1).unwrap().pgid();
+
+        unsafe { libc::kill(-(pgid as libc::pid_t), libc::SIGTERM) };
+
+        run("fg", &mut state, &mut sh_vars).unwrap();
+    }
+
+    #[test]
+    fn test_eval_builtin_subshell() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let current_dir = state.current_dir().to_path_buf();
+
+        // the shell survives `exit` in a subshell, the status is the status of the subshell.
+        run("exit 3 | cat", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS).unwrap(), "3 0");
+
+        // a code that is not a number is an error, not a success.
+        run("exit foo | cat", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS).unwrap(), "2 0");
+
+        // `cd` changes the current directory of the subshell only.
+        run("cd / | cat", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(state.current_dir(), current_dir);
+        assert_eq!(sh_vars.get(RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS).unwrap(), "0 0");
+
+        // the last stage still runs in the shell.
+        run("true | cd /", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(state.current_dir(), Path::new("/"));
+        assert!(state.handler().is_empty());
+    }
+
+    #[test]
+    fn test_eval_builtin_registry() {
+        struct Hello;
+
+        impl Builtin for Hello {
+            fn run(&self, args: &[String], io: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+                writeln!(io.stdout, "hello {}", args.join(" ")).unwrap();
+                writeln!(io.stderr, "bye").unwrap();
+
+                Ok(ExitStatus::new(3))
+            }
+
+            fn help(&self) -> &str {
+                "Say hello"
+            }
+        }
+
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-builtin-registry-{}", std::process::id()));
+
+        state.builtins_mut().register("hello", Hello);
+
+        // the stderr of the builtin is piped with `|&`.
+        run(&format!("hello fsh |& cat > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello fsh\nbye\n");
+        assert_eq!(sh_vars.get("PIPESTATUS").unwrap(), "3 0");
+
+        run(&format!("help hello type > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "hello  Say hello\ntype   Tell if a name is an alias, a builtin or a command\n"
+        );
+
+        let status = run(&format!("type hello sh fsh-command-not-found > {} @2 > @1", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        let output = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(status.code(), 1);
+        assert!(output.starts_with("hello is a shell builtin\nsh is /"));
+        assert!(output.ends_with("type: fsh-command-not-found: not found\n"));
+
+        fs::remove_file(&path).unwrap();
+
+        // an unregistered builtin is a command again.
+        state.builtins_mut().unregister("hello");
+
+        assert_eq!(run("hello", &mut state, &mut sh_vars).unwrap_err().kind(), &ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_eval_builtin_stdin() {
+        struct Upper;
+
+        impl Builtin for Upper {
+            fn run(&self, _: &[String], io: &mut Io, _: &mut State, _: &mut ShVars) -> Result<ExitStatus> {
+                let mut input = String::new();
+
+                io.stdin.read_to_string(&mut input).unwrap();
+
+                write!(io.stdout, "{}", input.to_uppercase()).unwrap();
+
+                Ok(ExitStatus::SUCCESS)
+            }
+        }
+
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-builtin-stdin-{}", std::process::id()));
+
+        state.builtins_mut().register("upper", Upper);
+
+        // the builtin reads the pipe of its stage, in a subshell and in the shell.
+        run(&format!("echo a | upper | cat > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A\n");
+
+        run(&format!("echo b | upper > {}", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "B\n");
+
+        // a here-document larger than the buffer of a pipe, no thread writes it.
+        let body = "c".repeat(1 << 18);
+
+        let source = format!("upper <<EOF | wc -c > {}\n{body}\nEOF", path.display());
+
+        run(&source, &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), ((1 << 18) + 1).to_string());
+
+        run(&format!("cat <<EOF | upper > {}\n{body}\nEOF", path.display()), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", body.to_uppercase()));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_eval_redirect() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-redirect-{}", std::process::id()));
+
+        sh_vars.insert("out", path.display().to_string());
+
+        // the target is a variable, and the stderr follows the stdout to the file.
+        run("sh -c 'echo out; echo err >&2' > $out @2 > @1", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "out\nerr\n");
+
+        // the file is truncated.
+        run("echo hi > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hi\n");
+
+        fs::remove_file(&path).unwrap();
+
+        // a missing file is an error of the shell, not of the command.
+        let err = run("cat < fsh-file-not-found", &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+        assert!(state.handler().is_empty());
+    }
+
+    #[test]
+    fn test_eval_export() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-export-{}", std::process::id()));
+
+        sh_vars.insert("out", path.display().to_string());
+
+        // only the exported variables are in the environment of the command.
+        run("$a = 1\n$b = 2\nexport a 'c=3'", &mut state, &mut sh_vars).unwrap();
+        run("sh -c 'echo $a$b$c' > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "13\n");
+
+        // a variable exported with its value is in the environment of the next command.
+        run("export FOO=bar; sh -c 'echo $FOO' > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\n");
+
+        // the value is expanded before `export` runs.
+        sh_vars.insert("HOME", "/fsh-home");
+
+        run("export P=$HOME; sh -c 'echo $P' > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "/fsh-home\n");
+        assert_eq!(sh_vars.get("P").unwrap(), "/fsh-home");
+
+        run("unset a FOO P\nenv > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "c=3\n");
+
+        run("echo -n $b | cat > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(run("false", &mut state, &mut sh_vars).unwrap().code(), 1);
+    }
+
+    #[test]
+    fn test_eval_alias() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let path = std::env::temp_dir().join(format!("fsh-test-alias-{}", std::process::id()));
+
+        sh_vars.insert("out", path.display().to_string());
+
+        run("alias say='echo -n' hi='say hi ' q=\"echo 'a b'\"", &mut state, &mut sh_vars).unwrap();
+
+        // the alias expands to a builtin, and the word after an alias with a trailing space is expanded.
+        run("hi there hi > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hi there hi");
+
+        run("type hi > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hi is aliased to `say hi `\n");
+
+        run("unalias say\nalias > $out", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "alias hi='say hi '\nalias q=\"echo 'a b'\"\n");
+
+        fs::remove_file(&path).unwrap();
+
+        assert!(run("hi", &mut state, &mut sh_vars).is_err());
+    }
+
+    #[test]
+    fn test_eval_job() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let ast = fsh_parser::Parser::new("sleep 10 | cat &").parse().unwrap();
+
+        let status = eval(ast, &mut state, &mut sh_vars).unwrap();
+
+        assert!(status.success());
+
+        let id = state.jobs().current().unwrap();
+
+        let job = state.jobs().get(id).unwrap();
+
+        assert_eq!(job.command(), "sleep 10 | cat &");
+        assert_eq!(state.jobs().pids(id).len(), 2);
+
+        // the pipeline is in the process group of its first process.
+        for pid in state.jobs().pids(id) {
+            assert_eq!(unsafe { libc::getpgid(pid as libc::pid_t) }, job.pgid() as libc::pid_t);
+        }
+
+        unsafe { libc::kill(-(job.pgid() as libc::pid_t), libc::SIGTERM) };
+
+        let ast = fsh_parser::Parser::new("fg %1").parse().unwrap();
+
+        let status = eval(ast, &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(status.code(), 128 + libc::SIGTERM);
+        assert!(state.jobs().is_empty());
+
+        // a foreground job that is done is removed.
+        eval(command("true"), &mut state, &mut sh_vars).unwrap();
+
+        assert!(state.jobs().is_empty());
+
+        let err = eval(fsh_parser::Parser::new("fg").parse().unwrap(), &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_eval_reap_jobs() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let ast = fsh_parser::Parser::new("sh -c 'exit 0' | sh -c 'exit 3' &").parse().unwrap();
+
+        eval(ast, &mut state, &mut sh_vars).unwrap();
+
+        let id = state.jobs().current().unwrap();
+
+        assert_eq!(sh_vars.get("!").unwrap(), state.jobs().pids(id)[1].to_string());
+        assert_eq!(state.handler().len(), 2);
+
+        let mut notices = Vec::new();
+
+        for _ in 0..100 {
+            notices = state.reap_jobs();
+
+            if !notices.is_empty() {
+                break;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        assert_eq!(notices, ["[1]+  Exit 3                  sh -c exit 0 | sh -c exit 3 &"]);
+        assert!(state.jobs().is_empty());
+        assert!(state.handler().is_empty());
+
+        // a foreground job leaves nothing behind.
+        eval(command("true"), &mut state, &mut sh_vars).unwrap();
+
+        assert!(state.handler().is_empty());
+    }
+
+    #[test]
+    fn test_eval_trap() {
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            let ast = fsh_parser::Parser::new(source).parse().unwrap();
+
+            eval(ast, state, sh_vars)
+        };
+
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let status = run("trap '$e = 1; true' ERR; ! false; true", &mut state, &mut sh_vars).unwrap();
+
+        assert!(status.success());
+        assert!(!sh_vars.exists("e"));
+
+        // the trap command does not change $?.
+        let status = run("false", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(status.code(), 1);
+        assert_eq!(sh_vars.get("e").unwrap(), "1");
+
+        run("trap '$a = 1' ALRM", &mut state, &mut sh_vars).unwrap();
+
+        unsafe { libc::raise(libc::SIGALRM) };
+
+        eval_pending_traps(&mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("a").unwrap(), "1");
+
+        run("trap - ALRM; trap ERR", &mut state, &mut sh_vars).unwrap();
+
+        assert!(state.traps().entries().is_empty());
+
+        let err = run("trap 'echo' SIGFOO", &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_eval_exit_trap() {
+        let mut state = state();
+
+        let mut sh_vars = ShVars::new();
+
+        let dir = std::env::temp_dir().join(format!("fsh-exit-trap-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        sh_vars.insert("out", dir.join("out").display().to_string());
+
+        // a script that ends without `exit`, as the shell runs it before `exit_shell`.
+        fs::write(dir.join("main.fsh"), "trap 'echo bye > $out' EXIT\necho hi\n").unwrap();
+
+        eval_file(&dir.join("main.fsh"), &mut state, &mut sh_vars).unwrap();
+
+        assert!(!dir.join("out").exists());
+
+        eval_exit_trap(&mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), "bye\n");
+
+        // the trap runs once.
+        fs::remove_file(dir.join("out")).unwrap();
+
+        eval_exit_trap(&mut state, &mut sh_vars).unwrap();
+
+        assert!(!dir.join("out").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_eval_forward_signal() {
+        let handler = || unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+
+            libc::sigaction(libc::SIGINT, std::ptr::null(), &mut action);
+
+            action.sa_sigaction
+        };
+
+        let before = handler();
+
+        let guard = signal::init_guard();
+
+        let thread = unsafe { libc::pthread_self() } as usize;
+
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+
+            unsafe { libc::pthread_kill(thread as libc::pthread_t, libc::SIGINT) };
+        });
+
+        let status = eval(
+            fsh_parser::Parser::new("sleep 5").parse().unwrap(),
+            &mut state(),
+            &mut ShVars::new(),
+        )
+        .unwrap();
+
+        assert_eq!(status.code(), 128 + libc::SIGINT);
+
+        // the other tests of the binary are killed by SIGINT again.
+        drop(guard);
+
+        assert_eq!(handler(), before);
+    }
+
+    #[test]
+    fn test_eval_signal_status() {
+        let ast = fsh_parser::Parser::new("sh -c 'kill -TERM $$'").parse().unwrap();
+
+        let status = eval(ast, &mut state(), &mut ShVars::new()).unwrap();
+
+        assert_eq!(status.code(), 128 + libc::SIGTERM);
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        assert!(status.message().is_some());
+    }
+
+    #[test]
+    fn test_eval_negated() {
+        let mut state = state();
+
+        let negated = |ast| fsh_ast::Ast::Negated(Box::new(ast));
+
+        eval(negated(command("false")), &mut state, &mut ShVars::new()).unwrap();
+
+        assert_eq!(state.status().code(), 0);
+
+        eval(negated(command("true")), &mut state, &mut ShVars::new()).unwrap();
+
+        assert_eq!(state.status().code(), 1);
+
+        eval(negated(negated(command("false"))), &mut state, &mut ShVars::new()).unwrap();
+
+        assert_eq!(state.status().code(), 1);
+    }
+
+    #[test]
+    fn test_eval_import() {
+        let dir = std::env::temp_dir().join(format!("fsh-import-{}", std::process::id()));
+
+        fs::create_dir_all(dir.join("lib")).unwrap();
+
+        fs::write(dir.join("lib/git.fsh"), "$branch = main\nimport util.fsh").unwrap();
+
+        fs::write(dir.join("lib/util.fsh"), "$x = 1").unwrap();
+
+        fs::write(dir.join("a.fsh"), "import b.fsh").unwrap();
+
+        fs::write(dir.join("b.fsh"), "import a.fsh").unwrap();
+
+        let import = |path: &str| {
+            fsh_ast::Ast::Statement(fsh_ast::Statement::Import(fsh_ast::Import {
+                path: fsh_ast::Expr::String(path.to_string()),
+            }))
+        };
+
+        let mut state = state();
+
+        *state.current_dir_mut() = dir.to_owned();
+
+        let mut sh_vars = ShVars::new();
+
+        eval(import("lib/git.fsh"), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("git::branch").unwrap(), "main");
+        assert_eq!(sh_vars.get("util::x").unwrap(), "1");
+        assert!(!sh_vars.exists("branch"));
+
+        // a module is evaluated once per session.
+        sh_vars.insert("git::branch", "dev");
+
+        eval(import("lib/git.fsh"), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("git::branch").unwrap(), "dev");
+
+        let err = eval(import("a.fsh"), &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InvalidInput);
+
+        let err = eval(import("svn.fsh"), &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+
+        // the imports of a script are relative to the script, not to the current directory.
+        fs::write(dir.join("lib/y.fsh"), "$y = 2").unwrap();
+
+        fs::write(dir.join("main.fsh"), "import lib/y.fsh").unwrap();
+
+        *state.current_dir_mut() = Path::new("/").to_path_buf();
+
+        eval_file(&dir.join("main.fsh"), &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("y::y").unwrap(), "2");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_eval_source() {
+        let dir = std::env::temp_dir().join(format!("fsh-source-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(dir.join("env.fsh"), "#!/usr/bin/env flatshell\n$x = 1\necho $1 $# > args.txt\nsh -c 'exit 2'").unwrap();
+
+        fs::write(dir.join("bad.fsh"), "echo a\necho |").unwrap();
+
+        let run = |source: &str, state: &mut State, sh_vars: &mut ShVars| {
+            eval(fsh_parser::Parser::new(source).parse().unwrap(), state, sh_vars)
+        };
+
+        let mut state = state();
+
+        *state.current_dir_mut() = dir.to_owned();
+
+        let mut sh_vars = ShVars::new();
+
+        sh_vars.set_args(&["a".to_string()]);
+
+        // the file runs in the current shell, the variables it assigns are kept.
+        let status = run("source env.fsh b c", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(status.code(), 2);
+        assert_eq!(sh_vars.get("x").unwrap(), "1");
+        assert_eq!(fs::read_to_string(dir.join("args.txt")).unwrap(), "b 2\n");
+
+        // the positional parameters are set back.
+        assert_eq!(sh_vars.args(), ["a"]);
+
+        run(". env.fsh", &mut state, &mut sh_vars).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("args.txt")).unwrap(), "a 1\n");
+
+        let err = run("source bad.fsh", &mut state, &mut sh_vars).unwrap_err();
+
+        assert!(err.message().starts_with(&format!("{}:2:", dir.join("bad.fsh").display())));
+
+        // a script is not continued by more lines, an unterminated quote is a syntax error.
+        fs::write(dir.join("quote.fsh"), "echo 'a\n").unwrap();
+
+        let err = run("source quote.fsh", &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::SyntaxError);
+
+        let err = run("source missing.fsh", &mut state, &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_eval_try() {
+        let run = |source: &str, sh_vars: &mut ShVars| {
+            let ast = fsh_parser::Parser::new(source).parse().unwrap();
+
+            eval(ast, &mut state(), sh_vars)
+        };
+
+        let mut sh_vars = ShVars::new();
+
+        run("try { false; $a = 1 } catch $e { $b = 1 }", &mut sh_vars).unwrap();
+
+        assert!(!sh_vars.exists("a"));
+        assert!(sh_vars.exists("b"));
+        assert_eq!(sh_vars.get("e::kind").unwrap(), "Failure");
+        assert_eq!(sh_vars.get("e::status").unwrap(), "1");
+
+        run("try { fsh-command-not-found } catch $e {}", &mut sh_vars).unwrap();
+
+        assert_eq!(sh_vars.get("e::kind").unwrap(), "NotFound");
+        assert_eq!(sh_vars.get("e").unwrap(), sh_vars.get("e::message").unwrap());
+
+        // without catch, an engine error is returned after finally, a status is not.
+        let err = run("try { fsh-command-not-found } finally { $c = 1 }", &mut sh_vars).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+        assert!(sh_vars.exists("c"));
+
+        run("try { false } finally {}", &mut sh_vars).unwrap();
+    }
 }
@@ -0,0 +1,135 @@
+use std::{ffi::CStr, os::unix::process::ExitStatusExt, process};
+
+/// The exit status of a command, a builtin or a pipeline.
+///
+/// A process killed by a signal has the code 128 + the signal number, as in other shells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus {
+    code: i32,
+    signal: Option<i32>,
+    core_dumped: bool,
+}
+
+impl ExitStatus {
+    pub const SUCCESS: Self = Self::new(0);
+
+    pub const FAILURE: Self = Self::new(1);
+
+    /// Create a new exit status from a code
+    pub const fn new(code: i32) -> Self {
+        Self {
+            code,
+            signal: None,
+            core_dumped: false,
+        }
+    }
+
+    /// The exit code, 128 + N if the process was killed by the signal N.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// The signal that killed the process.
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    /// Check if the process dumped core.
+    pub fn core_dumped(&self) -> bool {
+        self.core_dumped
+    }
+
+    /// Check if the exit code is 0.
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+
+    /// The status with the success inverted, as by `!`.
+    pub fn negate(&self) -> Self {
+        if self.success() {
+            Self::FAILURE
+        } else {
+            Self::SUCCESS
+        }
+    }
+
+    /// A readable message for a process killed by a signal, such as `Segmentation fault (core dumped)`.
+    ///
+    /// SIGINT and SIGPIPE have no message, they are the usual way to stop a pipeline.
+    pub fn message(&self) -> Option<String> {
+        let signal = self.signal?;
+
+        if signal == libc::SIGINT || signal == libc::SIGPIPE {
+            return None;
+        }
+
+        let description = unsafe {
+            let ptr = libc::strsignal(signal);
+
+            if ptr.is_null() {
+                format!("Signal {signal}")
+            } else {
+                CStr::from_ptr(ptr).to_string_lossy().into_owned()
+            }
+        };
+
+        if self.core_dumped {
+            Some(format!("{description} (core dumped)"))
+        } else {
+            Some(description)
+        }
+    }
+}
+
+impl Default for ExitStatus {
+    fn default() -> Self {
+        Self::SUCCESS
+    }
+}
+
+impl From<process::ExitStatus> for ExitStatus {
+    fn from(status: process::ExitStatus) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Self::new(code),
+            (None, Some(signal)) => Self {
+                code: 128 + signal,
+                signal: Some(signal),
+                core_dumped: status.core_dumped(),
+            },
+            (None, None) => Self::FAILURE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_status() {
+        let status = ExitStatus::from(process::ExitStatus::from_raw(3 << 8));
+
+        assert_eq!(status.code(), 3);
+        assert_eq!(status.signal(), None);
+        assert_eq!(status.message(), None);
+
+        assert!(!status.success());
+        assert!(status.negate().success());
+    }
+
+    #[test]
+    fn test_exit_status_signal() {
+        // killed by SIGSEGV, with the core dump flag.
+        let status = ExitStatus::from(process::ExitStatus::from_raw(libc::SIGSEGV | 0x80));
+
+        assert_eq!(status.code(), 128 + libc::SIGSEGV);
+        assert_eq!(status.signal(), Some(libc::SIGSEGV));
+        assert!(status.core_dumped());
+        assert!(status.message().unwrap().ends_with(" (core dumped)"));
+
+        let status = ExitStatus::from(process::ExitStatus::from_raw(libc::SIGINT));
+
+        assert_eq!(status.code(), 130);
+        assert_eq!(status.message(), None);
+    }
+}
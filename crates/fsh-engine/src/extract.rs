@@ -8,12 +8,12 @@ pub(super) fn extract_command_args(
 ) -> Result<Vec<String>> {
     let mut v = Vec::with_capacity(command.args.len());
 
-    for arg in command.args.to_owned() {
+    for arg in command.args.iter().cloned() {
         let arg = match arg {
             fsh_ast::Expr::String(string) => {
                 let mut string_vec = globbing(&string);
 
-                if string_vec.len() > 0 {
+                if !string_vec.is_empty() {
                     v.append(&mut string_vec);
 
                     continue;
@@ -22,10 +22,32 @@ pub(super) fn extract_command_args(
                 }
             }
 
-            fsh_ast::Expr::Ident(ident) => sh_vars.get(&ident).unwrap_or_default().to_string(),
+            fsh_ast::Expr::Literal(string) => string,
+
+            // the value of a variable is globbed, as an unquoted word.
+            fsh_ast::Expr::Ident(ident) => {
+                let value = sh_vars.get(&ident).unwrap_or_default().to_string();
+
+                let mut string_vec = globbing(&value);
+
+                if !string_vec.is_empty() {
+                    v.append(&mut string_vec);
+
+                    continue;
+                } else {
+                    value
+                }
+            }
+
+            // `NAME=$VALUE` is one argument, the value is not globbed.
+            fsh_ast::Expr::KeyIdent(key, ident) => {
+                format!("{key}={}", sh_vars.get(&ident).unwrap_or_default())
+            }
 
             fsh_ast::Expr::Number(number) => number.to_string(),
 
+            fsh_ast::Expr::Float(number) => format!("{number:?}"),
+
             _ => Err(Error::new(
                 ErrorKind::EngineError,
                 "Extract command args error: Invalid abstract syntax tree",
@@ -42,12 +64,14 @@ pub(super) fn extract_command_name(
     sh_vars: &mut ShVars,
 ) -> Result<String> {
     let name = match command.expr.to_owned() {
-        fsh_ast::Expr::String(string) => string,
+        fsh_ast::Expr::String(string) | fsh_ast::Expr::Literal(string) => string,
 
         fsh_ast::Expr::Ident(ident) => sh_vars.get(&ident).unwrap_or_default().to_string(),
 
         fsh_ast::Expr::Number(number) => number.to_string(),
 
+        fsh_ast::Expr::Float(number) => format!("{number:?}"),
+
         _ => Err(Error::new(
             ErrorKind::EngineError,
             "Extract command name error: Invalid abstract syntax tree",
@@ -68,7 +92,11 @@ pub(super) fn extract_assign(assign: fsh_ast::Assign) -> Result<(String, String)
     };
 
     let value = match assign.expr {
-        fsh_ast::Expr::String(value) => value,
+        fsh_ast::Expr::String(value) | fsh_ast::Expr::Literal(value) => value,
+
+        fsh_ast::Expr::Number(number) => number.to_string(),
+
+        fsh_ast::Expr::Float(number) => format!("{number:?}"),
 
         _ => Err(Error::new(
             ErrorKind::EngineError,
@@ -79,6 +107,139 @@ pub(super) fn extract_assign(assign: fsh_ast::Assign) -> Result<(String, String)
     Ok((key, value))
 }
 
+pub(super) fn extract_match_value(
+    r#match: &fsh_ast::Match,
+    sh_vars: &mut ShVars,
+) -> Result<String> {
+    let value = match r#match.expr.to_owned() {
+        fsh_ast::Expr::String(string) | fsh_ast::Expr::Literal(string) => string,
+
+        fsh_ast::Expr::Ident(ident) => sh_vars.get(&ident).unwrap_or_default().to_string(),
+
+        fsh_ast::Expr::Number(number) => number.to_string(),
+
+        fsh_ast::Expr::Float(number) => format!("{number:?}"),
+
+        _ => Err(Error::new(
+            ErrorKind::EngineError,
+            "Extract match value error: Invalid abstract syntax tree",
+        ))?,
+    };
+
+    Ok(value)
+}
+
+pub(super) fn extract_import_path(
+    import: &fsh_ast::Import,
+    sh_vars: &mut ShVars,
+) -> Result<String> {
+    let path = match import.path.to_owned() {
+        fsh_ast::Expr::String(string) | fsh_ast::Expr::Literal(string) => string,
+
+        fsh_ast::Expr::Ident(ident) => sh_vars.get(&ident).unwrap_or_default().to_string(),
+
+        _ => Err(Error::new(
+            ErrorKind::EngineError,
+            "Extract import path error: Invalid abstract syntax tree",
+        ))?,
+    };
+
+    Ok(path)
+}
+
+/// The path of a redirect target, the variables and the globs are expanded.
+///
+/// # Errors
+/// `Kind::InvalidInput` - If the glob matches more than one path
+pub(super) fn extract_redirect_target(expr: &fsh_ast::Expr, sh_vars: &ShVars) -> Result<String> {
+    match expr {
+        fsh_ast::Expr::String(string) => match globbing(string).as_slice() {
+            [path] => Ok(path.to_string()),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("{string}: ambiguous redirect"),
+            )),
+        },
+        fsh_ast::Expr::Literal(string) => Ok(string.to_string()),
+        fsh_ast::Expr::Ident(ident) => Ok(sh_vars.get(ident).unwrap_or_default().to_string()),
+        fsh_ast::Expr::KeyIdent(key, ident) => Ok(format!("{key}={}", sh_vars.get(ident).unwrap_or_default())),
+        fsh_ast::Expr::Number(number) => Ok(number.to_string()),
+        fsh_ast::Expr::Float(number) => Ok(format!("{number:?}")),
+        fsh_ast::Expr::FD(_) => Err(Error::new(
+            ErrorKind::EngineError,
+            "Extract redirect target error: Invalid abstract syntax tree",
+        )),
+    }
+}
+
+/// The command as written, without its here-document body, as listed by `jobs`.
+pub(super) fn extract_command_text(command: &fsh_ast::Command) -> String {
+    let text = |expr: &fsh_ast::Expr| match expr {
+        fsh_ast::Expr::String(string) | fsh_ast::Expr::Literal(string) => string.to_string(),
+        fsh_ast::Expr::Ident(ident) => format!("${ident}"),
+        fsh_ast::Expr::KeyIdent(key, ident) => format!("{key}=${ident}"),
+        fsh_ast::Expr::Number(number) => number.to_string(),
+        fsh_ast::Expr::Float(number) => format!("{number:?}"),
+        fsh_ast::Expr::FD(fd) => format!("@{fd}"),
+    };
+
+    let mut words = vec![text(&command.expr)];
+
+    words.extend(command.args.iter().map(text));
+
+    for redirect in &command.redirects {
+        words.push(match redirect.operator {
+            fsh_ast::RedirectOperator::Gt => format!("{} > {}", text(&redirect.left), text(&redirect.right)),
+            fsh_ast::RedirectOperator::Lt => format!("{} < {}", text(&redirect.left), text(&redirect.right)),
+            fsh_ast::RedirectOperator::HereDoc => "<<EOF".to_string(),
+        });
+    }
+
+    if command.background {
+        words.push("&".to_string());
+    }
+
+    words.join(" ")
+}
+
+/// The pipeline as written, the commands joined by `|` or `|&`.
+pub(super) fn extract_pipe_text(pipe: &fsh_ast::Pipe) -> String {
+    let mut text = String::new();
+
+    for (i, command) in pipe.commands().iter().enumerate() {
+        if i > 0 {
+            text.push_str(if pipe.commands()[i - 1].pipe_stderr { " |& " } else { " | " });
+        }
+
+        text.push_str(&extract_command_text(command));
+    }
+
+    text
+}
+
+/// Check if the value matches the pattern, globs are matched against the string, not against paths.
+pub(super) fn is_match(pattern: &fsh_ast::Pattern, value: &str) -> Result<bool> {
+    match pattern {
+        fsh_ast::Pattern::Glob(glob) => match glob::Pattern::new(glob) {
+            Ok(glob) => Ok(glob.matches(value)),
+            Err(err) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("Invalid glob pattern '{glob}': {err}"),
+            )),
+        },
+
+        fsh_ast::Pattern::Regex(regex) => match regex::Regex::new(regex) {
+            Ok(regex) => Ok(regex.is_match(value)),
+            Err(err) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("Invalid regex pattern '{regex}': {err}"),
+            )),
+        },
+
+        fsh_ast::Pattern::Wildcard => Ok(true),
+    }
+}
+
 fn globbing(path: &str) -> Vec<String> {
     if path.is_empty() {
         return vec![path.to_string()];
@@ -86,13 +247,13 @@ fn globbing(path: &str) -> Vec<String> {
 
     let mut v = Vec::new();
 
-    match glob::glob(&path) {
+    match glob::glob(path) {
         Ok(paths) => {
             let paths = paths
                 .map(|path| path.unwrap().to_str().unwrap().to_string())
                 .collect::<Vec<String>>();
 
-            if paths.len() > 0 {
+            if !paths.is_empty() {
                 for path in paths {
                     v.push(path);
                 }
@@ -105,3 +266,123 @@ fn globbing(path: &str) -> Vec<String> {
 
     v
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fsh_ast::Pattern;
+
+    #[test]
+    fn test_extract_command_args_number() {
+        let command = fsh_ast::Command {
+            expr: fsh_ast::Expr::String("seq".to_string()),
+            args: vec![
+                fsh_ast::Expr::Number(-1),
+                fsh_ast::Expr::Float(0.5),
+                fsh_ast::Expr::Float(2.0),
+            ],
+            redirects: Vec::default(),
+            background: false,
+            pipe_stderr: false,
+        };
+
+        assert_eq!(
+            extract_command_args(&command, &mut ShVars::new()).unwrap(),
+            ["-1", "0.5", "2.0"]
+        );
+    }
+
+    #[test]
+    fn test_extract_command_args_literal() {
+        let command = fsh_ast::Command {
+            expr: fsh_ast::Expr::String("echo".to_string()),
+            args: vec![fsh_ast::Expr::Literal("*".to_string())],
+            redirects: Vec::default(),
+            background: false,
+            pipe_stderr: false,
+        };
+
+        assert_eq!(
+            extract_command_args(&command, &mut ShVars::new()).unwrap(),
+            ["*"]
+        );
+    }
+
+    #[test]
+    fn test_extract_command_args_ident() {
+        let mut sh_vars = ShVars::new();
+
+        sh_vars.insert("manifest", "Cargo.tom?");
+        sh_vars.insert("missing", "*.missing");
+
+        let command = fsh_ast::Command {
+            expr: fsh_ast::Expr::String("ls".to_string()),
+            args: vec![
+                fsh_ast::Expr::Ident("manifest".to_string()),
+                fsh_ast::Expr::Ident("missing".to_string()),
+                fsh_ast::Expr::Literal("Cargo.tom?".to_string()),
+            ],
+            redirects: Vec::default(),
+            background: false,
+            pipe_stderr: false,
+        };
+
+        // the value of a variable is globbed, a quoted string is not.
+        assert_eq!(
+            extract_command_args(&command, &mut sh_vars).unwrap(),
+            ["Cargo.toml", "*.missing", "Cargo.tom?"]
+        );
+    }
+
+    #[test]
+    fn test_extract_redirect_target() {
+        let mut sh_vars = ShVars::new();
+
+        sh_vars.insert("log", "out.log");
+
+        assert_eq!(
+            extract_redirect_target(&fsh_ast::Expr::Ident("log".to_string()), &sh_vars).unwrap(),
+            "out.log"
+        );
+        assert_eq!(
+            extract_redirect_target(&fsh_ast::Expr::String("Cargo.tom?".to_string()), &sh_vars).unwrap(),
+            "Cargo.toml"
+        );
+        assert_eq!(
+            extract_redirect_target(&fsh_ast::Expr::String("src/*.rs".to_string()), &sh_vars)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_is_match() {
+        let glob = Pattern::Glob("*.rs".to_string());
+
+        assert!(is_match(&glob, "main.rs").unwrap());
+        assert!(is_match(&glob, "src/main.rs").unwrap());
+        assert!(!is_match(&glob, "main.c").unwrap());
+
+        let escaped = Pattern::Glob("a[*]".to_string());
+
+        assert!(is_match(&escaped, "a*").unwrap());
+        assert!(!is_match(&escaped, "ab").unwrap());
+
+        let regex = Pattern::Regex("^v[0-9]+$".to_string());
+
+        assert!(is_match(&regex, "v12").unwrap());
+        assert!(!is_match(&regex, "v1.2").unwrap());
+
+        assert!(is_match(&Pattern::Wildcard, "").unwrap());
+    }
+
+    #[test]
+    fn test_is_match_error() {
+        let err = is_match(&Pattern::Regex("(".to_string()), "a").unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InvalidInput);
+
+        assert!(is_match(&Pattern::Glob("[".to_string()), "a").is_err());
+    }
+}
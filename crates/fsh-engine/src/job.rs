@@ -0,0 +1,644 @@
+use fsh_common::{Error, ErrorKind, Result};
+use std::{fmt, mem::MaybeUninit, os::unix::process::ExitStatusExt, process};
+
+use super::{process_handler::ProcessHandler, signal, ExitStatus};
+
+/// The state of a job, or of a process of a job.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    /// Stopped by the signal, as `SIGTSTP` or `SIGSTOP`.
+    Stopped(libc::c_int),
+    Done(ExitStatus),
+}
+
+impl JobState {
+    /// The exit status, 128 + the signal for a stopped job, 0 for a running job.
+    pub fn status(&self) -> ExitStatus {
+        match self {
+            JobState::Running => ExitStatus::SUCCESS,
+            JobState::Stopped(signal) => ExitStatus::new(128 + signal),
+            JobState::Done(status) => *status,
+        }
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped(_) => write!(f, "Stopped"),
+            JobState::Done(status) if status.success() => write!(f, "Done"),
+            JobState::Done(status) => write!(f, "Exit {}", status.code()),
+        }
+    }
+}
+
+/// A job, the processes of a pipeline in one process group.
+///
+/// The processes are in the process table of the jobs, see `Jobs::pids`.
+#[derive(Debug)]
+pub struct Job {
+    id: usize,
+    pgid: u32,
+    command: String,
+    /// The terminal modes of the job, saved when it is stopped.
+    termios: Option<libc::termios>,
+}
+
+impl Job {
+    /// The job number, as `%n`.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The process group id, the process id of the first command.
+    pub fn pgid(&self) -> u32 {
+        self.pgid
+    }
+
+    /// The command line of the pipeline.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+}
+
+/// The job table.
+///
+/// Each pipeline runs in its own process group. When the shell controls a terminal, the terminal is handed to the foreground job, and Ctrl-Z stops it.
+///
+/// The processes of the jobs, and their states, are owned by the process table.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    /// The jobs, the most recent last, the last one is the current job (`%+`).
+    jobs: Vec<Job>,
+    handler: ProcessHandler,
+    /// The process group of the shell, when the shell controls the terminal.
+    shell_pgid: Option<libc::pid_t>,
+    /// The terminal modes of the shell.
+    termios: Option<libc::termios>,
+    /// The processes of the removed jobs that are not done, they are only reaped, see `update`.
+    disowned: Vec<u32>,
+}
+
+impl Jobs {
+    /// Create a new job table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the control of the terminal, if the standard input is a terminal.
+    ///
+    /// The shell waits to be in the foreground, puts itself in its own process group, and ignores the signals of job control.
+    ///
+    /// # Errors
+    /// `ErrorKind::Failure` - If the shell can not take the control of the terminal.
+    pub fn init_terminal(&mut self) -> Result<()> {
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 0 {
+                return Ok(());
+            }
+
+            loop {
+                let pgrp = libc::getpgrp();
+
+                if libc::tcgetpgrp(libc::STDIN_FILENO) == pgrp {
+                    break;
+                }
+
+                libc::kill(-pgrp, libc::SIGTTIN);
+            }
+
+            libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+            libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+
+            let pid = libc::getpid();
+
+            // a session leader is already the leader of its process group.
+            if libc::setpgid(pid, pid) < 0 && libc::getpgrp() != pid {
+                Err(Error::new(
+                    ErrorKind::Failure,
+                    "Failed to put the shell in its own process group",
+                ))?
+            }
+
+            if libc::tcsetpgrp(libc::STDIN_FILENO, pid) < 0 {
+                Err(Error::new(
+                    ErrorKind::Failure,
+                    "Failed to take the control of the terminal",
+                ))?
+            }
+
+            self.shell_pgid = Some(pid);
+            self.termios = get_termios();
+        }
+
+        Ok(())
+    }
+
+    /// Check if the shell controls the terminal.
+    pub fn is_interactive(&self) -> bool {
+        self.shell_pgid.is_some()
+    }
+
+    /// The process table
+    pub fn handler(&self) -> &ProcessHandler {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut ProcessHandler {
+        &mut self.handler
+    }
+
+    /// Add a running job, of processes of the process table.
+    ///
+    /// # Returns
+    /// The job number
+    pub fn push(&mut self, pgid: u32, pids: Vec<u32>, command: impl Into<String>) -> usize {
+        let id = self.jobs.iter().map(Job::id).max().unwrap_or(0) + 1;
+
+        for pid in pids {
+            self.handler.set_job(pid, id);
+        }
+
+        self.jobs.push(Job {
+            id,
+            pgid,
+            command: command.into(),
+            termios: None,
+        });
+
+        id
+    }
+
+    /// Remove a job, and its processes from the process table. the processes are not signaled.
+    ///
+    /// The processes that are not done are still reaped by `update`, so they do not stay zombies.
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+
+        for pid in self.handler.job(id) {
+            if !matches!(self.handler.state(pid), Some(JobState::Done(_))) {
+                self.disowned.push(pid);
+            }
+        }
+
+        self.handler.remove_job(id);
+
+        Some(self.jobs.remove(index))
+    }
+
+    /// Get a job
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// Get all jobs, in order of job number.
+    pub fn entries(&self) -> Vec<&Job> {
+        let mut jobs = self.jobs.iter().collect::<Vec<&Job>>();
+
+        jobs.sort_by_key(|job| job.id);
+
+        jobs
+    }
+
+    /// The process ids of a job, in order of the pipeline.
+    pub fn pids(&self, id: usize) -> Vec<u32> {
+        self.handler.job(id)
+    }
+
+    /// The state of a process of a job.
+    pub fn process_state(&self, pid: u32) -> Option<JobState> {
+        self.handler.state(pid)
+    }
+
+    /// The state of a job.
+    ///
+    /// A job is stopped if any process is stopped, and done with the status of the last process when all processes are done.
+    pub fn state(&self, id: usize) -> JobState {
+        let states = self
+            .pids(id)
+            .into_iter()
+            .filter_map(|pid| self.handler.state(pid))
+            .collect::<Vec<JobState>>();
+
+        if let Some(state) = states.iter().find(|state| matches!(state, JobState::Stopped(_))) {
+            return *state;
+        }
+
+        match states.last() {
+            Some(JobState::Done(status))
+                if states.iter().all(|state| matches!(state, JobState::Done(_))) =>
+            {
+                JobState::Done(*status)
+            }
+            _ => JobState::Running,
+        }
+    }
+
+    /// Check if a job is done.
+    pub fn is_done(&self, id: usize) -> bool {
+        matches!(self.state(id), JobState::Done(_))
+    }
+
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Get the number of jobs
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// The current job, `%+`.
+    pub fn current(&self) -> Option<usize> {
+        self.jobs.last().map(Job::id)
+    }
+
+    /// The previous job, `%-`.
+    pub fn previous(&self) -> Option<usize> {
+        self.jobs.iter().rev().nth(1).map(Job::id)
+    }
+
+    /// Find a job from a job spec: `%n`, `%+`, `%%`, `%-`, or `n`.
+    ///
+    /// Without a spec, the current job.
+    ///
+    /// # Errors
+    /// `ErrorKind::NotFound` - If there is no such job.
+    pub fn find(&self, spec: Option<&str>) -> Result<usize> {
+        let id = match spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec)) {
+            None | Some("+") | Some("%") | Some("") => self.current(),
+            Some("-") => self.previous(),
+            Some(number) => number
+                .parse()
+                .ok()
+                .filter(|id| self.get(*id).is_some()),
+        };
+
+        id.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                &format!("{}: no such job", spec.unwrap_or("current")),
+            )
+        })
+    }
+
+    /// Format a job as listed by `jobs`: `[1]+  Stopped                 sleep 10`.
+    pub fn format(&self, id: usize) -> Option<String> {
+        let job = self.get(id)?;
+
+        let marker = if Some(id) == self.current() {
+            '+'
+        } else if Some(id) == self.previous() {
+            '-'
+        } else {
+            ' '
+        };
+
+        Some(format!(
+            "[{id}]{marker}  {:<24}{}",
+            self.state(id).to_string(),
+            job.command
+        ))
+    }
+
+    /// Make the job the current job.
+    fn touch(&mut self, id: usize) {
+        if let Some(index) = self.jobs.iter().position(|job| job.id == id) {
+            let job = self.jobs.remove(index);
+
+            self.jobs.push(job);
+        }
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// Continue a stopped job, in the foreground or the background.
+    ///
+    /// # Errors
+    /// `ErrorKind::NotFound` - If there is no such job.
+    ///
+    /// `ErrorKind::Failure` - If the job can not be signaled.
+    pub fn resume(&mut self, id: usize, is_foreground: bool) -> Result<()> {
+        let is_interactive = self.is_interactive();
+
+        let job = self
+            .get_mut(id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No such job"))?;
+
+        if is_interactive && is_foreground {
+            if let Some(termios) = job.termios.take() {
+                set_termios(&termios);
+            }
+        }
+
+        if unsafe { libc::kill(-(job.pgid as libc::pid_t), libc::SIGCONT) } < 0 {
+            Err(Error::new(ErrorKind::Failure, "Failed to continue the job"))?
+        }
+
+        for pid in self.handler.job(id) {
+            if matches!(self.handler.state(pid), Some(JobState::Stopped(_))) {
+                self.handler.set_state(pid, JobState::Running);
+            }
+        }
+
+        self.touch(id);
+
+        Ok(())
+    }
+
+    /// Wait for a foreground job, until it is done or stopped.
+    ///
+    /// When the shell controls the terminal, the terminal is handed to the job while it runs.
+    /// A stopped job is reported on the standard error.
+    ///
+    /// # Returns
+    /// The state of the job, `None` if there is no such job.
+    pub fn wait(&mut self, id: usize) -> Option<JobState> {
+        let shell_pgid = self.shell_pgid;
+
+        let pgid = self.get(id)?.pgid;
+
+        if shell_pgid.is_some() {
+            unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, pgid as libc::pid_t) };
+        }
+
+        for pid in self.handler.job(id) {
+            if matches!(self.handler.state(pid), Some(JobState::Done(_))) {
+                continue;
+            }
+
+            let state = wait_pid(pid, libc::WUNTRACED, || signal::forward(pgid))
+                .unwrap_or(JobState::Done(ExitStatus::FAILURE));
+
+            self.handler.set_state(pid, state);
+
+            if matches!(state, JobState::Stopped(_)) {
+                break;
+            }
+        }
+
+        let state = self.state(id);
+
+        if let Some(shell_pgid) = shell_pgid {
+            if matches!(state, JobState::Stopped(_)) {
+                if let Some(job) = self.get_mut(id) {
+                    job.termios = get_termios();
+                }
+            }
+
+            unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, shell_pgid) };
+
+            if let Some(termios) = self.termios.as_ref() {
+                set_termios(termios);
+            }
+        }
+
+        if matches!(state, JobState::Stopped(_)) {
+            self.touch(id);
+
+            if let Some(line) = self.format(id) {
+                eprintln!("\n{line}");
+            }
+        }
+
+        Some(state)
+    }
+
+    /// Update the states of the jobs, and reap the processes of the removed jobs, without waiting.
+    pub fn update(&mut self) {
+        self.disowned
+            .retain(|pid| !matches!(wait_pid(*pid, libc::WNOHANG, || {}), Some(JobState::Done(_))));
+
+        for id in self.handler.jobs() {
+            for pid in self.handler.job(id) {
+                if matches!(self.handler.state(pid), Some(JobState::Done(_))) {
+                    continue;
+                }
+
+                if let Some(state) =
+                    wait_pid(pid, libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED, || {})
+                {
+                    self.handler.set_state(pid, state);
+                }
+            }
+        }
+    }
+
+    /// Remove the jobs that are done, after updating the states.
+    ///
+    /// # Returns
+    /// The notices of the jobs, as `[1]+  Done                    sleep 1 &`.
+    pub fn reap(&mut self) -> Vec<String> {
+        self.update();
+
+        let ids = self
+            .entries()
+            .iter()
+            .map(|job| job.id)
+            .filter(|id| self.is_done(*id))
+            .collect::<Vec<usize>>();
+
+        ids.into_iter()
+            .filter_map(|id| {
+                let notice = self.format(id);
+
+                self.remove(id);
+
+                notice
+            })
+            .collect()
+    }
+}
+
+/// Wait for a process to change state, this is the only place the processes are reaped.
+///
+/// `interrupted` is called when the wait is interrupted by a signal, before waiting again.
+///
+/// # Returns
+/// The new state, `None` if the state did not change (`WNOHANG`) or the process can not be waited for.
+pub(super) fn wait_pid(pid: u32, options: libc::c_int, interrupted: impl Fn()) -> Option<JobState> {
+    let mut status = 0;
+
+    loop {
+        let ret = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, options) };
+
+        if ret == 0 {
+            return None;
+        }
+
+        if ret > 0 {
+            break;
+        }
+
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return None;
+        }
+
+        interrupted();
+    }
+
+    if libc::WIFSTOPPED(status) {
+        Some(JobState::Stopped(libc::WSTOPSIG(status)))
+    } else if libc::WIFCONTINUED(status) {
+        Some(JobState::Running)
+    } else {
+        Some(JobState::Done(ExitStatus::from(
+            process::ExitStatus::from_raw(status),
+        )))
+    }
+}
+
+fn get_termios() -> Option<libc::termios> {
+    let mut termios = MaybeUninit::uninit();
+
+    unsafe {
+        (libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) == 0)
+            .then(|| termios.assume_init())
+    }
+}
+
+fn set_termios(termios: &libc::termios) {
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, termios) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::process::CommandExt;
+
+    fn spawn(jobs: &mut Jobs, program: &str, arg: &str) -> u32 {
+        let child = std::process::Command::new(program)
+            .arg(arg)
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        jobs.handler_mut().push(child, false)
+    }
+
+    #[test]
+    fn test_jobs_find() {
+        let mut jobs = Jobs::new();
+
+        assert_eq!(jobs.find(None).unwrap_err().kind(), &ErrorKind::NotFound);
+
+        let a = jobs.push(1, vec![1], "a");
+        let b = jobs.push(2, vec![2], "b");
+
+        assert_eq!(jobs.find(None).unwrap(), b);
+        assert_eq!(jobs.find(Some("%%")).unwrap(), b);
+        assert_eq!(jobs.find(Some("%-")).unwrap(), a);
+        assert_eq!(jobs.find(Some("%1")).unwrap(), a);
+        assert_eq!(jobs.find(Some("2")).unwrap(), b);
+        assert!(jobs.find(Some("%3")).is_err());
+
+        jobs.touch(a);
+
+        assert_eq!(jobs.current(), Some(a));
+        assert_eq!(jobs.format(b).unwrap(), "[2]-  Running                 b");
+
+        jobs.remove(a);
+
+        // the job numbers are reused from the highest.
+        assert_eq!(jobs.push(3, vec![3], "c"), 3);
+        jobs.remove(3);
+        assert_eq!(jobs.push(4, vec![4], "d"), 3);
+    }
+
+    #[test]
+    fn test_jobs_wait() {
+        let mut jobs = Jobs::new();
+
+        let pids = vec![spawn(&mut jobs, "true", ""), spawn(&mut jobs, "false", "")];
+
+        let id = jobs.push(pids[0], pids.to_owned(), "true | false");
+
+        assert_eq!(jobs.wait(id), Some(JobState::Done(ExitStatus::FAILURE)));
+        assert_eq!(jobs.pids(id), pids);
+        assert_eq!(
+            jobs.process_state(pids[0]),
+            Some(JobState::Done(ExitStatus::SUCCESS))
+        );
+        assert_eq!(jobs.format(id).unwrap(), "[1]+  Exit 1                  true | false");
+
+        assert_eq!(jobs.reap().len(), 1);
+        assert!(jobs.is_empty());
+        assert!(jobs.handler().is_empty());
+    }
+
+    #[test]
+    fn test_jobs_reap_once() {
+        let mut jobs = Jobs::new();
+
+        let pid = spawn(&mut jobs, "true", "");
+
+        let id = jobs.push(pid, vec![pid], "true");
+
+        assert_eq!(jobs.wait(id), Some(JobState::Done(ExitStatus::SUCCESS)));
+
+        // the process is reaped, the process table checks its state instead of waiting or signaling it.
+        assert!(jobs.handler_mut().wait().is_empty());
+        assert!(jobs.handler_mut().kill(pid).is_ok());
+
+        jobs.update();
+
+        assert_eq!(jobs.process_state(pid), Some(JobState::Done(ExitStatus::SUCCESS)));
+    }
+
+    #[test]
+    fn test_jobs_remove_reap() {
+        let mut jobs = Jobs::new();
+
+        let pid = spawn(&mut jobs, "sleep", "0.1");
+
+        let id = jobs.push(pid, vec![pid], "sleep 0.1");
+
+        // the job is removed while it runs, as by `disown`, its process is still reaped.
+        jobs.remove(id);
+
+        assert_eq!(jobs.disowned, vec![pid]);
+
+        for _ in 0..100 {
+            jobs.update();
+
+            if jobs.disowned.is_empty() {
+                break;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        assert!(jobs.disowned.is_empty());
+        assert!(unsafe { libc::waitpid(pid as libc::pid_t, std::ptr::null_mut(), libc::WNOHANG) } < 0);
+    }
+
+    #[test]
+    fn test_jobs_stop() {
+        let mut jobs = Jobs::new();
+
+        let pid = spawn(&mut jobs, "sleep", "10");
+
+        let id = jobs.push(pid, vec![pid], "sleep 10");
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) };
+
+        assert_eq!(jobs.wait(id), Some(JobState::Stopped(libc::SIGSTOP)));
+        assert_eq!(jobs.state(id).status().code(), 128 + libc::SIGSTOP);
+
+        jobs.resume(id, false).unwrap();
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+
+        assert_eq!(
+            jobs.wait(id),
+            Some(JobState::Done(ExitStatus::from(process::ExitStatus::from_raw(libc::SIGTERM))))
+        );
+    }
+}
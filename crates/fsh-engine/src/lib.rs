@@ -1,14 +1,20 @@
 mod state;
 mod extract;
 mod eval;
-mod builtin;
+mod exit_status;
 
 // pub mod
 pub mod pipe;
 pub mod sh_vars;
 pub mod process_handler;
+pub mod module;
+pub mod job;
+pub mod signal;
+pub mod builtin;
+pub mod alias;
 
 // pub use
 pub use state::*;
 pub use eval::*;
-pub use sh_vars::*;
\ No newline at end of file
+pub use sh_vars::*;
+pub use exit_status::*;
\ No newline at end of file
@@ -0,0 +1,205 @@
+use fsh_common::{Error, ErrorKind, Result};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// The modules of the session.
+///
+/// A module is imported once per session, the modules being imported are kept to detect cycles.
+#[derive(Debug, Default)]
+pub struct Modules {
+    imported: HashSet<PathBuf>,
+    importing: Vec<PathBuf>,
+}
+
+impl Modules {
+    /// Create a new modules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the path of a module to its canonical path.
+    ///
+    /// A relative path is resolved against the directory of the importing module, or the current directory at the top level,
+    /// then against each directory of the search path.
+    ///
+    /// # Arguments
+    /// - `path` - The path of the import statement
+    /// - `current_dir` - The current directory of the shell
+    /// - `search_path` - The directories separated by `:`, usually `FSH_PATH`
+    pub fn resolve(
+        &self,
+        path: &str,
+        current_dir: &Path,
+        search_path: Option<&str>,
+    ) -> Result<PathBuf> {
+        let base = self
+            .importing
+            .last()
+            .and_then(|module| module.parent())
+            .unwrap_or(current_dir);
+
+        let mut candidates = vec![base.join(path)];
+
+        if Path::new(path).is_relative() {
+            candidates.extend(
+                search_path
+                    .unwrap_or_default()
+                    .split(':')
+                    .filter(|dir| !dir.is_empty())
+                    .map(|dir| Path::new(dir).join(path)),
+            );
+        }
+
+        candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .and_then(|candidate| candidate.canonicalize().ok())
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, &format!("Module not found: {path}")))
+    }
+
+    /// Check if the module was imported in this session.
+    pub fn is_imported(&self, path: &Path) -> bool {
+        self.imported.contains(path)
+    }
+
+    /// Start importing a module.
+    ///
+    /// # Errors
+    /// `ErrorKind::InvalidInput` - If the module is already being imported, that is, the imports form a cycle.
+    pub fn enter(&mut self, path: PathBuf) -> Result<()> {
+        if let Some(start) = self.importing.iter().position(|module| *module == path) {
+            let cycle = self.importing[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|module| module.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                &format!("Import cycle: {cycle}"),
+            ))?
+        }
+
+        self.importing.push(path);
+
+        Ok(())
+    }
+
+    /// Start running a script, the shell script or a file run by `source`.
+    ///
+    /// The imports of the script are resolved against its directory.
+    /// A script is not a module, so a script that runs itself is not a cycle.
+    pub fn enter_script(&mut self, path: PathBuf) {
+        self.importing.push(path);
+    }
+
+    /// Finish importing the current module, or running the current script.
+    ///
+    /// A module that failed is not marked as imported, it is imported again by the next import.
+    pub fn leave(&mut self, is_imported: bool) {
+        if let Some(path) = self.importing.pop() {
+            if is_imported {
+                self.imported.insert(path);
+            }
+        }
+    }
+}
+
+/// Get the namespace of a module, the file name without its extension.
+///
+/// # Example
+/// ```
+/// use fsh_engine::module::namespace;
+/// use std::path::Path;
+///
+/// assert_eq!(namespace(Path::new("lib/git.fsh")), "git");
+/// ```
+pub fn namespace(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Get the names of the variables assigned by a module, in its blocks included.
+pub(crate) fn variables(ast: &fsh_ast::Ast) -> Vec<String> {
+    match ast {
+        fsh_ast::Ast::Semicolon(statements) => statements.iter().flat_map(variables).collect(),
+        fsh_ast::Ast::Statement(fsh_ast::Statement::Assign(fsh_ast::Assign {
+            ident: fsh_ast::Expr::Ident(name),
+            ..
+        })) => vec![name.to_string()],
+        fsh_ast::Ast::Statement(fsh_ast::Statement::Match(r#match)) => r#match
+            .arms
+            .iter()
+            .flat_map(|arm| variables(&arm.body))
+            .collect(),
+        fsh_ast::Ast::Statement(fsh_ast::Statement::Try(r#try)) => std::iter::once(&*r#try.body)
+            .chain(r#try.catch.iter().map(|catch| &*catch.body))
+            .chain(r#try.finally.as_deref())
+            .flat_map(variables)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_modules_resolve() {
+        let dir = std::env::temp_dir().join(format!("fsh-module-{}", std::process::id()));
+
+        fs::create_dir_all(dir.join("lib")).unwrap();
+
+        fs::write(dir.join("lib/git.fsh"), "").unwrap();
+
+        let modules = Modules::new();
+
+        let path = modules.resolve("lib/git.fsh", &dir, None).unwrap();
+
+        assert_eq!(path, dir.join("lib/git.fsh").canonicalize().unwrap());
+
+        let search_path = format!("/nonexistent:{}", dir.join("lib").display());
+
+        assert_eq!(
+            modules
+                .resolve("git.fsh", Path::new("/"), Some(&search_path))
+                .unwrap(),
+            path
+        );
+
+        let err = modules.resolve("svn.fsh", &dir, None).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_modules_cycle() {
+        let mut modules = Modules::new();
+
+        modules.enter(PathBuf::from("/a.fsh")).unwrap();
+
+        modules.enter(PathBuf::from("/b.fsh")).unwrap();
+
+        let err = modules.enter(PathBuf::from("/a.fsh")).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InvalidInput);
+
+        assert_eq!(err.message(), "Import cycle: /a.fsh -> /b.fsh -> /a.fsh");
+
+        modules.leave(true);
+
+        modules.leave(false);
+
+        assert!(modules.is_imported(Path::new("/b.fsh")));
+
+        assert!(!modules.is_imported(Path::new("/a.fsh")));
+    }
+}
@@ -1,225 +1,203 @@
+use std::os::unix::io::{FromRawFd, OwnedFd};
 
-use std::os::unix::io::RawFd;
+use fsh_common::{Error, ErrorKind, Result};
 
-use fsh_common::{
-    Error,
-    ErrorKind,
-    Result
-};
+/// Create a pipe(2) pair, both ends are close-on-exec.
+///
+/// # Returns
+/// The read end and the write end
+///
+/// # Errors
+/// `ErrorKind::BrokenPipe` - If the pipe can not be created, as when the shell has too many open files.
+pub fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
 
-#[derive(Debug, PartialEq)]
-pub enum PipeState {
-    Closed = 0,
-    Sendable = 1,
-    Recvable = 2,
-}
+    // a process forked between pipe and fcntl would keep both ends open, pipe2 sets the flag at once.
+    #[cfg(target_os = "linux")]
+    let ret = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
 
-impl PipeState {
-    pub fn as_u32(self) -> u32 {
-        self as u32
-    }
+    #[cfg(target_os = "macos")]
+    let ret = unsafe { libc::pipe(fds.as_mut_ptr()) };
 
-    pub fn as_usize(self) -> usize {
-        self as usize
+    if ret < 0 {
+        Err(Error::new(ErrorKind::BrokenPipe, "Failed to create a pipe"))?
     }
 
-    pub fn from_u32(value: u32) -> Result<Self> {
-        match value {
-            0 => Ok(PipeState::Closed),
-            1 => Ok(PipeState::Sendable),
-            2 => Ok(PipeState::Recvable),
-            _ => Err(Error::new(ErrorKind::InvalidInput, "Invalid PipeState")),
-        }
-    }
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
 
-    pub fn from_usize(value: usize) -> Result<Self> {
-        match value {
-            0 => Ok(PipeState::Closed),
-            1 => Ok(PipeState::Sendable),
-            2 => Ok(PipeState::Recvable),
-            _ => Err(Error::new(ErrorKind::InvalidInput, "Invalid PipeState")),
-        }
+    #[cfg(target_os = "macos")]
+    for fd in [&reader, &writer] {
+        use std::os::unix::io::AsRawFd;
+
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
     }
+
+    Ok((reader, writer))
 }
 
-type FD = Option<std::os::unix::io::RawFd>;
+/// The stdio of a stage of a pipeline, `None` is inherited from the shell.
+///
+/// The file descriptors are closed when they are dropped, so an unused end never leaks.
+#[derive(Debug, Default)]
+pub struct Stdio {
+    pub stdin: Option<OwnedFd>,
+    pub stdout: Option<OwnedFd>,
+    pub stderr: Option<OwnedFd>,
+}
 
-#[derive(Debug, PartialEq)]
+/// The pipes of a pipeline.
+///
+/// Each stage but the last writes to a new pipe(2) pair, and the next stage reads from it.
+/// The pipe only keeps the read end for the next stage, the ends given to a stage are owned by the stage.
+///
+/// The ends are close-on-exec, a child keeps only the ends set as its stdio.
+#[derive(Debug, Default)]
 pub struct Pipe {
-    state: PipeState,
-    fd: FD,
+    /// The read end of the pipe of the previous stage.
+    reader: Option<OwnedFd>,
+    is_open: bool,
 }
 
 impl Pipe {
-    /// Create a new Pipe instance
+    /// Create a new closed Pipe instance
     pub fn new() -> Self {
-        Self {
-            state: PipeState::Closed,
-            fd: None,
-        }
+        Self::default()
     }
 
-    /// Open a new Pipe instance
+    /// Open a new Pipe instance, for a new pipeline
     pub fn open() -> Self {
         Self {
-            state: PipeState::Sendable,
-            fd: None,
+            reader: None,
+            is_open: true,
         }
     }
 
-    /// Get the pipe state
-    ///
-    /// Returns the current state of the pipe.
-    ///
-    /// # PipeState
-    /// - Closed: 0
-    /// - Sendable: 1
-    /// - Recvable: 2
-    pub fn state(&self) -> &PipeState {
-        &self.state
+    /// Check if the pipe is open
+    pub fn is_open(&self) -> bool {
+        self.is_open
     }
 
-    /// Get the file descriptor
+    /// Get the read end of the previous stage
     ///
-    /// Returns the value of std::os::unix::io::RawFd. if the pipe's FD is not set, that is, None, returns None.
-    pub fn fd(&self) -> Option<&RawFd> {
-        self.fd.as_ref()
+    /// Returns `None` for the first stage.
+    pub fn reader(&self) -> Option<&OwnedFd> {
+        self.reader.as_ref()
     }
 
-    /// Close the Pipe instance
+    /// Set up the stdio of the next stage.
     ///
-    /// FD is closed only when FD is greater than or equal to 0. In other words, it is not closed when FD is a negative number.
+    /// The stdin is the read end of the previous stage. a stage that is not the last writes its stdout,
+    /// and its stderr with `pipe_stderr` (`|&`), to a new pipe.
     ///
-    /// If you want to close all FDs regardless of their value, use `pub fn quit`.
+    /// # Arguments
+    /// - `is_last`: If the stage is the last of the pipeline
+    /// - `pipe_stderr`: If the stderr is piped with the stdout
     ///
     /// # Errors
-    /// FD is less than 0, return error.
-    pub fn close(&mut self) -> Result<()> {
-        // if FD is Some and FD is greater than or equal to 0,
-        //  use libc::close to close the FD.
-        if let Some(fd) = self.fd {
-            if fd >= 0 {
-                unsafe { libc::close(fd) };
-            } else {
-                Err(Error::new(ErrorKind::BrokenPipe, "Pipe is broken"))?
-            }
+    /// - Pipe is closed: If the pipe is closed.
+    /// - Failed to create a pipe: If the pipe can not be created.
+    pub fn stage(&mut self, is_last: bool, pipe_stderr: bool) -> Result<Stdio> {
+        if !self.is_open {
+            Err(Error::new(ErrorKind::BrokenPipe, "Pipe is closed"))?
         }
 
-        // Reset the state and file descriptor
-        // State: Closed
-        // FD: None
-        self.state = PipeState::Closed;
-        self.fd = None;
-        Ok(())
-    }
+        let mut stdio = Stdio {
+            stdin: self.reader.take(),
+            ..Stdio::default()
+        };
 
-    /// Quit the Pipe instance
-    ///
-    /// In most cases, it works the same as `pub fn close`. The difference is that it forcibly closes the FD regardless of its value.
-    ///
-    /// How to use it with `pub fn close`: close operates on the assumption that FD is greater than or equal to 0, and will generate an error if fd < 0.
-    /// however, quit will close the FD even if fd is a negative number. Therefore, the Pipe and captured FD are always closed, so it can be safely used with `ManuallyDrop` etc.
-    pub fn quit(&mut self) {
-        if let Some(fd) = self.fd.take() {
-            unsafe { libc::close(fd) };
+        if is_last {
+            return Ok(stdio);
         }
 
-        self.state = PipeState::Closed;
-        self.fd = None;
-    }
+        let (reader, writer) = pipe()?;
 
-    /// Cancel the Pipe state
-    ///
-    /// If FD is Some, it is forcibly Closed regardless of the FD value. Then, the Pipe is reset to a transmittable state.
-    pub fn cancel(mut self) {
-        if let Some(fd) = self.fd {
-            unsafe { libc::close(fd) };
+        if pipe_stderr {
+            stdio.stderr = Some(writer.try_clone().map_err(|_| {
+                Error::new(ErrorKind::BrokenPipe, "Failed to duplicate the pipe")
+            })?);
         }
 
-        self.state = PipeState::Sendable;
-        self.fd = None;
+        stdio.stdout = Some(writer);
+
+        self.reader = Some(reader);
+
+        Ok(stdio)
     }
-    
-    /// Check if the Pipe instance is sendable
-    pub fn is_sendable(&self) -> bool {
-        self.state == PipeState::Sendable
+
+    /// Close the Pipe instance
+    ///
+    /// The read end that no stage took is closed.
+    pub fn close(&mut self) {
+        self.reader = None;
+        self.is_open = false;
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        os::unix::io::AsRawFd,
+    };
 
-    /// Check if the Pipe instance is recvable
-    pub fn is_recvable(&self) -> bool {
-        self.state == PipeState::Recvable
+    fn is_cloexec(fd: &OwnedFd) -> bool {
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) & libc::FD_CLOEXEC != 0 }
     }
 
-    /// Send the file descriptor to the Pipe
-    ///
-    /// If the state is Sendable, the file descriptor is set to the Pipe instance.
-    ///
-    /// # Arguments
-    /// - `fd`: File descriptor
-    ///
-    /// # Errors
-    /// - Pipe is broken: If the File descriptor is Some.
-    /// - Pipe is closed: If the Pipe is closed.
-    /// - Pipe is in recvable state: If the Pipe is in recvable state.
-    pub fn send(&mut self, fd: std::os::unix::io::RawFd) -> Result<()> {
-        // if the state is Sendable, fd is None.
-        if self.fd.is_some() {
-            Err(Error::new(ErrorKind::BrokenPipe, "Pipe is broken"))?
-        }
+    #[test]
+    fn test_pipe() {
+        let (reader, writer) = pipe().unwrap();
 
-        match self.state {
-            PipeState::Sendable => {
-                self.fd = Some(fd);
-                self.state = PipeState::Recvable;
-                Ok(())
-            }
+        assert!(is_cloexec(&reader));
+        assert!(is_cloexec(&writer));
 
-            PipeState::Closed => Err(Error::new(ErrorKind::BrokenPipe, "Pipe is closed")),
+        File::from(writer).write_all(b"hello").unwrap();
 
-            PipeState::Recvable => Err(Error::new(
-                ErrorKind::BrokenPipe,
-                "Pipe is in recvable state",
-            )),
-        }
+        let mut buf = String::new();
+
+        // the write end is dropped, the read reaches the end.
+        File::from(reader).read_to_string(&mut buf).unwrap();
+
+        assert_eq!(buf, "hello");
     }
 
-    /// Receive the file descriptor from the Pipe
-    ///
-    /// You can receive the sent FD. For example, if you send FD 3 using send, recv will return FD 3.
-    ///
-    /// # Errors
-    /// - Pipe is broken: If the File descriptor is None.
-    /// - Pipe is closed: If the Pipe is closed.
-    /// - Pipe is in sendable state: If the Pipe is in sendable state.
-    pub fn recv(&mut self) -> Result<std::os::unix::io::RawFd> {
-        // if the state is Recvable, fd is Some.
-        if self.fd.is_none() {
-            Err(Error::new(ErrorKind::BrokenPipe, "Pipe is broken"))?
-        }
+    #[test]
+    fn test_pipe_stage() {
+        let mut pipe = Pipe::new();
 
-        match self.state {
-            PipeState::Recvable => {
-                // get fd
-                let fd = self.fd.unwrap_or(-1);
+        assert!(pipe.stage(true, false).is_err());
 
-                // fd is less than 0, return error
-                if fd < 0 {
-                    Err(Error::new(ErrorKind::BrokenPipe, "Pipe is broken"))?
-                }
+        let mut pipe = Pipe::open();
 
-                // reset the state and file descriptor
-                self.state = PipeState::Sendable;
-                self.fd = None;
+        let first = pipe.stage(false, true).unwrap();
 
-                Ok(fd)
-            }
+        assert!(first.stdin.is_none());
+        assert!(first.stdout.is_some());
+        assert!(first.stderr.is_some());
+        assert!(pipe.reader().is_some());
 
-            PipeState::Closed => Err(Error::new(ErrorKind::BrokenPipe, "Pipe is closed")),
+        let last = pipe.stage(true, false).unwrap();
 
-            PipeState::Sendable => Err(Error::new(
-                ErrorKind::BrokenPipe,
-                "Pipe is in sendable state",
-            )),
-        }
+        assert!(last.stdin.is_some());
+        assert!(last.stdout.is_none());
+        assert!(pipe.reader().is_none());
+
+        // the stderr writes to the same pipe as the stdout.
+        File::from(first.stderr.unwrap()).write_all(b"err ").unwrap();
+        File::from(first.stdout.unwrap()).write_all(b"out").unwrap();
+
+        let mut buf = String::new();
+
+        File::from(last.stdin.unwrap()).read_to_string(&mut buf).unwrap();
+
+        assert_eq!(buf, "err out");
+
+        pipe.close();
+
+        assert!(!pipe.is_open());
     }
-}
\ No newline at end of file
+}
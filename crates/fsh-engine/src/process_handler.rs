@@ -1,15 +1,31 @@
 use fsh_common::{Error, ErrorKind, Result};
-use std::mem::ManuallyDrop;
 
-/// ProcessHandler is a Vec that stores processes (specifically, std::process::*).
+use super::{
+    job::{wait_pid, JobState},
+    ExitStatus,
+};
+
+/// A process of the handler.
+#[derive(Debug)]
+struct Process {
+    pid: u32,
+    /// The child, `None` for a subshell forked by the shell.
+    child: Option<std::process::Child>,
+    /// The job of the process, 0 if the process is not in a job.
+    job: usize,
+    is_background: bool,
+    state: JobState,
+}
+
+/// ProcessHandler is the process table, it stores processes (specifically, std::process::Child, or the process id of a subshell).
 ///
-/// all process states are stored here.
+/// all process states are stored here, as `Running`, `Stopped` or `Done(status)`.
 ///
-/// Think of ProcessHandler as simply a Vector (Vec).
+/// processes are grouped by job, in order of the pipeline. a process stays in the handler after it is done, until it is removed, see `remove` and `remove_job`.
 ///
-/// to empty ProcessHandler, you can use high-level operations such as deleting the ProcessHandler instance. ProcessHandler does not provide a remove method like Vec. This is intentional.
+/// the processes are reaped by `waitpid` of the job table only, never by `Child::wait`, so a process that is done is never signaled or waited for again.
 #[derive(Debug)]
-pub struct ProcessHandler(Vec<(ManuallyDrop<std::process::Child>, bool)>);
+pub struct ProcessHandler(Vec<Process>);
 
 impl ProcessHandler {
     /// Create a new handler
@@ -24,6 +40,8 @@ impl ProcessHandler {
 
     /// Push a process to the handler
     ///
+    /// The process is not in a job, until `set_job`.
+    ///
     /// # Arguments
     /// - `ps` - The process to push
     /// - `is_background` - If the process is a background process
@@ -33,35 +51,117 @@ impl ProcessHandler {
     pub fn push(&mut self, ps: std::process::Child, is_background: bool) -> u32 {
         let pid = ps.id();
 
-        self.0.push((ManuallyDrop::new(ps), is_background));
+        self.0.push(Process {
+            pid,
+            child: Some(ps),
+            job: 0,
+            is_background,
+            state: JobState::Running,
+        });
+
+        pid
+    }
+
+    /// Push a subshell to the handler, a process forked by the shell that has no `std::process::Child`.
+    ///
+    /// The process is not in a job, until `set_job`.
+    pub fn push_subshell(&mut self, pid: u32, is_background: bool) -> u32 {
+        self.0.push(Process {
+            pid,
+            child: None,
+            job: 0,
+            is_background,
+            state: JobState::Running,
+        });
 
         pid
     }
 
     /// Pop a process from the handler
+    ///
+    /// A subshell is popped too, but it has no child.
     pub fn pop(&mut self) -> Option<std::process::Child> {
-        self.0.pop().map(|(ps, _)| ManuallyDrop::into_inner(ps))
+        self.0.pop().and_then(|process| process.child)
+    }
+
+    /// Remove a process from the handler
+    ///
+    /// # Returns
+    /// The process, `None` if the handler has no process with the id, or if it is a subshell.
+    pub fn remove(&mut self, pid: u32) -> Option<std::process::Child> {
+        let index = self.0.iter().position(|process| process.pid == pid)?;
+
+        self.0.remove(index).child
+    }
+
+    /// Remove the processes of a job from the handler
+    ///
+    /// # Returns
+    /// The processes, in order of the pipeline, without the subshells.
+    pub fn remove_job(&mut self, job: usize) -> Vec<std::process::Child> {
+        let (removed, kept) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition(|process| process.job == job);
+
+        self.0 = kept;
+
+        removed.into_iter().filter_map(|process| process.child).collect()
     }
 
     /// Get a process from the handler
     pub fn get(&self, pid: u32) -> Option<&std::process::Child> {
-        self.0
-            .iter()
-            .find(|(ps, _)| ps.id() == pid)
-            .map(|(ps, _)| &**ps)
+        self.find(pid).and_then(|process| process.child.as_ref())
     }
 
     /// Get a mutable process from the handler
     pub fn get_mut(&mut self, pid: u32) -> Option<&mut std::process::Child> {
-        self.0
-            .iter_mut()
-            .find(|(ps, _)| ps.id() == pid)
-            .map(|(ps, _)| &mut **ps)
+        self.find_mut(pid).and_then(|process| process.child.as_mut())
     }
 
-    /// Get all processes from the handler
+    /// Get all processes from the handler, without the subshells
     pub fn entries(&self) -> Vec<&std::process::Child> {
-        self.0.iter().map(|(ps, _)| &**ps).collect()
+        self.0.iter().filter_map(|process| process.child.as_ref()).collect()
+    }
+
+    /// Get the state of a process
+    pub fn state(&self, pid: u32) -> Option<JobState> {
+        self.find(pid).map(|process| process.state)
+    }
+
+    /// Set the state of a process, as reported by `waitpid`.
+    pub fn set_state(&mut self, pid: u32, state: JobState) {
+        if let Some(process) = self.find_mut(pid) {
+            process.state = state;
+        }
+    }
+
+    /// Put a process in a job
+    pub fn set_job(&mut self, pid: u32, job: usize) {
+        if let Some(process) = self.find_mut(pid) {
+            process.job = job;
+        }
+    }
+
+    /// Get the process ids of a job, in order of the pipeline.
+    pub fn job(&self, job: usize) -> Vec<u32> {
+        self.0
+            .iter()
+            .filter(|process| process.job == job)
+            .map(|process| process.pid)
+            .collect()
+    }
+
+    /// Get the jobs of the processes, in order of their first process.
+    pub fn jobs(&self) -> Vec<usize> {
+        let mut jobs = Vec::new();
+
+        for process in &self.0 {
+            if process.job != 0 && !jobs.contains(&process.job) {
+                jobs.push(process.job);
+            }
+        }
+
+        jobs
     }
 
     /// Check if the handler is empty
@@ -80,23 +180,18 @@ impl ProcessHandler {
     }
 
     /// Kill a process
+    ///
+    /// The process stays in the handler, its state is updated when it is waited for.
+    /// A process that is done is not signaled, its process id may be reused.
     pub fn kill(&mut self, pid: u32) -> Result<()> {
-        self.0.iter_mut().try_for_each(|(ps, _)| -> Result<()> {
-            if ps.id() == pid {
-                // try to kill the process
-                let kill = ps.kill();
-
-                // drop the process
-                unsafe {
-                    ManuallyDrop::drop(ps);
-                }
-
-                // check if the process was killed
-                kill.map_err(|_| Error::new(ErrorKind::Failure, "Failed to kill the process."))?;
+        match self.state(pid) {
+            Some(JobState::Running | JobState::Stopped(_))
+                if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } < 0 =>
+            {
+                Err(Error::new(ErrorKind::Failure, "Failed to kill the process."))
             }
-
-            Ok(())
-        })
+            _ => Ok(()),
+        }
     }
 
     /// Wait for all processes
@@ -107,47 +202,47 @@ impl ProcessHandler {
     ///
     /// however, background processes can be made to run completely in the background by combining them with nohup. additionally, such processes will be automatically closed after they terminate.
     ///
-    /// Processes are dropped after wait, but this does not mean they are removed from the ProcessHandler. it simply refers to the dropping of the processes themselves.
+    /// Processes that are already done are not waited for again.
     ///
     /// # Returns
-    /// A vector of process id and exit status
-    pub fn wait(&mut self) -> Vec<(u32, std::process::ExitStatus)> {
+    /// A vector of process id and exit status, of the processes that are done by this wait
+    pub fn wait(&mut self) -> Vec<(u32, ExitStatus)> {
         let mut v = Vec::with_capacity(self.0.len());
 
-        self.0.iter_mut().for_each(|(ps, is_background)| {
-            if *is_background == true {
-                // background process
-                if let Ok(exitstatus) = ps.try_wait() {
-                    if let Some(exitstatus) = exitstatus {
-                        v.push((ps.id(), exitstatus));
-                    }
-
-                    unsafe {
-                        ManuallyDrop::drop(ps);
-                    }
-                }
-            } else {
-                // foreground process
-                if let Ok(status) = ps.wait() {
-                    v.push((ps.id(), status));
+        self.0
+            .iter_mut()
+            .filter(|process| !matches!(process.state, JobState::Done(_)))
+            .for_each(|process| {
+                let options = if process.is_background {
+                    // background process
+                    libc::WNOHANG
+                } else {
+                    // foreground process
+                    0
+                };
+
+                if let Some(JobState::Done(status)) = wait_pid(process.pid, options, || {}) {
+                    process.state = JobState::Done(status);
+
+                    v.push((process.pid, status));
                 }
-
-                unsafe {
-                    ManuallyDrop::drop(ps);
-                }
-            }
-        });
+            });
 
         v
     }
+
+    fn find(&self, pid: u32) -> Option<&Process> {
+        self.0.iter().find(|process| process.pid == pid)
+    }
+
+    fn find_mut(&mut self, pid: u32) -> Option<&mut Process> {
+        self.0.iter_mut().find(|process| process.pid == pid)
+    }
 }
 
-impl Drop for ProcessHandler {
-    /// It simply refers to the dropping of the processes themselves.
-    fn drop(&mut self) {
-        self.0.iter_mut().for_each(|(ps, _)| unsafe {
-            ManuallyDrop::drop(ps);
-        });
+impl Default for ProcessHandler {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -159,7 +254,7 @@ mod tests {
     fn test_process_handler() {
         let handler = ProcessHandler::new();
 
-        assert_eq!(handler.is_empty(), true);
+        assert!(handler.is_empty());
 
         assert_eq!(handler.len(), 0);
     }
@@ -198,6 +293,21 @@ mod tests {
         assert_eq!(handler.len(), 0);
     }
 
+    #[test]
+    fn test_process_handler_remove() {
+        let mut handler = ProcessHandler::new();
+
+        let ps = std::process::Command::new("true").spawn().unwrap();
+
+        let pid = handler.push(ps, false);
+
+        assert!(handler.remove(pid + 1).is_none());
+
+        assert_eq!(handler.remove(pid).unwrap().wait().unwrap().code(), Some(0));
+
+        assert!(handler.is_empty());
+    }
+
     #[test]
     fn test_process_handler_wait() {
         let mut handler = ProcessHandler::new();
@@ -221,9 +331,61 @@ mod tests {
         assert_eq!(v.len(), 2);
 
         for (pid, status) in v {
-            assert_eq!(status.success(), true);
+            assert!(status.success());
 
             assert_eq!(handler.get(pid).unwrap().id(), pid);
+
+            assert_eq!(handler.state(pid), Some(JobState::Done(ExitStatus::SUCCESS)));
         }
+
+        // waiting twice is safe, the processes are already done.
+        assert!(handler.wait().is_empty());
+    }
+
+    #[test]
+    fn test_process_handler_kill() {
+        let mut handler = ProcessHandler::new();
+
+        let ps = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+
+        let pid = handler.push(ps, false);
+
+        handler.kill(pid).unwrap();
+
+        let v = handler.wait();
+
+        assert_eq!(v.len(), 1);
+
+        assert_eq!(handler.state(pid), Some(JobState::Done(v[0].1)));
+
+        // killing a process that is done, then dropping the handler, is safe.
+        handler.kill(pid).unwrap();
+
+        drop(handler);
+    }
+
+    #[test]
+    fn test_process_handler_job() {
+        let mut handler = ProcessHandler::new();
+
+        let spawn = || std::process::Command::new("true").spawn().unwrap();
+
+        let a = handler.push(spawn(), false);
+        let b = handler.push(spawn(), false);
+        let c = handler.push(spawn(), false);
+
+        handler.set_job(a, 1);
+        handler.set_job(b, 2);
+        handler.set_job(c, 1);
+
+        assert_eq!(handler.job(1), [a, c]);
+        assert_eq!(handler.jobs(), [1, 2]);
+
+        let removed = handler.remove_job(1);
+
+        assert_eq!(removed.iter().map(|ps| ps.id()).collect::<Vec<u32>>(), [a, c]);
+        assert_eq!(handler.jobs(), [2]);
+
+        handler.wait();
     }
 }
@@ -1,26 +1,56 @@
 use fsh_common::{Error, ErrorKind, Result};
-use std::{collections::HashMap, fs, io::Write, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    path::Path,
+};
 
 
 pub const RESERVEDWORD_SHELL_VARIABLE_FSH_PROMPT: &str = "FSH_PROMPT";
 
+pub const RESERVEDWORD_SHELL_VARIABLE_FSH_PROMPT2: &str = "FSH_PROMPT2";
+
 pub const RESERVEDWORD_SHELL_VARIABLE_FSH_CWD: &str = "FSH_CWD";
 
+/// The directories searched for modules, separated by `:`.
+pub const RESERVEDWORD_SHELL_VARIABLE_FSH_PATH: &str = "FSH_PATH";
+
+/// The exit status of the last pipeline, as `$?`.
+pub const RESERVEDWORD_SHELL_VARIABLE_STATUS: &str = "?";
+
+/// The process id of the last command of the last background job, as `$!`.
+pub const RESERVEDWORD_SHELL_VARIABLE_LAST_BACKGROUND: &str = "!";
+
+/// The exit statuses of the commands of the last pipeline, separated by spaces.
+pub const RESERVEDWORD_SHELL_VARIABLE_PIPESTATUS: &str = "PIPESTATUS";
+
+/// The name of the script, as `$0`.
+pub const RESERVEDWORD_SHELL_VARIABLE_SCRIPT_NAME: &str = "0";
+
+/// The number of the positional parameters, as `$#`.
+pub const RESERVEDWORD_SHELL_VARIABLE_ARGC: &str = "#";
+
+/// The positional parameters separated by spaces, as `$*`.
+pub const RESERVEDWORD_SHELL_VARIABLE_ARGS: &str = "*";
+
 /// Shell variables.
+///
+/// The variables, and the names of the exported variables. only the exported variables are in the environment of the commands.
 #[derive(Debug, Clone)]
-pub struct ShVars(HashMap<String, String>);
+pub struct ShVars(HashMap<String, String>, HashSet<String>);
 
 impl ShVars {
     /// Create a new instance of `ShVars`.
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self(HashMap::new(), HashSet::new())
     }
 
-    /// Inherit environment variables.
+    /// Inherit environment variables, they are exported.
     ///
     /// # Example
     /// ```
-    /// use flat_engine::ShVars;
+    /// use fsh_engine::ShVars;
     ///
     /// let mut vars = ShVars::new();
     ///
@@ -28,6 +58,7 @@ impl ShVars {
     /// ```
     pub fn inherit(&mut self, env_vars: std::env::Vars) -> &mut Self {
         for (key, value) in env_vars {
+            self.1.insert(key.to_owned());
             self.0.insert(key, value);
         }
 
@@ -74,12 +105,12 @@ impl ShVars {
                 value.trim_start().trim_end().to_string(),
             );
         }
-        Ok(Self(vars))
+        Ok(Self(vars, HashSet::new()))
     }
 
     /// Save shell variables to a file.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let mut file = match fs::File::options().create(true).write(true).open(path) {
+        let mut file = match fs::File::options().create(true).truncate(true).write(true).open(path) {
             Ok(file) => file,
             Err(err) => {
                 if err.kind() == std::io::ErrorKind::PermissionDenied {
@@ -123,11 +154,11 @@ impl ShVars {
     ///
     /// # Example
     /// ```
-    /// use flat_engine::ShVars;
+    /// use fsh_engine::ShVars;
     ///
     /// let mut vars = ShVars::new();
     ///
-    /// vars.set("key", "value");
+    /// vars.insert("key", "value");
     ///
     /// assert_eq!(vars.get("key").unwrap(), "value");
     /// ```
@@ -144,6 +175,10 @@ impl ShVars {
         self.get(RESERVEDWORD_SHELL_VARIABLE_FSH_PROMPT)
     }
 
+    pub fn get_prompt2(&self) -> Result<&str> {
+        self.get(RESERVEDWORD_SHELL_VARIABLE_FSH_PROMPT2)
+    }
+
     pub fn get_cwd(&self) -> Result<&str> {
         self.get(RESERVEDWORD_SHELL_VARIABLE_FSH_CWD)
     }
@@ -152,11 +187,11 @@ impl ShVars {
     ///
     /// # Example
     /// ```
-    /// use flat_engine::ShVars;
+    /// use fsh_engine::ShVars;
     ///
     /// let mut vars = ShVars::new();
     ///
-    /// vars.set("key", "value");
+    /// vars.insert("key", "value");
     ///
     /// assert_eq!(vars.get("key").unwrap(), "value");
     /// ```
@@ -168,29 +203,111 @@ impl ShVars {
     ///
     /// # Example
     /// ```
-    /// use flat_engine::ShVars;
+    /// use fsh_engine::ShVars;
     ///
     /// let mut vars = ShVars::new();
     ///
-    /// vars.set("key", "value");
+    /// vars.insert("key", "value");
     ///
-    /// vars.unset("key");
+    /// vars.remove("key");
     ///
     /// assert_eq!(vars.exists("key"), false);
     /// ```
     pub fn remove(&mut self, key: &str) {
         self.0.remove(key);
+        self.1.remove(key);
+    }
+
+    /// Get the positional parameters, `$1`, `$2`...
+    pub fn args(&self) -> Vec<String> {
+        let argc = self
+            .get(RESERVEDWORD_SHELL_VARIABLE_ARGC)
+            .ok()
+            .and_then(|argc| argc.parse().ok())
+            .unwrap_or(0);
+
+        (1..=argc)
+            .map(|i: usize| self.get(&i.to_string()).unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Set the positional parameters, `$1`, `$2`..., with `$#` and `$*`.
+    ///
+    /// The previous positional parameters are unset.
+    ///
+    /// # Example
+    /// ```
+    /// use fsh_engine::ShVars;
+    ///
+    /// let mut vars = ShVars::new();
+    ///
+    /// vars.set_args(&["a".to_string(), "b".to_string()]);
+    ///
+    /// assert_eq!(vars.get("2").unwrap(), "b");
+    /// assert_eq!(vars.get("#").unwrap(), "2");
+    /// assert_eq!(vars.get("*").unwrap(), "a b");
+    /// ```
+    pub fn set_args(&mut self, args: &[String]) {
+        for i in 1..=self.args().len() {
+            self.remove(&i.to_string());
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            self.insert((i + 1).to_string(), arg);
+        }
+
+        self.insert(RESERVEDWORD_SHELL_VARIABLE_ARGC, args.len().to_string());
+        self.insert(RESERVEDWORD_SHELL_VARIABLE_ARGS, args.join(" "));
+    }
+
+    /// Export a shell variable, to the environment of the commands.
+    ///
+    /// A name without a value is exported once it is set.
+    ///
+    /// # Example
+    /// ```
+    /// use fsh_engine::ShVars;
+    ///
+    /// let mut vars = ShVars::new();
+    ///
+    /// vars.insert("key", "value");
+    ///
+    /// vars.export("key");
+    ///
+    /// assert_eq!(vars.exports(), [("key", "value")]);
+    /// ```
+    pub fn export(&mut self, key: impl Into<String>) {
+        self.1.insert(key.into());
+    }
+
+    /// Check if a shell variable is exported.
+    pub fn is_exported(&self, key: &str) -> bool {
+        self.1.contains(key)
+    }
+
+    /// Get the exported shell variables, the environment of the commands, in order of key.
+    pub fn exports(&self) -> Vec<(&str, &str)> {
+        let mut exports = self
+            .0
+            .iter()
+            .filter(|(key, _)| self.1.contains(*key))
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+
+        exports.sort();
+
+        exports
     }
 
     /// Check if a shell variable exists.
     ///
     /// # Example
     /// ```
-    /// use flat_engine::ShVars;
+    /// use fsh_engine::ShVars;
     ///
     /// let mut vars = ShVars::new();
     ///
-    /// vars.set("key", "value");
+    /// vars.insert("key", "value");
     ///
     /// assert_eq!(vars.exists("key"), true);
     /// ```
@@ -211,6 +328,7 @@ impl ShVars {
     /// Clear shell variables.
     pub fn clear(&mut self) {
         self.0.clear();
+        self.1.clear();
     }
 
     /// Get all keys of shell variables.
@@ -229,9 +347,15 @@ impl ShVars {
     }
 }
 
+impl Default for ShVars {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<HashMap<String, String>> for ShVars {
     fn from(vars: HashMap<String, String>) -> Self {
-        Self(vars)
+        Self(vars, HashSet::new())
     }
 }
 
@@ -241,7 +365,7 @@ impl From<HashMap<&str, &str>> for ShVars {
         for (key, value) in vars {
             map.insert(key.to_string(), value.to_string());
         }
-        Self(map)
+        Self(map, HashSet::new())
     }
 }
 
@@ -255,7 +379,7 @@ mod tests {
 
         assert_eq!(vars.len(), 0);
 
-        assert_eq!(vars.is_empty(), true);
+        assert!(vars.is_empty());
     }
 
     #[test]
@@ -275,7 +399,7 @@ mod tests {
 
         assert_eq!(vars.len(), 10);
 
-        assert_eq!(vars.is_empty(), false);
+        assert!(!vars.is_empty());
     }
 
     #[test]
@@ -295,7 +419,7 @@ mod tests {
 
         assert_eq!(vars.len(), 3);
 
-        assert_eq!(vars.is_empty(), false);
+        assert!(!vars.is_empty());
     }
 
     #[test]
@@ -308,11 +432,69 @@ mod tests {
 
         vars.remove("key1");
 
-        assert_eq!(vars.exists("key1"), false);
+        assert!(!vars.exists("key1"));
 
         assert_eq!(vars.len(), 2);
     }
 
+    #[test]
+    fn test_sh_vars_save() {
+        let path = std::env::temp_dir().join(format!("fsh-test-save-{}", std::process::id()));
+
+        fs::write(&path, "key1=value1\nkey2=value2\nkey3=value3\n").unwrap();
+
+        let mut vars = ShVars::new();
+
+        vars.insert("a", "1");
+
+        // the file is replaced, the lines of a longer file are not left after the variables.
+        vars.save(&path).unwrap();
+
+        let saved = ShVars::open(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved.len(), 1);
+
+        assert_eq!(saved.get("a").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_sh_vars_export() {
+        let mut vars = ShVars::new();
+
+        vars.export("b");
+        vars.insert("a", "1");
+        vars.insert("b", "2");
+        vars.insert("c", "3");
+        vars.export("c");
+
+        assert!(!vars.is_exported("a"));
+        assert_eq!(vars.exports(), [("b", "2"), ("c", "3")]);
+
+        vars.remove("b");
+        vars.insert("b", "4");
+
+        assert_eq!(vars.exports(), [("c", "3")]);
+    }
+
+    #[test]
+    fn test_sh_vars_args() {
+        let mut vars = ShVars::new();
+
+        assert!(vars.args().is_empty());
+
+        vars.set_args(&["a".to_string(), "b c".to_string()]);
+
+        assert_eq!(vars.args(), ["a", "b c"]);
+
+        vars.set_args(&["d".to_string()]);
+
+        assert_eq!(vars.args(), ["d"]);
+        assert!(!vars.exists("2"));
+        assert_eq!(vars.get("*").unwrap(), "d");
+    }
+
     #[test]
     fn test_sh_vars_clear() {
         let mut vars = ShVars::from(HashMap::from([
@@ -325,6 +507,6 @@ mod tests {
 
         assert_eq!(vars.len(), 0);
 
-        assert_eq!(vars.is_empty(), true);
+        assert!(vars.is_empty());
     }
 }
@@ -0,0 +1,391 @@
+use fsh_common::{Error, ErrorKind, Result};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The signals received and not yet handled, one bit per signal.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// The signals received and not yet forwarded to the foreground job.
+static UNFORWARDED: AtomicU64 = AtomicU64::new(0);
+
+/// The signals that the shell forwards to the foreground job.
+const FORWARDED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGQUIT];
+
+/// The signals of job control, ignored by an interactive shell.
+const JOB_CONTROL: [libc::c_int; 3] = [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU];
+
+const NAMES: [(&str, libc::c_int); 24] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+extern "C" fn record(signal: libc::c_int) {
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+    UNFORWARDED.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+/// What the shell does when it receives a signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Disposition {
+    Ignore,
+    /// The signal is recorded, and handled between commands.
+    Record,
+}
+
+/// Set the disposition of a signal.
+///
+/// # Returns
+/// The previous action of the signal.
+fn set_disposition(signal: libc::c_int, disposition: Disposition) -> libc::sigaction {
+    let handler = match disposition {
+        Disposition::Ignore => libc::SIG_IGN,
+        Disposition::Record => record as extern "C" fn(libc::c_int) as libc::sighandler_t,
+    };
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+
+        let mut previous: libc::sigaction = std::mem::zeroed();
+
+        action.sa_sigaction = handler;
+
+        // without SA_RESTART, a blocking wait is interrupted, so the signal can be forwarded.
+        action.sa_flags = 0;
+
+        libc::sigemptyset(&mut action.sa_mask);
+
+        libc::sigaction(signal, &action, &mut previous);
+
+        previous
+    }
+}
+
+/// Handle SIGINT and SIGQUIT, instead of being killed by them.
+///
+/// While a foreground job runs, they are forwarded to the job.
+pub fn init() {
+    for signal in FORWARDED {
+        set_disposition(signal, Disposition::Record);
+    }
+}
+
+/// The dispositions of SIGINT and SIGQUIT before `init`, restored when dropped.
+///
+/// The tests share one process, a test that handles the signals must not leave them handled for the others.
+#[cfg(test)]
+pub(super) struct InitGuard(Vec<(libc::c_int, libc::sigaction)>);
+
+/// Handle SIGINT and SIGQUIT as `init`, until the guard is dropped.
+#[cfg(test)]
+pub(super) fn init_guard() -> InitGuard {
+    InitGuard(
+        FORWARDED
+            .into_iter()
+            .map(|signal| (signal, set_disposition(signal, Disposition::Record)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+impl Drop for InitGuard {
+    fn drop(&mut self) {
+        for (signal, action) in &self.0 {
+            unsafe { libc::sigaction(*signal, action, std::ptr::null_mut()) };
+
+            take(*signal);
+        }
+
+        UNFORWARDED.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Check if the signal was received since the last call.
+pub fn take(signal: libc::c_int) -> bool {
+    PENDING.fetch_and(!(1 << signal), Ordering::SeqCst) & (1 << signal) != 0
+}
+
+/// Forward SIGINT and SIGQUIT, if received, to a process group.
+pub(super) fn forward(pgid: u32) {
+    let unforwarded = UNFORWARDED.swap(0, Ordering::SeqCst);
+
+    FORWARDED
+        .into_iter()
+        .filter(|signal| unforwarded & (1 << signal) != 0)
+        .for_each(|signal| unsafe {
+            libc::kill(-(pgid as libc::pid_t), signal);
+        });
+}
+
+/// Reset the signals handled by the shell to their default disposition, in a new process.
+///
+/// The signals ignored by `trap '' SIGNAL` stay ignored.
+///
+/// # Safety
+/// Only async-signal-safe functions are called, this can run between fork and exec.
+pub(super) unsafe fn reset(ignored: &[libc::c_int]) {
+    FORWARDED
+        .into_iter()
+        .chain(JOB_CONTROL)
+        .filter(|signal| !ignored.contains(signal))
+        .for_each(|signal| {
+            libc::signal(signal, libc::SIG_DFL);
+        });
+}
+
+/// A condition of a trap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trap {
+    /// The shell exits.
+    Exit,
+    /// A pipeline exits with a non-zero status.
+    Err,
+    /// The shell receives the signal.
+    Signal(libc::c_int),
+}
+
+impl Trap {
+    /// Parse a trap condition: `EXIT`, `ERR`, a signal name with or without `SIG`, or a signal number.
+    ///
+    /// # Example
+    /// ```
+    /// use fsh_engine::signal::Trap;
+    ///
+    /// assert_eq!(Trap::parse("SIGINT"), Some(Trap::Signal(2)));
+    /// assert_eq!(Trap::parse("int"), Some(Trap::Signal(2)));
+    /// assert_eq!(Trap::parse("0"), Some(Trap::Exit));
+    /// ```
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase();
+
+        match name.as_str() {
+            "EXIT" | "0" => return Some(Trap::Exit),
+            "ERR" => return Some(Trap::Err),
+            _ => {}
+        }
+
+        if let Ok(number) = name.parse::<libc::c_int>() {
+            return NAMES
+                .iter()
+                .any(|(_, signal)| *signal == number)
+                .then_some(Trap::Signal(number));
+        }
+
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+
+        NAMES
+            .iter()
+            .find(|(signal_name, _)| *signal_name == name)
+            .map(|(_, signal)| Trap::Signal(*signal))
+    }
+
+    /// The name of the condition, `EXIT`, `ERR` or `SIGINT`.
+    pub fn name(&self) -> String {
+        match self {
+            Trap::Exit => "EXIT".to_string(),
+            Trap::Err => "ERR".to_string(),
+            Trap::Signal(signal) => NAMES
+                .iter()
+                .find(|(_, number)| number == signal)
+                .map(|(name, _)| format!("SIG{name}"))
+                .unwrap_or_else(|| signal.to_string()),
+        }
+    }
+}
+
+/// The trap table, the commands run on the conditions.
+///
+/// An empty command ignores the condition.
+#[derive(Default)]
+pub struct Traps {
+    traps: HashMap<Trap, String>,
+    /// The actions of the trapped signals before their first trap, restored by `reset`.
+    actions: HashMap<libc::c_int, libc::sigaction>,
+    is_running: bool,
+}
+
+impl fmt::Debug for Traps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Traps")
+            .field("traps", &self.traps)
+            .field("is_running", &self.is_running)
+            .finish()
+    }
+}
+
+impl Traps {
+    /// Create a new trap table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the command of a trap.
+    ///
+    /// # Errors
+    /// `ErrorKind::InvalidInput` - If the signal can not be caught, as SIGKILL and SIGSTOP.
+    pub fn set(&mut self, trap: Trap, command: impl Into<String>) -> Result<()> {
+        let command = command.into();
+
+        if let Trap::Signal(signal) = trap {
+            if signal == libc::SIGKILL || signal == libc::SIGSTOP {
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    &format!("{}: the signal can not be trapped", trap.name()),
+                ))?
+            }
+
+            let previous = if command.is_empty() {
+                set_disposition(signal, Disposition::Ignore)
+            } else {
+                set_disposition(signal, Disposition::Record)
+            };
+
+            self.actions.entry(signal).or_insert(previous);
+
+            // a signal received before the trap is not handled by it.
+            take(signal);
+        }
+
+        self.traps.insert(trap, command);
+
+        Ok(())
+    }
+
+    /// Reset a trap, the signal has the disposition it had before its first trap again.
+    pub fn reset(&mut self, trap: Trap) {
+        if let Trap::Signal(signal) = trap {
+            if let Some(action) = self.actions.remove(&signal) {
+                unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) };
+            }
+        }
+
+        self.traps.remove(&trap);
+    }
+
+    /// Get the command of a trap.
+    pub fn get(&self, trap: Trap) -> Option<&str> {
+        self.traps.get(&trap).map(String::as_str)
+    }
+
+    /// Get all traps, in order of condition.
+    pub fn entries(&self) -> Vec<(Trap, &str)> {
+        let mut traps = self
+            .traps
+            .iter()
+            .map(|(trap, command)| (*trap, command.as_str()))
+            .collect::<Vec<(Trap, &str)>>();
+
+        traps.sort_by_key(|(trap, _)| match trap {
+            Trap::Exit => 0,
+            Trap::Signal(signal) => *signal,
+            Trap::Err => libc::c_int::MAX,
+        });
+
+        traps
+    }
+
+    /// The signals ignored by traps.
+    pub fn ignored(&self) -> Vec<libc::c_int> {
+        self.traps
+            .iter()
+            .filter_map(|(trap, command)| match trap {
+                Trap::Signal(signal) if command.is_empty() => Some(*signal),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Mark a trap command as running, traps do not run inside traps.
+    ///
+    /// # Returns
+    /// `false` if a trap command is already running.
+    pub(super) fn enter(&mut self) -> bool {
+        !std::mem::replace(&mut self.is_running, true)
+    }
+
+    pub(super) fn leave(&mut self) {
+        self.is_running = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trap_parse() {
+        assert_eq!(Trap::parse("EXIT"), Some(Trap::Exit));
+        assert_eq!(Trap::parse("err"), Some(Trap::Err));
+        assert_eq!(Trap::parse("TERM"), Some(Trap::Signal(libc::SIGTERM)));
+        assert_eq!(Trap::parse("SIGUSR1"), Some(Trap::Signal(libc::SIGUSR1)));
+        assert_eq!(Trap::parse("15"), Some(Trap::Signal(libc::SIGTERM)));
+        assert_eq!(Trap::parse("SIGFOO"), None);
+        assert_eq!(Trap::parse("99"), None);
+
+        assert_eq!(Trap::Signal(libc::SIGINT).name(), "SIGINT");
+    }
+
+    #[test]
+    fn test_traps() {
+        let mut traps = Traps::new();
+
+        traps.set(Trap::Err, "echo err").unwrap();
+        traps.set(Trap::Exit, "echo bye").unwrap();
+        traps.set(Trap::Signal(libc::SIGUSR2), "").unwrap();
+
+        assert!(traps.set(Trap::Signal(libc::SIGKILL), "echo").is_err());
+
+        assert_eq!(
+            traps.entries(),
+            [
+                (Trap::Exit, "echo bye"),
+                (Trap::Signal(libc::SIGUSR2), ""),
+                (Trap::Err, "echo err"),
+            ]
+        );
+        assert_eq!(traps.ignored(), [libc::SIGUSR2]);
+
+        traps.reset(Trap::Signal(libc::SIGUSR2));
+
+        assert!(traps.ignored().is_empty());
+        assert_eq!(traps.get(Trap::Err), Some("echo err"));
+    }
+
+    #[test]
+    fn test_take() {
+        let mut traps = Traps::new();
+
+        traps.set(Trap::Signal(libc::SIGUSR1), "echo usr1").unwrap();
+
+        unsafe { libc::raise(libc::SIGUSR1) };
+
+        assert!(take(libc::SIGUSR1));
+        assert!(!take(libc::SIGUSR1));
+
+        traps.reset(Trap::Signal(libc::SIGUSR1));
+    }
+}
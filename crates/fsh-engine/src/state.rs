@@ -1,29 +1,48 @@
 use std::path::{Path, PathBuf};
 
-use super::{pipe::*, process_handler::*};
+use super::{alias::Aliases, builtin::{self, Builtins}, exit_status::*, job::*, module::*, pipe::*, process_handler::*, signal::*};
 
 #[derive(Debug)]
 pub struct State {
-    handler: ProcessHandler,
     pipe: Pipe,
     current_dir: PathBuf,
+    status: ExitStatus,
+    pipe_status: Vec<ExitStatus>,
+    modules: Modules,
+    jobs: Jobs,
+    traps: Traps,
+    builtins: Builtins,
+    aliases: Aliases,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
-            handler: ProcessHandler::new(),
             pipe: Pipe::new(),
             current_dir:PathBuf::new(),
+            status: ExitStatus::SUCCESS,
+            pipe_status: Vec::new(),
+            modules: Modules::new(),
+            jobs: Jobs::new(),
+            traps: Traps::new(),
+            builtins: builtin::defaults(),
+            aliases: Aliases::new(),
         }
     }
 
+    /// The process table, owned by the jobs.
     pub fn handler(&self) -> &ProcessHandler {
-        &self.handler
+        self.jobs.handler()
     }
 
     pub fn handler_mut(&mut self) -> &mut ProcessHandler {
-        &mut self.handler
+        self.jobs.handler_mut()
     }
 
     pub fn pipe(&self) -> &Pipe {
@@ -42,4 +61,77 @@ impl State {
         &mut self.current_dir
     }
 
+    /// The exit status of the last pipeline.
+    pub fn status(&self) -> ExitStatus {
+        self.status
+    }
+
+    pub fn status_mut(&mut self) -> &mut ExitStatus {
+        &mut self.status
+    }
+
+    /// The exit statuses of the commands of the last pipeline.
+    pub fn pipe_status(&self) -> &[ExitStatus] {
+        &self.pipe_status
+    }
+
+    pub fn pipe_status_mut(&mut self) -> &mut Vec<ExitStatus> {
+        &mut self.pipe_status
+    }
+
+    pub fn modules(&self) -> &Modules {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut Modules {
+        &mut self.modules
+    }
+
+    pub fn jobs(&self) -> &Jobs {
+        &self.jobs
+    }
+
+    pub fn jobs_mut(&mut self) -> &mut Jobs {
+        &mut self.jobs
+    }
+
+    /// Remove a job, and its processes from the handler.
+    pub fn remove_job(&mut self, id: usize) -> Option<Job> {
+        self.jobs.remove(id)
+    }
+
+    /// Reap the jobs that are done.
+    ///
+    /// # Returns
+    /// The notices of the jobs, as `[1]+  Done                    sleep 1 &`.
+    pub fn reap_jobs(&mut self) -> Vec<String> {
+        self.jobs.reap()
+    }
+
+    pub fn traps(&self) -> &Traps {
+        &self.traps
+    }
+
+    pub fn traps_mut(&mut self) -> &mut Traps {
+        &mut self.traps
+    }
+
+    /// The builtin registry, embedders register their builtins here.
+    pub fn builtins(&self) -> &Builtins {
+        &self.builtins
+    }
+
+    pub fn builtins_mut(&mut self) -> &mut Builtins {
+        &mut self.builtins
+    }
+
+    /// The aliases, expanded on the first word of a command.
+    pub fn aliases(&self) -> &Aliases {
+        &self.aliases
+    }
+
+    pub fn aliases_mut(&mut self) -> &mut Aliases {
+        &mut self.aliases
+    }
+
 }
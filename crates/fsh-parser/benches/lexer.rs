@@ -0,0 +1,89 @@
+//! Benchmark the lexer and the parser on a generated script, `cargo bench -p fsh-parser`.
+//!
+//! `tokenize` and `parse` only use the API the lexer and the parser had before streaming,
+//! so the same functions build on an older checkout and measure the old paths on the same input.
+
+use fsh_parser::{lexer::Lexer, token::TokenKind, Parser};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 5;
+
+/// Generate a script of about `size` bytes, with a bit of every syntax.
+fn script(size: usize) -> String {
+    let chunk = "# generated by a tool, do not edit\n\
+        echo \"hello world\" 'single quoted' bare_word > /dev/null\n\
+        $name = value\n\
+        ls -la $name | grep -v target | sort @2 > errors.log &\n\
+        cat <<EOF\n\
+        here-document body line\n\
+        EOF\n\
+        cargo build --workspace \\\n    --release; echo done # trailing comment\n";
+
+    chunk.repeat(size / chunk.len() + 1)
+}
+
+fn bench(name: &str, size: usize, f: impl Fn() -> usize) {
+    let mut elapsed = Duration::ZERO;
+
+    let mut count = 0;
+
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        count = f();
+        elapsed += start.elapsed();
+    }
+
+    let elapsed = elapsed / ITERATIONS;
+
+    println!(
+        "{name:<24} {:>8.2} ms {:>8.2} MB/s {count:>9}",
+        elapsed.as_secs_f64() * 1000.0,
+        size as f64 / elapsed.as_secs_f64() / 1_000_000.0,
+    );
+}
+
+/// Read all the lexemes, without converting them to tokens.
+fn lexemes(mut lexer: Lexer) -> usize {
+    let mut count = 0;
+
+    while lexer.next_lexeme().unwrap().kind != TokenKind::EOF {
+        count += 1;
+    }
+
+    count
+}
+
+/// Read the whole input to tokens, then parse it, as before the lexer streamed.
+fn whole_input(source: &str) {
+    bench("tokenize", source.len(), || {
+        Lexer::new(source).tokenize().unwrap().len()
+    });
+
+    bench("parse", source.len(), || {
+        let mut parser = Parser::new(source);
+
+        parser.parse().unwrap();
+
+        parser.locations().len()
+    });
+}
+
+/// Read the lexemes one at a time, from the source and from a reader.
+fn streaming(source: &str) {
+    bench("next_lexeme", source.len(), || lexemes(Lexer::new(source)));
+
+    bench("next_lexeme from_reader", source.len(), || {
+        lexemes(Lexer::from_reader(source.as_bytes()))
+    });
+}
+
+fn main() {
+    for size in [1_000_000, 8_000_000] {
+        let source = script(size);
+
+        println!("input: {} bytes", source.len());
+
+        whole_input(&source);
+        streaming(&source);
+    }
+}
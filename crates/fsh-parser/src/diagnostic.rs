@@ -0,0 +1,83 @@
+use fsh_common::Error;
+use std::fmt::{Display, Formatter, Result};
+
+/// A location in the source, line and column start from 1.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub const fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An error found in the source, and where it was found.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    location: Location,
+    error: Error,
+}
+
+impl Diagnostic {
+    pub fn new(location: Location, error: Error) -> Self {
+        Self { location, error }
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl Display for Diagnostic {
+    /// Format as `line:column: kind: message`.
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.location,
+            self.error.kind().as_str2(),
+            self.error.message()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fsh_common::ErrorKind;
+
+    #[test]
+    fn test_location_display() {
+        assert_eq!(format!("{}", Location::new(3, 14)), "3:14");
+    }
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diagnostic = Diagnostic::new(
+            Location::new(1, 5),
+            Error::new(ErrorKind::SyntaxError, "Expected expr, found '|'"),
+        );
+
+        assert_eq!(
+            format!("{}", diagnostic),
+            "1:5: syntax error: Expected expr, found '|'"
+        );
+    }
+}
@@ -0,0 +1,669 @@
+use super::{
+    diagnostic::Location,
+    lexer::{parse_number, SYMBOLS},
+    parser::Parser,
+    token::Keyword,
+};
+use fsh_ast::*;
+use fsh_common::{Error, ErrorKind, Result};
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+/// Format the source in the canonical style.
+///
+/// - One statement per line, and at most one blank line between statements.
+/// - One space around `|`, `=`, `=>`, redirections, and before `&`.
+/// - Blocks are indented with four spaces, one arm or statement per line.
+/// - Words are written without quotes when possible, otherwise with single quotes, or double quotes if the word contains a single quote.
+/// - Comments are kept, on their own line or at the end of the statement they follow.
+///
+/// # Errors
+/// The first error of the source, a source that can not be parsed can not be formatted.
+///
+/// # Example
+/// ```
+/// let source = "echo   hello|cat>out.txt;ls   -la &  # list\n";
+///
+/// assert_eq!(
+///     fsh_parser::format(source).unwrap(),
+///     "echo hello | cat > out.txt\nls -la & # list\n"
+/// );
+/// ```
+pub fn format(source: &str) -> Result<String> {
+    let mut parser = Parser::new(source);
+
+    let (ast, diagnostics) = parser.parse_with_diagnostics();
+
+    if let Some(diagnostic) = diagnostics.into_iter().next() {
+        Err(diagnostic.into_error())?
+    }
+
+    // the formatted source replaces the source, a part that was not parsed would be lost.
+    if !parser.is_consumed() {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            "the source is not parsed to its end",
+        ))?
+    }
+
+    let statements = match &ast {
+        Ast::Semicolon(statements) => statements.iter().collect(),
+        ast => vec![ast],
+    };
+
+    let mut formatter = Formatter::new(source, parser.comments(), parser.block_locations());
+
+    for (statement, location) in statements.into_iter().zip(parser.locations()) {
+        formatter.statement(location, statement)?;
+    }
+
+    Ok(formatter.finish())
+}
+
+const INDENT: &str = "    ";
+
+struct Formatter<'a> {
+    source_lines: Vec<&'a str>,
+    comments: Peekable<IntoIter<(Location, &'a str)>>,
+    /// The locations of the items inside blocks, consumed in order of the source.
+    block_locations: VecDeque<Location>,
+    output: String,
+    line: String,
+    indent: usize,
+    /// The indentation of the current line, the indentation may change before the line is written.
+    line_indent: usize,
+    /// The line of the source where the last item starts.
+    last_line: Option<usize>,
+    /// The last line of the source covered by the last item, the here-document bodies included.
+    end_line: usize,
+    here_documents: Vec<(String, String)>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, comments: Vec<(Location, &'a str)>, block_locations: &[Location]) -> Self {
+        Self {
+            source_lines: source.lines().collect(),
+            comments: comments.into_iter().peekable(),
+            block_locations: block_locations.iter().copied().collect(),
+            output: String::new(),
+            line: String::new(),
+            indent: 0,
+            line_indent: 0,
+            last_line: None,
+            end_line: 0,
+            here_documents: Vec::new(),
+        }
+    }
+
+    /// Write the current line, and the here-documents it opened.
+    fn flush(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        self.output.push_str(&INDENT.repeat(self.line_indent));
+        self.output.push_str(&self.line);
+        self.output.push('\n');
+        self.line.clear();
+
+        for (delimiter, body) in self.here_documents.drain(..) {
+            self.output.push_str(&body);
+            self.output.push_str(&delimiter);
+            self.output.push('\n');
+        }
+    }
+
+    /// Start a new line for the item at the line of the source, keeping one blank line if the source had any.
+    fn new_line(&mut self, line: usize) {
+        self.flush();
+
+        if self.last_line.is_some() {
+            let is_separated = self
+                .source_lines
+                .iter()
+                .take(line.saturating_sub(1))
+                .skip(self.end_line)
+                .any(|line| line.trim().is_empty());
+
+            if is_separated {
+                self.output.push('\n');
+            }
+        }
+
+        self.last_line = Some(line);
+        self.end_line = line;
+        self.line_indent = self.indent;
+    }
+
+    /// Check if the comment at the line follows the last statement on the same line, or on the lines it continues to.
+    fn is_trailing(&self, line: usize) -> bool {
+        match self.last_line {
+            Some(last_line) if !self.line.is_empty() && last_line <= line => self
+                .source_lines
+                .iter()
+                .take(line - 1)
+                .skip(last_line - 1)
+                .all(|line| line.trim_end().ends_with('\\')),
+            _ => false,
+        }
+    }
+
+    fn comment(&mut self, location: &Location, comment: &str) {
+        if self.is_trailing(location.line) {
+            self.line.push_str(" #");
+        } else {
+            self.new_line(location.line);
+            self.line.push('#');
+        }
+
+        self.line.push_str(comment);
+    }
+
+    /// Write the comments that come before the location.
+    fn comments_before(&mut self, location: &Location) {
+        while let Some((comment_location, comment)) = self
+            .comments
+            .next_if(|(comment_location, _)| comment_location < location)
+        {
+            self.comment(&comment_location, comment);
+        }
+    }
+
+    /// Get the location of the next item of a block.
+    ///
+    /// # Errors
+    /// `Kind::SyntaxError` - If the parser did not record a location for the item.
+    fn block_location(&mut self) -> Result<Location> {
+        match self.block_locations.pop_front() {
+            Some(location) => Ok(location),
+            None => Err(Error::new(
+                ErrorKind::SyntaxError,
+                "no location for an item of a block",
+            )),
+        }
+    }
+
+    fn statement(&mut self, location: &Location, statement: &Ast) -> Result<()> {
+        self.comments_before(location);
+
+        self.new_line(location.line);
+
+        match statement {
+            Ast::Statement(Statement::Match(r#match)) => return self.r#match(r#match),
+            Ast::Statement(Statement::Try(r#try)) => return self.r#try(r#try),
+            _ => {}
+        }
+
+        let statement = self.ast(statement);
+
+        self.line.push_str(&statement);
+
+        // the bodies of here-documents are not blank lines of the source.
+        let body_lines = self
+            .here_documents
+            .iter()
+            .map(|(_, body)| body.lines().count() + 1)
+            .sum::<usize>();
+
+        self.end_line = location.line + body_lines;
+
+        Ok(())
+    }
+
+    fn ast(&mut self, ast: &Ast) -> String {
+        match ast {
+            Ast::Semicolon(statements) => statements
+                .iter()
+                .map(|statement| self.ast(statement))
+                .collect::<Vec<String>>()
+                .join("; "),
+            Ast::Pipe(pipe) => {
+                let mut line = String::new();
+
+                for (i, command) in pipe.commands().iter().enumerate() {
+                    if i > 0 {
+                        line.push_str(if pipe.commands()[i - 1].pipe_stderr { " |& " } else { " | " });
+                    }
+
+                    line.push_str(&self.command(command));
+                }
+
+                line
+            }
+            Ast::Statement(Statement::Command(command)) => self.command(command),
+            Ast::Statement(Statement::Assign(assign)) => {
+                format!("{} = {}", expr(&assign.ident), expr(&assign.expr))
+            }
+            Ast::Statement(Statement::Match(_) | Statement::Try(_)) => {
+                unreachable!("blocks are written by lines")
+            }
+            Ast::Statement(Statement::Import(import)) => format!("import {}", expr(&import.path)),
+            Ast::Negated(ast) => format!("! {}", self.ast(ast)),
+        }
+    }
+
+    fn r#match(&mut self, r#match: &Match) -> Result<()> {
+        self.line
+            .push_str(&format!("match {} {{", expr(&r#match.expr)));
+
+        self.indent += 1;
+
+        for arm in &r#match.arms {
+            let location = self.block_location()?;
+
+            self.comments_before(&location);
+
+            self.new_line(location.line);
+
+            let patterns = arm
+                .patterns
+                .iter()
+                .map(pattern)
+                .collect::<Vec<String>>()
+                .join(" | ");
+
+            self.line.push_str(&format!("{patterns} => {{"));
+
+            self.block(&arm.body)?;
+        }
+
+        self.indent -= 1;
+
+        self.close_block(false)
+    }
+
+    fn r#try(&mut self, r#try: &Try) -> Result<()> {
+        self.line.push_str("try {");
+
+        self.block(&r#try.body)?;
+
+        if let Some(catch) = &r#try.catch {
+            match &catch.ident {
+                Some(ident) => self.line.push_str(&format!(" catch {} {{", expr(ident))),
+                None => self.line.push_str(" catch {"),
+            }
+
+            self.block(&catch.body)?;
+        }
+
+        if let Some(finally) = &r#try.finally {
+            self.line.push_str(" finally {");
+
+            self.block(finally)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the statements of the block, and its closing brace.
+    fn block(&mut self, block: &Ast) -> Result<()> {
+        let statements = match block {
+            Ast::Semicolon(statements) => statements.iter().collect(),
+            ast => vec![ast],
+        };
+
+        self.indent += 1;
+
+        for statement in &statements {
+            let location = self.block_location()?;
+
+            self.statement(&location, statement)?;
+        }
+
+        self.indent -= 1;
+
+        self.close_block(statements.is_empty())
+    }
+
+    /// Write the closing brace, on the line of the opening brace if the block is empty.
+    fn close_block(&mut self, is_empty: bool) -> Result<()> {
+        let location = self.block_location()?;
+
+        let has_comments = self
+            .comments
+            .peek()
+            .is_some_and(|(comment_location, _)| comment_location < &location);
+
+        if is_empty && !has_comments {
+            self.line.push('}');
+            return Ok(());
+        }
+
+        self.comments_before(&location);
+
+        self.new_line(location.line);
+
+        self.line.push('}');
+
+        Ok(())
+    }
+
+    fn command(&mut self, command: &Command) -> String {
+        // a command named like a keyword must stay quoted.
+        let name = match &command.expr {
+            Expr::String(name) | Expr::Literal(name) if Keyword::parse(name).is_some() => {
+                format!("'{name}'")
+            }
+            expr => self::expr(expr),
+        };
+
+        let mut words = vec![name];
+
+        words.extend(command.args.iter().map(arg));
+
+        for redirect in &command.redirects {
+            let word = match (&redirect.operator, &redirect.left) {
+                (RedirectOperator::HereDoc, _) => {
+                    let body = match &redirect.right {
+                        Expr::String(body) => body.to_string(),
+                        right => format!("{}\n", expr(right)),
+                    };
+
+                    let delimiter = here_document_delimiter(&body);
+
+                    let word = format!("<<{delimiter}");
+
+                    self.here_documents.push((delimiter, body));
+
+                    word
+                }
+                (RedirectOperator::Gt, Expr::FD(1)) => format!("> {}", expr(&redirect.right)),
+                (RedirectOperator::Lt, Expr::FD(0)) => format!("< {}", expr(&redirect.right)),
+                (RedirectOperator::Gt, left) => {
+                    format!("{} > {}", expr(left), expr(&redirect.right))
+                }
+                (RedirectOperator::Lt, left) => {
+                    format!("{} < {}", expr(left), expr(&redirect.right))
+                }
+            };
+
+            words.push(word);
+        }
+
+        if command.background {
+            words.push("&".to_string());
+        }
+
+        words.join(" ")
+    }
+
+    fn finish(mut self) -> String {
+        while let Some((location, comment)) = self.comments.next() {
+            self.comment(&location, comment);
+        }
+
+        self.flush();
+        self.output
+    }
+}
+
+fn expr(expr: &Expr) -> String {
+    match expr {
+        Expr::String(string) => quote(string),
+        Expr::Literal(string) => quote_literal(string),
+        Expr::Ident(ident) => format!("${ident}"),
+        Expr::KeyIdent(key, ident) => format!("{key}=${ident}"),
+        Expr::Number(number) => number.to_string(),
+        Expr::Float(number) => format!("{number:?}"),
+        Expr::FD(fd) => format!("@{fd}"),
+    }
+}
+
+/// An argument of a command, `=` does not split an argument, as `k=v` or `--x=y`.
+fn arg(expr: &Expr) -> String {
+    match expr {
+        Expr::String(word) if is_bare_key_value(word) => word.to_string(),
+        Expr::Literal(word)
+            if is_bare_key_value(word)
+                && !word.contains(['*', '?', '['])
+                && !word.chars().any(char::is_control) =>
+        {
+            word.to_string()
+        }
+        expr => self::expr(expr),
+    }
+}
+
+/// Check if the word is an argument with `=` that the lexer reads as one word, the parts are bare words.
+///
+/// `=` at the start is an assignment, `=>` ends a pattern, and a word starting with a digit is read as a number.
+fn is_bare_key_value(word: &str) -> bool {
+    let key = word.split('=').next().unwrap_or_default();
+
+    let parts = word.replace('=', "");
+
+    word.contains('=')
+        && !key.is_empty()
+        && !word.contains("=>")
+        && !key.trim_start_matches('-').starts_with(|c: char| c.is_ascii_digit())
+        && quote(&parts) == parts
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Glob(glob) => quote(glob),
+        Pattern::Regex(regex) => format!("~ {}", quote(regex)),
+        Pattern::Wildcard => "_".to_string(),
+    }
+}
+
+/// Quote the word, only if it would not be read back as the same word.
+fn quote(word: &str) -> String {
+    let is_bare = !word.is_empty()
+        && !word.starts_with('#')
+        && word != "{"
+        && word != "!"
+        && word != "}"
+        && parse_number(word).is_none()
+        && !word
+            .chars()
+            .any(|c| c.is_whitespace() || SYMBOLS.contains(&c));
+
+    if is_bare {
+        word.to_string()
+    } else if word.contains('\'') {
+        format!("\"{word}\"")
+    } else {
+        format!("'{word}'")
+    }
+}
+
+/// Quote a string that is never globbed.
+///
+/// A string with glob characters stays quoted, and a string with control characters is quoted as an ANSI-C string.
+fn quote_literal(string: &str) -> String {
+    if string.chars().any(char::is_control) || string.contains('\'') && string.contains('"') {
+        ansi_c_quote(string)
+    } else if string.contains(['*', '?', '[']) {
+        if string.contains('\'') {
+            format!("\"{string}\"")
+        } else {
+            format!("'{string}'")
+        }
+    } else {
+        quote(string)
+    }
+}
+
+/// Quote the string as an ANSI-C string, escaping the backslashes, the quotes and the control characters.
+fn ansi_c_quote(string: &str) -> String {
+    let mut quoted = String::from("$'");
+
+    for c in string.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '\'' => quoted.push_str("\\'"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\x1b' => quoted.push_str("\\e"),
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('\'');
+
+    quoted
+}
+
+/// Find a delimiter that does not appear as a line of the body.
+fn here_document_delimiter(body: &str) -> String {
+    let mut delimiter = "EOF".to_string();
+
+    while body.lines().any(|line| line == delimiter) {
+        delimiter.push('_');
+    }
+
+    delimiter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_spacing() {
+        assert_eq!(
+            format("  echo   a |cat  |  grep  b>out;$x=1 ;sleep 1&").unwrap(),
+            "echo a | cat | grep b > out\n$x = 1\nsleep 1 &\n"
+        );
+
+        assert_eq!(format("make|&grep error|wc &").unwrap(), "make |& grep error | wc &\n");
+    }
+
+    #[test]
+    fn test_format_redirect() {
+        assert_eq!(
+            format("cat<in @2>err @1 > out").unwrap(),
+            "cat < in @2 > err > out\n"
+        );
+    }
+
+    #[test]
+    fn test_format_quote() {
+        assert_eq!(
+            format("echo \"a\" 'b c' \"it's\" '' '123' '$x' \"{\" '-1.5' '007' -2 0.5").unwrap(),
+            "echo a 'b c' \"it's\" '' '123' '$x' '{' '-1.5' 007 -2 0.5\n"
+        );
+
+        // a quoted glob is not expanded, it stays quoted.
+        assert_eq!(format("ls \"*.rs\" *.rs").unwrap(), "ls '*.rs' *.rs\n");
+
+        // `=` does not split an argument, but it splits a command name.
+        assert_eq!(
+            format("echo 'k=v' --x=y \"a=b c\" '=x' 'x=>' '1=2' '*=x' *=x\n'a=b' c").unwrap(),
+            "echo k=v --x=y 'a=b c' '=x' 'x=>' '1=2' '*=x' *=x\n'a=b' c\n"
+        );
+    }
+
+    #[test]
+    fn test_format_keyword() {
+        assert_eq!(
+            format("\"if\" while; echo for").unwrap(),
+            "'if' while\necho for\n"
+        );
+    }
+
+    #[test]
+    fn test_format_negated() {
+        assert_eq!(
+            format("!   grep a|wc;'!' b").unwrap(),
+            "! grep a | wc\n'!' b\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comment() {
+        let source = "#!/usr/bin/env flatshell\n\n# head\necho a   # tail\n\n\n\necho b; echo c # last\n# end";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "#!/usr/bin/env flatshell\n\n# head\necho a # tail\n\necho b\necho c # last\n# end\n"
+        );
+    }
+
+    #[test]
+    fn test_format_here_document() {
+        let source = "cat <<END >out\nEOF\n\nbody\nEND\necho done";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "cat <<EOF_ > out\nEOF\n\nbody\nEOF_\necho done\n"
+        );
+    }
+
+    #[test]
+    fn test_format_trailing_comment() {
+        let source = "echo a \\\n  b # c\ncat <<EOF # d\nbody\nEOF\n\necho e";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "echo a b # c\ncat <<EOF # d\nbody\nEOF\n\necho e\n"
+        );
+    }
+
+    #[test]
+    fn test_format_match() {
+        let source = "match $x { a|'b c'=>{ echo a;ls }\n\n# v\n~ '^v$'=>{ }\n_ => { # other\n} } # end";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "match $x {\n    a | 'b c' => {\n        echo a\n        ls\n    }\n\n    # v\n    ~ '^v$' => {}\n    _ => { # other\n    }\n} # end\n"
+        );
+    }
+
+    #[test]
+    fn test_format_try() {
+        let source = "try { a }\ncatch $err {\n  # report\n  echo $err\n}\nfinally {}\ntry {} catch { b }";
+
+        assert_eq!(
+            format(source).unwrap(),
+            "try {\n    a\n} catch $err {\n    # report\n    echo $err\n} finally {}\ntry {} catch {\n    b\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ansi_c_string() {
+        let source = r"echo $'a\tb\x41\e\001\\\'' $'é'";
+
+        let formatted = format(source).unwrap();
+
+        assert_eq!(formatted, "echo $'a\\tbA\\e\\x01\\\\\\'' \u{e9}\n");
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_backslash() {
+        // the whole input is formatted, nothing after a backslash is dropped.
+        let source = "echo a\\b\necho c\nrm important\n";
+
+        assert_eq!(format(source).unwrap(), "echo 'a\\b'\necho c\nrm important\n");
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let source = "# a\nls -la|grep x>y &\n\n$x = 'a b'\ncat <<EOF\nhi\nEOF\necho $x # b\nmatch $x {\n    _ => {\n        cat <<EOF\nhi\nEOF\n    }\n}\n";
+
+        let formatted = format(source).unwrap();
+
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_background() {
+        // the statement after `&` is kept.
+        assert_eq!(format("echo x & echo y").unwrap(), "echo x &\necho y\n");
+        assert_eq!(
+            format("try { sleep 1 & echo z } catch { echo e }").unwrap(),
+            "try {\n    sleep 1 &\n    echo z\n} catch {\n    echo e\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_error() {
+        assert!(format("echo a\n| cat").is_err());
+    }
+}
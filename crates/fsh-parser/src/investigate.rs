@@ -0,0 +1,548 @@
+use super::lexer::Lexer;
+use fsh_ast::*;
+use std::collections::HashSet;
+use std::path::Path;
+use std::fmt::{Display, Formatter, Result};
+
+/// The prefix of the comment that suppresses warnings in a script.
+///
+/// For example, `# fsh-allow: W001, unchecked-background` suppresses W001 and W005.
+pub const ALLOW_DIRECTIVE: &str = "fsh-allow:";
+
+/// The kind of a warning.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Code {
+    UndefinedVariable,
+    UnreachableCode,
+    RedirectToInput,
+    UselessCat,
+    UncheckedBackground,
+    UnquotedGlob,
+}
+
+impl Code {
+    pub const ALL: [Code; 6] = [
+        Code::UndefinedVariable,
+        Code::UnreachableCode,
+        Code::RedirectToInput,
+        Code::UselessCat,
+        Code::UncheckedBackground,
+        Code::UnquotedGlob,
+    ];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Code::UndefinedVariable => "W001",
+            Code::UnreachableCode => "W002",
+            Code::RedirectToInput => "W003",
+            Code::UselessCat => "W004",
+            Code::UncheckedBackground => "W005",
+            Code::UnquotedGlob => "W006",
+        }
+    }
+
+    pub const fn as_str2(&self) -> &'static str {
+        match self {
+            Code::UndefinedVariable => "undefined-variable",
+            Code::UnreachableCode => "unreachable-code",
+            Code::RedirectToInput => "redirect-to-input",
+            Code::UselessCat => "useless-cat",
+            Code::UncheckedBackground => "unchecked-background",
+            Code::UnquotedGlob => "unquoted-glob",
+        }
+    }
+
+    /// Get a code from its code (W001) or its name (undefined-variable).
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|code| code.as_str().eq_ignore_ascii_case(s) || code.as_str2() == s)
+    }
+}
+
+/// A warning found by the investigation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    code: Code,
+    message: String,
+    statement: usize,
+}
+
+impl Warning {
+    pub fn new(code: Code, message: &str, statement: usize) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            statement,
+        }
+    }
+
+    pub fn code(&self) -> &Code {
+        &self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The index of the top-level statement the warning was found in.
+    ///
+    /// The location of the statement can be obtained from `Parser::locations`.
+    pub fn statement(&self) -> usize {
+        self.statement
+    }
+}
+
+impl Display for Warning {
+    /// Format as `warning[code]: message`.
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "warning[{}]: {}", self.code.as_str(), self.message)
+    }
+}
+
+/// Static analyzer for fsh scripts.
+///
+/// # Example
+/// ```
+/// use fsh_parser::{investigate::*, Parser};
+///
+/// let source = "echo $name; cat file | grep fsh";
+///
+/// let ast = Parser::new(source).parse().unwrap();
+///
+/// let warnings = Investigator::from_source(source).investigate(&ast);
+///
+/// assert_eq!(warnings[0].code(), &Code::UndefinedVariable);
+/// assert_eq!(warnings[1].code(), &Code::UselessCat);
+/// ```
+#[derive(Debug, Default)]
+pub struct Investigator {
+    defined: HashSet<String>,
+    allowed: HashSet<Code>,
+}
+
+impl Investigator {
+    /// Create a new investigator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new investigator, with the codes allowed by the `fsh-allow:` comments of the source.
+    pub fn from_source(source: &str) -> Self {
+        let mut investigator = Self::new();
+
+        let mut lexer = Lexer::new(source);
+
+        lexer.tokenize_with_location();
+
+        for (_, comment) in lexer.comments() {
+            if let Some(codes) = comment.trim().strip_prefix(ALLOW_DIRECTIVE) {
+                codes
+                    .split([',', ' '])
+                    .filter_map(Code::parse)
+                    .for_each(|code| {
+                        investigator.allow(code);
+                    });
+            }
+        }
+
+        investigator
+    }
+
+    /// Define a variable, for example an environment variable, so that it is not reported as undefined.
+    pub fn define(&mut self, name: impl Into<String>) -> &mut Self {
+        self.defined.insert(name.into());
+        self
+    }
+
+    /// Suppress the warnings of the code.
+    pub fn allow(&mut self, code: Code) -> &mut Self {
+        self.allowed.insert(code);
+        self
+    }
+
+    /// Investigate the AST.
+    ///
+    /// # Returns
+    /// The warnings, in order of statement.
+    pub fn investigate(&self, ast: &Ast) -> Vec<Warning> {
+        let statements = match ast {
+            Ast::Semicolon(statements) => statements.iter().collect::<Vec<&Ast>>(),
+            _ => vec![ast],
+        };
+
+        let mut warnings = Vec::new();
+
+        let mut defined = self.defined.to_owned();
+
+        let mut values = Vec::new();
+
+        // the namespaces of the imported modules, their variables are not known.
+        let mut namespaces = HashSet::new();
+
+        for (i, statement) in statements.iter().enumerate() {
+            // the negation only changes the exit status, the pipeline is investigated as is.
+            let mut statement = *statement;
+
+            while let Ast::Negated(ast) = statement {
+                statement = ast;
+            }
+
+            let commands = match statement {
+                Ast::Pipe(pipe) => pipe.commands().iter().collect(),
+                Ast::Statement(Statement::Command(command)) => vec![command],
+                Ast::Statement(Statement::Assign(assign)) => {
+                    if let (Expr::Ident(name), Expr::String(value) | Expr::Literal(value)) =
+                        (&assign.ident, &assign.expr)
+                    {
+                        values.push((name.to_string(), value.to_string()));
+                    }
+
+                    if let Expr::Ident(name) = &assign.ident {
+                        defined.insert(name.to_string());
+                    }
+
+                    continue;
+                }
+                Ast::Statement(Statement::Import(import)) => {
+                    if let Expr::String(path) | Expr::Literal(path) = &import.path {
+                        if let Some(namespace) = Path::new(path).file_stem() {
+                            namespaces.insert(namespace.to_string_lossy().to_string());
+                        }
+                    }
+
+                    continue;
+                }
+                Ast::Statement(Statement::Try(_)) => {
+                    let mut commands = Vec::new();
+
+                    block_commands(statement, &mut defined, &mut commands);
+
+                    commands
+                }
+                Ast::Statement(Statement::Match(r#match)) => {
+                    if let Expr::Ident(name) = &r#match.expr {
+                        if !defined.contains(name.as_str()) {
+                            warnings.push(Warning::new(
+                                Code::UndefinedVariable,
+                                &format!("variable `${name}` is used but never assigned"),
+                                i,
+                            ));
+                        }
+                    }
+
+                    let mut commands = Vec::new();
+
+                    for arm in &r#match.arms {
+                        block_commands(&arm.body, &mut defined, &mut commands);
+                    }
+
+                    commands
+                }
+                _ => Vec::new(),
+            };
+
+            // W001, W006
+            for command in &commands {
+                for expr in std::iter::once(&command.expr)
+                    .chain(command.args.iter())
+                    .chain(command.redirects.iter().map(|redirect| &redirect.right))
+                {
+                    let name = match expr {
+                        Expr::Ident(name) | Expr::KeyIdent(_, name) => name,
+                        _ => continue,
+                    };
+
+                    // `$err::kind` of `catch $err`, or `$git::name` of an imported module.
+                    let is_imported = name.split_once("::").is_some_and(|(namespace, _)| {
+                        namespaces.contains(namespace) || defined.contains(namespace)
+                    });
+
+                    // `$1`, `$#` and `$*` are the positional parameters, they are set by the caller of the script.
+                    let is_positional = name == "#"
+                        || name == "*"
+                        || name.chars().all(|c| c.is_ascii_digit());
+
+                    if !defined.contains(name.as_str()) && !is_imported && !is_positional {
+                        warnings.push(Warning::new(
+                            Code::UndefinedVariable,
+                            &format!("variable `${name}` is used but never assigned"),
+                            i,
+                        ));
+                    }
+
+                    if let Some((_, value)) = values.iter().rev().find(|(n, _)| n == name.as_str())
+                    {
+                        if value.contains(['*', '?', '[']) {
+                            warnings.push(Warning::new(
+                                Code::UnquotedGlob,
+                                &format!(
+                                    "variable `${name}` is unquoted and its value `{value}` contains glob characters"
+                                ),
+                                i,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            // W002
+            if let Ast::Statement(Statement::Command(command)) = statement {
+                if is_command(command, "exit") && !command.background && i + 1 < statements.len() {
+                    warnings.push(Warning::new(
+                        Code::UnreachableCode,
+                        "unreachable code after `exit`",
+                        i + 1,
+                    ));
+                }
+            }
+
+            // W003
+            let inputs = commands
+                .iter()
+                .flat_map(|command| {
+                    let redirects = command.redirects.iter().filter_map(|redirect| {
+                        match (&redirect.operator, &redirect.right) {
+                            (RedirectOperator::Lt, Expr::String(path) | Expr::Literal(path)) => Some(path),
+                            _ => None,
+                        }
+                    });
+
+                    let args = command.args.iter().filter_map(|arg| match arg {
+                        Expr::String(path) | Expr::Literal(path) if is_command(command, "cat") => {
+                            Some(path)
+                        }
+                        _ => None,
+                    });
+
+                    redirects.chain(args)
+                })
+                .collect::<HashSet<&String>>();
+
+            for command in &commands {
+                for redirect in &command.redirects {
+                    if let (RedirectOperator::Gt, Expr::String(path) | Expr::Literal(path)) =
+                        (&redirect.operator, &redirect.right)
+                    {
+                        if inputs.contains(path) {
+                            warnings.push(Warning::new(
+                                Code::RedirectToInput,
+                                &format!("`{path}` is read and written by the same command, it is truncated before it is read"),
+                                i,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            // W004
+            if let Ast::Pipe(pipe) = statement {
+                if let (Some(cat), true) = (pipe.commands().front(), pipe.commands().len() > 1) {
+                    if is_command(cat, "cat")
+                        && cat.redirects.is_empty()
+                        && matches!(cat.args.as_slice(), [Expr::String(arg) | Expr::Literal(arg)] if !arg.starts_with('-'))
+                    {
+                        warnings.push(Warning::new(
+                            Code::UselessCat,
+                            "useless use of `cat`, redirect the file to the next command with `<`",
+                            i,
+                        ));
+                    }
+                }
+            }
+
+            // W005
+            for command in &commands {
+                if command.background
+                    && !statements[i + 1..].iter().any(|statement| {
+                        matches!(statement, Ast::Statement(Statement::Command(command)) if is_command(command, "wait"))
+                    })
+                {
+                    warnings.push(Warning::new(
+                        Code::UncheckedBackground,
+                        "background command is never waited for, its exit status is lost",
+                        i,
+                    ));
+                }
+            }
+        }
+
+        warnings.retain(|warning| !self.allowed.contains(warning.code()));
+
+        warnings
+    }
+}
+
+/// Collect the commands of a block and of its nested blocks.
+///
+/// The variables assigned in the block are defined, whichever arm runs.
+fn block_commands<'a>(ast: &'a Ast, defined: &mut HashSet<String>, commands: &mut Vec<&'a Command>) {
+    match ast {
+        Ast::Semicolon(statements) => statements
+            .iter()
+            .for_each(|statement| block_commands(statement, defined, commands)),
+        Ast::Pipe(pipe) => commands.extend(pipe.commands()),
+        Ast::Negated(ast) => block_commands(ast, defined, commands),
+        Ast::Statement(Statement::Command(command)) => commands.push(command),
+        Ast::Statement(Statement::Assign(assign)) => {
+            if let Expr::Ident(name) = &assign.ident {
+                defined.insert(name.to_string());
+            }
+        }
+        Ast::Statement(Statement::Import(_)) => {}
+        Ast::Statement(Statement::Try(r#try)) => {
+            block_commands(&r#try.body, defined, commands);
+
+            if let Some(catch) = &r#try.catch {
+                if let Some(Expr::Ident(name)) = &catch.ident {
+                    defined.insert(name.to_string());
+                }
+
+                block_commands(&catch.body, defined, commands);
+            }
+
+            if let Some(finally) = &r#try.finally {
+                block_commands(finally, defined, commands);
+            }
+        }
+        Ast::Statement(Statement::Match(r#match)) => r#match
+            .arms
+            .iter()
+            .for_each(|arm| block_commands(&arm.body, defined, commands)),
+    }
+}
+
+fn is_command(command: &Command, name: &str) -> bool {
+    matches!(&command.expr, Expr::String(s) | Expr::Literal(s) if s == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn investigate(source: &str) -> Vec<(Code, usize)> {
+        let ast = Parser::new(source).parse().unwrap();
+
+        Investigator::from_source(source)
+            .investigate(&ast)
+            .into_iter()
+            .map(|warning| (warning.code, warning.statement))
+            .collect()
+    }
+
+    #[test]
+    fn test_code_parse() {
+        assert_eq!(Code::parse("W001"), Some(Code::UndefinedVariable));
+        assert_eq!(Code::parse("w004"), Some(Code::UselessCat));
+        assert_eq!(Code::parse("unquoted-glob"), Some(Code::UnquotedGlob));
+        assert_eq!(Code::parse("W999"), None);
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        assert_eq!(
+            investigate("echo $a\n$a = hello\necho $a > $b"),
+            [(Code::UndefinedVariable, 0), (Code::UndefinedVariable, 2)]
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_match() {
+        assert_eq!(
+            investigate("match $a {\n  x => { $b = 1 }\n  _ => { echo $c }\n}\necho $b"),
+            [(Code::UndefinedVariable, 0), (Code::UndefinedVariable, 0)]
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_import() {
+        assert_eq!(
+            investigate("import lib/git.fsh\necho $git::branch $svn::branch"),
+            [(Code::UndefinedVariable, 1)]
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_positional() {
+        assert!(investigate("echo $0 $1 $# $*").is_empty());
+    }
+
+    #[test]
+    fn test_undefined_variable_define() {
+        let ast = Parser::new("echo $HOME").parse().unwrap();
+
+        assert!(Investigator::new()
+            .define("HOME")
+            .investigate(&ast)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(
+            investigate("echo a\nexit 1\necho b\necho c"),
+            [(Code::UnreachableCode, 2)]
+        );
+
+        assert!(investigate("echo a\nexit 1").is_empty());
+    }
+
+    #[test]
+    fn test_redirect_to_input() {
+        assert_eq!(
+            investigate("sort < file > file"),
+            [(Code::RedirectToInput, 0)]
+        );
+
+        assert_eq!(
+            investigate("cat -n file | sort > file"),
+            [(Code::RedirectToInput, 0)]
+        );
+
+        assert!(investigate("sort < a > b").is_empty());
+    }
+
+    #[test]
+    fn test_useless_cat() {
+        assert_eq!(investigate("cat file | grep a"), [(Code::UselessCat, 0)]);
+
+        assert!(investigate("cat -n file | grep a").is_empty());
+        assert!(investigate("cat a b | grep a").is_empty());
+    }
+
+    #[test]
+    fn test_unchecked_background() {
+        assert_eq!(
+            investigate("sleep 1 &\necho a"),
+            [(Code::UncheckedBackground, 0)]
+        );
+
+        assert!(investigate("sleep 1 &\nwait").is_empty());
+    }
+
+    #[test]
+    fn test_unquoted_glob() {
+        assert_eq!(
+            investigate("$files = '*.rs'\nls $files"),
+            [(Code::UnquotedGlob, 1)]
+        );
+    }
+
+    #[test]
+    fn test_allow() {
+        assert!(investigate("# fsh-allow: W001, useless-cat\necho $a\ncat a | grep b").is_empty());
+
+        assert_eq!(
+            investigate("echo '# fsh-allow: W001' $a"),
+            [(Code::UndefinedVariable, 0)]
+        );
+
+        let ast = Parser::new("echo $a").parse().unwrap();
+
+        assert!(Investigator::new()
+            .allow(Code::UndefinedVariable)
+            .investigate(&ast)
+            .is_empty());
+    }
+}
@@ -1,70 +1,432 @@
 use super::{
-    token::Token,
-    utils::{remove_comment, remove_empty_line, replace_line_with_semicolon},
+    diagnostic::{Diagnostic, Location},
+    token::{Keyword, Lexeme, Span, Token, TokenKind},
+};
+use fsh_common::{Error, ErrorKind};
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
+    io::{self, Read},
+    iter::Peekable,
+    str::Chars,
 };
 
-const SYMBOLS: [char; 11] = [';', '=', '\\', '\'', '"', '&', '$', '@', '|', '>', '<'];
-
-#[derive(Debug)]
-pub struct Lexer {
-    source: Vec<char>,
+pub(crate) const SYMBOLS: [char; 11] = [';', '=', '\\', '\'', '"', '&', '$', '@', '|', '>', '<'];
+
+/// The size of the chunks read from a reader.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streaming lexer.
+///
+/// The lexer reads the source as it goes, without preprocessing. The lexemes hold byte ranges of the source, so reading them does not copy the text.
+///
+/// # Example
+/// ```
+/// use fsh_parser::{lexer::Lexer, token::TokenKind};
+///
+/// let mut lexer = Lexer::new("echo 'hello world'");
+///
+/// let echo = lexer.next_lexeme().unwrap();
+/// let hello = lexer.next_lexeme().unwrap();
+///
+/// assert_eq!(echo.kind, TokenKind::Word);
+/// assert_eq!(lexer.text(echo.span), "echo");
+///
+/// assert_eq!(hello.kind, TokenKind::Quoted);
+/// assert_eq!(lexer.text(hello.span), "'hello world'");
+///
+/// assert_eq!(lexer.next_lexeme().unwrap().kind, TokenKind::EOF);
+/// ```
+pub struct Lexer<'a> {
+    /// The input read so far, from `offset`. The input before it is discarded once it is parsed.
+    source: Cow<'a, str>,
+    offset: usize,
+    reader: Option<Box<dyn Read + 'a>>,
+    /// Bytes read but not yet decoded, the end of a chunk may split a character.
+    pending: Vec<u8>,
+    read_error: Option<Error>,
     position: usize,
+    /// The start of the lines from `first_line`, the lines before it are discarded.
+    lines: Vec<usize>,
+    first_line: usize,
+    lines_scanned: usize,
+    /// The start of the pending here-document bodies, and where the lexing resumes after them.
+    here_document: Option<(usize, usize)>,
+    comments: Vec<(Location, String)>,
+    /// If the next word is in command position, where the keywords are recognised.
+    is_command_position: bool,
 }
 
-impl Lexer {
-    /// Create a new lexer.
-    pub fn new(source: &str) -> Self {
-        let preprocess = |source: &str| -> String {
-            let p1 = remove_comment(source);
+impl<'a> Lexer<'a> {
+    /// Create a new lexer, borrowing the source.
+    pub fn new(source: &'a str) -> Self {
+        Self::with_source(Cow::Borrowed(source), None)
+    }
+
+    /// Create a new lexer, reading the source from the reader as the lexing goes.
+    pub fn from_reader(reader: impl Read + 'a) -> Self {
+        Self::with_source(Cow::Owned(String::new()), Some(Box::new(reader)))
+    }
+
+    fn with_source(source: Cow<'a, str>, reader: Option<Box<dyn Read + 'a>>) -> Self {
+        Self {
+            source,
+            offset: 0,
+            reader,
+            pending: Vec::new(),
+            read_error: None,
+            position: 0,
+            lines: vec![0],
+            first_line: 0,
+            lines_scanned: 0,
+            here_document: None,
+            comments: Vec::new(),
+            is_command_position: true,
+        }
+    }
+
+    /// Get the text of the span.
+    ///
+    /// # Panics
+    /// If the span starts before the discarded input, the parser discards the statements it has parsed.
+    pub fn text(&self, span: Span) -> &str {
+        &self.source[span.start - self.offset..span.end - self.offset]
+    }
+
+    /// Get the comments read so far, and where they start.
+    ///
+    /// Comments are not tokens, they are kept aside so that tools like the formatter can put them back.
+    /// The text does not include the leading `#`.
+    pub fn comments(&self) -> Vec<(Location, &str)> {
+        self.comments
+            .iter()
+            .map(|(location, comment)| (*location, comment.as_str()))
+            .collect()
+    }
+
+    /// Check if the whole input has been read.
+    pub fn is_consumed(&mut self) -> bool {
+        self.char_at(self.position).is_none()
+    }
+
+    /// Convert the lexeme to a token, the text is borrowed unless it has to be unescaped.
+    pub fn token(&self, lexeme: &Lexeme) -> Token<'_> {
+        let text = self.text(lexeme.span);
+
+        match lexeme.kind {
+            TokenKind::EOF => Token::EOF,
+            TokenKind::Pipe => Token::Pipe,
+            TokenKind::PipeStderr => Token::PipeStderr,
+            TokenKind::Assign => Token::Assign,
+            TokenKind::Gt => Token::Gt,
+            TokenKind::Lt => Token::Lt,
+            TokenKind::Semicolon => Token::Semicolon,
+            TokenKind::Ampersand => Token::Ampersand,
+            TokenKind::Bang => Token::Bang,
+            TokenKind::LBrace => Token::LBrace,
+            TokenKind::RBrace => Token::RBrace,
+            TokenKind::Keyword(keyword) => Token::Keyword(keyword),
+            TokenKind::HereDoc(body) => Token::HereDoc(Cow::Borrowed(self.text(body))),
+            TokenKind::Word => Token::String(remove_line_continuation(text)),
+            // a quoted string is never globbed.
+            TokenKind::Quoted => Token::Literal(remove_line_continuation(&text[1..text.len() - 1])),
+            TokenKind::KeyValue(value) => match self.text(value) {
+                quoted if quoted.starts_with(['"', '\'']) => {
+                    let key = self.text(Span::new(lexeme.span.start, value.start));
+
+                    Token::String(Cow::Owned(format!(
+                        "{key}{}",
+                        remove_line_continuation(&quoted[1..quoted.len() - 1])
+                    )))
+                }
+                ansi_c if ansi_c.starts_with("$'") => {
+                    let key = self.text(Span::new(lexeme.span.start, value.start));
+
+                    Token::String(Cow::Owned(format!(
+                        "{key}{}",
+                        decode_ansi_c(&ansi_c[2..ansi_c.len() - 1]).unwrap_or_default()
+                    )))
+                }
+                ident if ident.starts_with('$') => Token::KeyIdent(
+                    Cow::Borrowed(self.text(Span::new(lexeme.span.start, value.start - 1))),
+                    remove_line_continuation(&ident[1..]),
+                ),
+                _ => Token::String(remove_line_continuation(text)),
+            },
+            TokenKind::AnsiC => Token::Literal(Cow::Owned(
+                decode_ansi_c(&text[2..text.len() - 1]).unwrap_or_default(),
+            )),
+            TokenKind::Ident => Token::Ident(remove_line_continuation(&text[1..])),
+            TokenKind::Number(number) => Token::Number(number),
+            TokenKind::Float(number) => Token::Float(number),
+            TokenKind::FD(fd) => Token::FD(fd),
+        }
+    }
+
+    /// Make sure the input is read up to the position, reading more from the reader if needed.
+    ///
+    /// # Returns
+    /// false if the input ends before the position.
+    fn fill(&mut self, position: usize) -> bool {
+        while position >= self.end() {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => return false,
+            };
+
+            let len = self.pending.len();
+
+            self.pending.resize(len + CHUNK_SIZE, 0);
+
+            let result = reader.read(&mut self.pending[len..]);
+
+            let read = match &result {
+                Ok(read) => *read,
+                Err(_) => 0,
+            };
 
-            let p2 = remove_empty_line(&p1);
+            self.pending.truncate(len + read);
 
-            let p3 = replace_line_with_semicolon(&p2);
+            match result {
+                Ok(0) => {
+                    self.reader = None;
 
-            p3
+                    if !self.pending.is_empty() {
+                        self.read_error = Some(Error::new(
+                            ErrorKind::InvalidInput,
+                            "stream did not contain valid UTF-8",
+                        ));
+                    }
+                }
+                Ok(_) => self.decode(),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.reader = None;
+                    self.read_error = Some(Error::new(ErrorKind::Other, &err.to_string()));
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Move the complete characters of the pending bytes to the source.
+    fn decode(&mut self) {
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(string) => string.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => {
+                self.reader = None;
+                self.read_error = Some(Error::new(
+                    ErrorKind::InvalidInput,
+                    "stream did not contain valid UTF-8",
+                ));
+                return;
+            }
         };
 
-        let source = preprocess(source);
+        // the bytes are valid UTF-8 up to `valid`
+        if let Ok(string) = std::str::from_utf8(&self.pending[..valid]) {
+            self.source.to_mut().push_str(string);
+        }
 
-        Self {
-            source: source.chars().collect(),
-            position: 0,
+        self.pending.drain(..valid);
+    }
+
+    /// Read the whole input.
+    fn fill_all(&mut self) {
+        while self.fill(self.end()) {}
+    }
+
+    /// Get the position of the end of the input read so far.
+    fn end(&self) -> usize {
+        self.offset + self.source.len()
+    }
+
+    /// Discard the input before the line of the position, the spans before it are no longer valid.
+    ///
+    /// The input is only moved once the discarded part is as long as the rest, so moving it costs no more than reading it.
+    pub(crate) fn discard(&mut self, position: usize) {
+        self.location(position);
+
+        let line = self.lines.partition_point(|start| *start <= position) - 1;
+
+        let start = self.lines[line];
+
+        let len = start - self.offset;
+
+        if len == 0 || len < self.source.len() - len {
+            return;
+        }
+
+        match &mut self.source {
+            Cow::Borrowed(source) => *source = &source[len..],
+            Cow::Owned(source) => {
+                source.drain(..len);
+            }
         }
+
+        self.offset = start;
+        self.lines.drain(..line);
+        self.first_line += line;
     }
 
-    fn current_char(&self) -> Option<&char> {
-        self.source.get(self.position)
+    fn char_at(&mut self, position: usize) -> Option<char> {
+        if !self.fill(position) {
+            return None;
+        }
+
+        self.source[position - self.offset..].chars().next()
     }
 
-    fn peek_char(&self) -> Option<&char> {
-        self.source.get(self.position + 1)
+    fn current_char(&mut self) -> Option<char> {
+        self.char_at(self.position)
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        let c = self.current_char()?;
+
+        self.char_at(self.position + c.len_utf8())
     }
 
     fn advance(&mut self) {
-        self.position += 1;
+        if let Some(c) = self.current_char() {
+            self.position += c.len_utf8();
+        }
     }
 
-    fn read_while<F>(&mut self, f: F) -> (Vec<char>, Option<&char>)
-    where
-        F: Fn(char) -> bool,
-    {
-        let mut result = Vec::new();
+    /// Get the location (line and column) of the position.
+    fn location(&mut self, position: usize) -> Location {
+        if position > self.lines_scanned {
+            let end = position.min(self.end());
+
+            for (i, b) in self.source.as_bytes()[self.lines_scanned - self.offset..end - self.offset]
+                .iter()
+                .enumerate()
+            {
+                if *b == b'\n' {
+                    self.lines.push(self.lines_scanned + i + 1);
+                }
+            }
+
+            self.lines_scanned = end;
+        }
 
-        while let Some(&c) = self.source.get(self.position) {
-            if f(c) {
-                result.push(c);
-                self.position += 1;
-            } else {
+        let line = self.lines.partition_point(|start| *start <= position);
+
+        let start = self.lines[line - 1];
+
+        let column = self
+            .text(Span::new(start, position.min(self.end())))
+            .chars()
+            .count();
+
+        Location::new(self.first_line + line, column + 1)
+    }
+
+    /// Get the length of the line continuation at the position, a backslash followed by a newline.
+    fn line_continuation_len(&mut self, position: usize) -> Option<usize> {
+        if self.char_at(position) != Some('\\') {
+            return None;
+        }
+
+        match self.char_at(position + 1) {
+            Some('\n') => Some(2),
+            Some('\r') if self.char_at(position + 2) == Some('\n') => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Skip whitespace and line continuations, except newlines which end a statement.
+    fn skip_whitespace(&mut self) {
+        loop {
+            if let Some(len) = self.line_continuation_len(self.position) {
+                self.position += len;
+                continue;
+            }
+
+            match self.current_char() {
+                Some(c) if c.is_whitespace() && c != '\n' => self.position += c.len_utf8(),
+                _ => break,
+            }
+        }
+    }
+
+    /// Check if the position is at a word boundary, that is, whitespace, a semicolon or the end of the input.
+    fn is_word_boundary(&mut self, position: usize) -> bool {
+        match self.char_at(position) {
+            Some(c) => c.is_whitespace() || c == ';',
+            None => true,
+        }
+    }
+
+    /// Check if a `#` at the position starts a comment, it must be at the start of a word.
+    fn is_comment_start(&self, position: usize) -> bool {
+        match self.source[..position - self.offset].chars().next_back() {
+            Some(c) => c.is_whitespace() || c == ';',
+            None => true,
+        }
+    }
+
+    /// Check if a backslash at the position ends the input, the line it would continue is still to come.
+    ///
+    /// Only the next two characters are read, so the check does not read the rest of the input.
+    fn is_trailing_backslash(&mut self, position: usize) -> bool {
+        match self.char_at(position + 1) {
+            None => true,
+            Some('\r') => self.char_at(position + 2).is_none(),
+            _ => false,
+        }
+    }
+
+    /// Find the end of the line, the position of the newline or the end of the input.
+    fn line_end(&mut self, mut position: usize) -> usize {
+        while let Some(c) = self.char_at(position) {
+            if c == '\n' {
                 break;
             }
+
+            position += c.len_utf8();
         }
 
-        let next = self.source.get(self.position);
+        position
+    }
+
+    /// Read a word, that is, characters up to whitespace or a symbol. Line continuations are part of the word.
+    fn read_word(&mut self) -> Span {
+        let start = self.position;
 
-        (result, next)
+        // the symbols and whitespace that end a word are ASCII, so the bytes can be scanned without decoding.
+        while self.fill(self.position) {
+            let b = self.source.as_bytes()[self.position - self.offset];
+
+            if b.is_ascii() {
+                let c = b as char;
+
+                // a backslash that does not continue the line is part of the word, one at the end of the input may.
+                if c == '\\' {
+                    match self.line_continuation_len(self.position) {
+                        Some(len) => self.position += len,
+                        None if self.char_at(self.position + 1).is_some() => self.position += 1,
+                        None => break,
+                    }
+                } else if c.is_whitespace() || SYMBOLS.contains(&c) {
+                    break;
+                } else {
+                    self.position += 1;
+                }
+            } else {
+                match self.current_char() {
+                    Some(c) if !c.is_whitespace() => self.position += c.len_utf8(),
+                    _ => break,
+                }
+            }
+        }
+
+        Span::new(start, self.position)
     }
 
-    fn read_string(&mut self) -> std::result::Result<Option<String>, String> {
+    fn read_string(&mut self) -> std::result::Result<Option<TokenKind>, String> {
         let current_char = self.current_char();
 
         if current_char.is_none() {
@@ -73,49 +435,132 @@ impl Lexer {
 
         let start_position = self.position;
 
-        let is_double_quote = current_char == Some(&'"');
+        let quote = match current_char {
+            Some(c @ ('"' | '\'')) => c,
+            _ => {
+                let span = self.read_word();
 
-        let is_single_quote = current_char == Some(&'\'');
+                if span.is_empty() {
+                    return Ok(None);
+                }
 
-        if is_double_quote || is_single_quote {
-            self.advance();
+                return Ok(Some(TokenKind::Word));
+            }
+        };
+
+        self.advance();
+
+        loop {
+            let rest = &self.source.as_bytes()[self.position - self.offset..];
+
+            if let Some(end) = rest.iter().position(|b| *b == quote as u8) {
+                self.position += end;
+                break;
+            }
+
+            // the quote is not closed in the input read so far.
+            self.position = self.end();
+
+            if !self.fill(self.position) {
+                self.position = start_position;
+
+                if quote == '"' {
+                    Err("unterminated double quote".to_string())?
+                } else {
+                    Err("unterminated single quote".to_string())?
+                }
+            }
         }
 
-        let (string, end_char) = self.read_while(|c| {
-            if is_double_quote {
-                c != '"'
-            } else if is_single_quote {
-                c != '\''
-            } else {
-                !SYMBOLS.contains(&c) && !c.is_whitespace()
+        self.advance();
+
+        Ok(Some(TokenKind::Quoted))
+    }
+
+    /// Read the value of a `NAME=VALUE` argument, after the name, any word before `=`.
+    ///
+    /// The value is one word, a quoted string or a variable, as `P=$HOME`.
+    fn read_key_value(&mut self) -> fsh_common::Result<TokenKind> {
+        // skip `=`
+        self.advance();
+
+        let start = self.position;
+
+        match self.current_char() {
+            Some('"' | '\'') => {
+                self.read_string()
+                    .map_err(|err| Error::new(ErrorKind::Incomplete, &err))?;
+            }
+            Some('$') if self.peek_char() == Some('\'') => match self.read_ansi_c_string() {
+                Ok(_) => {}
+                Err(err) if err.starts_with("unterminated") => {
+                    Err(Error::new(ErrorKind::Incomplete, &err))?
+                }
+                Err(err) => Err(Error::new(ErrorKind::LexerError, &err))?,
+            },
+            Some('$') => {
+                self.read_ident()
+                    .map_err(|err| Error::new(ErrorKind::LexerError, &err))?;
             }
-        });
+            _ => {
+                self.read_word();
+                self.read_word_after_assign();
+            }
+        }
 
-        if is_double_quote && end_char != Some(&'"') {
-            self.position = start_position;
+        // `P=a$HOME` or `P=$a"b"`, a value of parts is not joined.
+        if self.current_char().is_some_and(|c| !c.is_whitespace() && !";&|<>".contains(c)) {
+            Err(Error::new(
+                ErrorKind::LexerError,
+                "the value of NAME=VALUE must be one word, a quoted string or a variable",
+            ))?
+        }
 
-            Err("double quote error".to_string())?
-        } else if is_single_quote && end_char != Some(&'\'') {
-            self.position = start_position;
+        Ok(TokenKind::KeyValue(Span::new(start, self.position)))
+    }
 
-            Err("single quote error".to_string())?
-        } else if !is_double_quote && !is_single_quote && string.is_empty() {
-            self.position = start_position;
+    /// Read the rest of a word argument after `=`, as `--color=auto` or `a=b=c`. `=>` ends the word.
+    fn read_word_after_assign(&mut self) {
+        while self.current_char() == Some('=') && self.peek_char() != Some('>') {
+            self.advance();
+            self.read_word();
+        }
+    }
 
-            return Ok(None);
-        } else {
-            if is_double_quote || is_single_quote {
-                self.advance();
+    /// Read an ANSI-C quoted string, `$'...'`, a backslash escapes the quote.
+    fn read_ansi_c_string(&mut self) -> std::result::Result<TokenKind, String> {
+        let start_position = self.position;
+
+        // skip `$'`
+        self.position += 2;
+
+        loop {
+            if !self.fill(self.position) {
+                self.position = start_position;
+                Err("unterminated ANSI-C quote".to_string())?
+            }
+
+            match self.source.as_bytes()[self.position - self.offset] {
+                b'\\' => self.position += 2,
+                b'\'' => break,
+                _ => self.position += 1,
             }
         }
 
-        Ok(Some(string.into_iter().collect()))
+        self.position += 1;
+
+        if let Err(err) = decode_ansi_c(self.text(Span::new(start_position + 2, self.position - 1))) {
+            self.position = start_position;
+            Err(err)?
+        }
+
+        Ok(TokenKind::AnsiC)
     }
 
-    fn read_number(&mut self) -> std::result::Result<Option<usize>, String> {
+    fn read_number(&mut self) -> std::result::Result<Option<TokenKind>, String> {
         match self.current_char() {
             Some(c) => {
-                if !c.is_digit(10) {
+                if !c.is_ascii_digit() && c != '-' {
                     Err("invalid number".to_string())?
                 }
             }
@@ -124,18 +569,18 @@ impl Lexer {
 
         let start_position = self.position;
 
-        let (string, _) = self.read_while(|c| !c.is_whitespace() && !SYMBOLS.contains(&c));
+        let span = self.read_word();
 
-        match string.into_iter().collect::<String>().parse::<usize>() {
-            Ok(number) => Ok(Some(number)),
-            Err(_) => {
+        match parse_number(&remove_line_continuation(self.text(span))) {
+            Some(kind) => Ok(Some(kind)),
+            None => {
                 self.position = start_position;
                 Err("invalid number".to_string())
             }
         }
     }
 
-    fn read_ident(&mut self) -> std::result::Result<Option<String>, String> {
+    fn read_ident(&mut self) -> std::result::Result<Option<()>, String> {
         let current_char = self.current_char();
 
         if current_char.is_none() {
@@ -144,41 +589,33 @@ impl Lexer {
 
         let start_position = self.position;
 
-        if current_char == Some(&'$') {
+        if current_char == Some('$') {
             self.advance();
         } else {
             self.position = start_position;
             Err("invalid identifier".to_string())?
         }
 
-        let (string, _) = self.read_while(|c| !c.is_whitespace() && !SYMBOLS.contains(&c));
+        let span = self.read_word();
 
-        if string.is_empty() {
-            self.position = start_position;
-            Err("invalid identifier".to_string())?
-        }
+        let ident = remove_line_continuation(self.text(span));
 
-        if let Some(ch) = string.first() {
-            if !ch.is_alphabetic() {
-                self.position = start_position;
-                Err("invalid identifier".to_string())?
-            }
-        } else {
-            self.position = start_position;
-            Err("invalid identifier".to_string())?
-        }
+        // `$?` is the exit status of the last pipeline, `$!` is the process id of the last background job.
+        // `$0`, `$1`... are the positional parameters of a script, `$#` is their number and `$*` all of them.
+        let is_valid = ident == "?"
+            || ident == "!"
+            || ident == "#"
+            || ident == "*"
+            || !ident.is_empty() && ident.chars().all(|c| c.is_ascii_digit())
+            || ident.chars().next().is_some_and(|c| c.is_alphabetic())
+                && ident.chars().last().is_some_and(|c| c.is_alphanumeric());
 
-        if let Some(c) = string.last() {
-            if !c.is_alphanumeric() {
-                self.position = start_position;
-                Err("invalid identifier".to_string())?
-            }
-        } else {
+        if !is_valid {
             self.position = start_position;
             Err("invalid identifier".to_string())?
         }
 
-        Ok(Some(string.into_iter().collect()))
+        Ok(Some(()))
     }
 
     fn read_fd(&mut self) -> std::result::Result<Option<usize>, String> {
@@ -190,16 +627,16 @@ impl Lexer {
 
         let start_position = self.position;
 
-        if current_char == Some(&'@') {
+        if current_char == Some('@') {
             self.advance();
         } else {
             self.position = start_position;
             Err("invalid file descriptor".to_string())?
         }
 
-        let (string, _) = self.read_while(|c| !c.is_whitespace() && !SYMBOLS.contains(&c));
+        let span = self.read_word();
 
-        match string.into_iter().collect::<String>().parse::<usize>() {
+        match remove_line_continuation(self.text(span)).parse::<usize>() {
             Ok(number) => Ok(Some(number)),
             Err(_) => {
                 self.position = start_position;
@@ -208,208 +645,1125 @@ impl Lexer {
         }
     }
 
-    fn read(&mut self) -> fsh_common::Result<Token> {
-        let mut token = Token::EOF;
+    /// Read a comment, from `#` to the end of the line.
+    fn read_comment(&mut self) {
+        let location = self.location(self.position);
 
-        while let Some(ch) = self.current_char() {
-            if ch.is_whitespace() {
-                self.advance();
-                continue;
+        self.advance();
+
+        let start = self.position;
+
+        let end = self.line_end(start);
+
+        self.position = end;
+
+        let comment = self.text(Span::new(start, end)).trim_end().to_string();
+
+        self.comments.push((location, comment));
+    }
+
+    /// Read a here-document, `<<DELIMITER` and its body.
+    ///
+    /// The body is the lines following the line of `<<DELIMITER`, up to a line that contains only the DELIMITER.
+    /// The lexing skips the body when it reaches the end of the line.
+    fn read_here_document(&mut self) -> fsh_common::Result<TokenKind> {
+        // skip `<<` and the whitespace before the delimiter
+        self.position += 2;
+
+        while let Some(' ' | '\t') = self.current_char() {
+            self.advance();
+        }
+
+        let start = self.position;
+
+        let delimiter = match self.read_string() {
+            Ok(Some(TokenKind::Quoted)) => self.text(Span::new(start + 1, self.position - 1)).to_string(),
+            Ok(Some(_)) => remove_line_continuation(self.text(Span::new(start, self.position))).into_owned(),
+            _ => Err(Error::new(
+                ErrorKind::SyntaxError,
+                "expected here-document delimiter",
+            ))?,
+        };
+
+        // the body follows the bodies of the previous here-documents of the line.
+        let (first, body_start) = match self.here_document {
+            Some((first, resume)) => (first, resume),
+            None => {
+                let end = self.line_end(self.position);
+
+                if self.char_at(end).is_none() {
+                    Err(Error::new(ErrorKind::Incomplete, "unterminated here-document"))?
+                }
+
+                (end + 1, end + 1)
+            }
+        };
+
+        let mut line_start = body_start;
+
+        loop {
+            if self.char_at(line_start).is_none() {
+                Err(Error::new(ErrorKind::Incomplete, "unterminated here-document"))?
+            }
+
+            let line_end = self.line_end(line_start);
+
+            if self.text(Span::new(line_start, line_end)).trim_end_matches('\r') == delimiter {
+                let resume = if self.char_at(line_end).is_some() {
+                    line_end + 1
+                } else {
+                    line_end
+                };
+
+                self.here_document = Some((first, resume));
+
+                return Ok(TokenKind::HereDoc(Span::new(body_start, line_start)));
             }
 
-            match ch {
+            line_start = line_end + 1;
+        }
+    }
+
+    fn read(&mut self) -> fsh_common::Result<Lexeme> {
+        loop {
+            self.skip_whitespace();
+
+            let start = self.position;
+
+            let ch = match self.current_char() {
+                Some(ch) => ch,
+                None => {
+                    if let Some(err) = self.read_error.take() {
+                        Err(err)?
+                    }
+
+                    return Ok(Lexeme::new(TokenKind::EOF, Span::new(start, start)));
+                }
+            };
+
+            let kind = match ch {
+                '#' if self.is_comment_start(self.position) => {
+                    self.read_comment();
+                    continue;
+                }
+
+                '\n' => {
+                    self.is_command_position = true;
+
+                    match self.here_document {
+                        Some((body, resume)) if body == self.position + 1 => {
+                            self.here_document = None;
+                            self.position = resume;
+                        }
+                        _ => self.advance(),
+                    }
+
+                    return Ok(Lexeme::new(
+                        TokenKind::Semicolon,
+                        Span::new(start, start + 1),
+                    ));
+                }
+
                 ';' => {
-                    token = Token::Semicolon;
                     self.advance();
-                    break;
+                    TokenKind::Semicolon
                 }
 
                 '=' => {
-                    token = Token::Assign;
                     self.advance();
-                    break;
+                    TokenKind::Assign
                 }
 
                 '&' => {
-                    token = Token::Ampersand;
                     self.advance();
-                    break;
+                    TokenKind::Ampersand
+                }
+
+                '|' if self.peek_char() == Some('&') => {
+                    self.advance();
+                    self.advance();
+                    TokenKind::PipeStderr
                 }
 
                 '|' => {
-                    token = Token::Pipe;
                     self.advance();
-                    break;
+                    TokenKind::Pipe
                 }
 
                 '>' => {
-                    token = Token::Gt;
                     self.advance();
-                    break;
+                    TokenKind::Gt
                 }
 
+                '<' if self.peek_char() == Some('<') => self.read_here_document()?,
+
                 '<' => {
-                    token = Token::Lt;
                     self.advance();
-                    break;
+                    TokenKind::Lt
+                }
+
+                '{' if self.is_word_boundary(self.position + 1) => {
+                    self.advance();
+                    TokenKind::LBrace
                 }
 
-                '@' => {
-                    // if let Ok(fd) = self.read_fd() {
-                    //     if let Some(fd) = fd {
-                    //         token = Token::FD(fd as i32);
-                    //     } else {
-                    //         token = Token::EOF;
-                    //     }
-                    // } else {
-                    //     token = Token::String("@".to_string());
-                    //     self.advance();
-                    // }
-
-                    match self.read_fd() {
-                        Ok(Some(fd)) => token = Token::FD(fd as i32),
-                        Ok(None) => token = Token::EOF,
-                        Err(err) => match self.peek_char() {
-                            Some(ch) => {
-                                if ch.is_whitespace() {
-                                    token = Token::String("@".to_string());
-                                    self.advance();
-                                } else {
-                                    Err(fsh_common::Error::new(fsh_common::ErrorKind::LexerError, &err))?;
-                                }
-                            }
-                            None => {
-                                token = Token::String("@".to_string());
-                                self.advance();
-                            }
+                '}' if self.is_word_boundary(self.position + 1) => {
+                    self.advance();
+                    TokenKind::RBrace
+                }
+
+                '\\' if self.is_trailing_backslash(self.position) => {
+                    Err(Error::new(ErrorKind::Incomplete, "line continuation"))?
+                }
+
+                '@' => match self.read_fd() {
+                    Ok(Some(fd)) => TokenKind::FD(fd as i32),
+                    Ok(None) => TokenKind::EOF,
+                    Err(err) => match self.peek_char() {
+                        Some(ch) if !ch.is_whitespace() => {
+                            Err(Error::new(ErrorKind::LexerError, &err))?
+                        }
+                        _ => {
+                            self.advance();
+                            TokenKind::Word
+                        }
+                    },
+                },
+
+                '$' if self.peek_char() == Some('\'') => match self.read_ansi_c_string() {
+                    Ok(kind) => kind,
+                    Err(err) if err.starts_with("unterminated") => {
+                        Err(Error::new(ErrorKind::Incomplete, &err))?
+                    }
+                    Err(err) => Err(Error::new(ErrorKind::LexerError, &err))?,
+                },
+
+                '$' => match self.read_ident() {
+                    Ok(Some(_)) => TokenKind::Ident,
+                    Ok(None) => TokenKind::EOF,
+                    Err(err) => match self.peek_char() {
+                        Some(ch) if !ch.is_whitespace() => {
+                            Err(Error::new(ErrorKind::LexerError, &err))?
+                        }
+                        _ => {
+                            self.advance();
+                            TokenKind::Word
+                        }
+                    },
+                },
+
+                // an unterminated quote may be closed on the following lines.
+                '"' | '\'' => match self.read_string() {
+                    Ok(Some(kind)) => kind,
+                    Ok(None) => TokenKind::EOF,
+                    Err(err) => Err(Error::new(ErrorKind::Incomplete, &err))?,
+                },
+
+                '0'..='9' | '-'
+                    if ch.is_ascii_digit() || self.peek_char().is_some_and(|c| c.is_ascii_digit()) =>
+                {
+                    match self.read_number() {
+                        Ok(Some(kind)) => kind,
+                        Ok(None) => TokenKind::EOF,
+                        Err(err) => match self.read_string() {
+                            Ok(Some(kind)) => kind,
+                            Ok(None) => TokenKind::EOF,
+                            Err(_) => Err(Error::new(ErrorKind::LexerError, &err))?,
                         },
                     }
+                }
 
-                    break;
+                // `export a=1`, `ls --color=auto` or `grep ^P=`, an argument with `=` is one word.
+                // the name is checked by the command, a word in command position is never an argument.
+                _ => match self.read_string() {
+                    Ok(Some(TokenKind::Word))
+                        if !self.is_command_position
+                            && self.current_char() == Some('=')
+                            && self.peek_char() != Some('>') =>
+                    {
+                        self.read_key_value()?
+                    }
+                    Ok(Some(kind)) => kind,
+                    Ok(None) => TokenKind::EOF,
+                    Err(err) => {
+                        if self.peek_char().is_some() {
+                            Err(Error::new(ErrorKind::LexerError, &err))?
+                        }
+
+                        TokenKind::EOF
+                    }
+                },
+            };
+
+            // the input ends only at its end, the rest of the input would be lost.
+            if kind == TokenKind::EOF {
+                if let Some(c) = self.current_char() {
+                    self.position = start;
+
+                    Err(Error::new(
+                        ErrorKind::LexerError,
+                        &format!("unexpected character '{c}'"),
+                    ))?
                 }
+            }
+
+            let span = Span::new(start, self.position);
 
-                '$' => {
-                    // if let Ok(ident) = self.read_ident() {
-                    //     if let Some(ident) = ident {
-                    //         token = Token::Ident(ident);
-                    //     } else {
-                    //         token = Token::EOF;
-                    //     }
-                    // } else {
-                    //     token = Token::String("$".to_string());
-                    //     self.advance();
-                    // }
-
-                    match self.read_ident() {
-                        Ok(Some(ident)) => token = Token::Ident(ident),
-                        Ok(None) => token = Token::EOF,
-                        Err(err) => match self.peek_char() {
-                            Some(ch) => {
-                                if ch.is_whitespace() {
-                                    token = Token::String("$".to_string());
-                                    self.advance();
-                                } else {
-                                    Err(fsh_common::Error::new(fsh_common::ErrorKind::LexerError, &err))?;
-                                }
-                            }
-                            None => {
-                                token = Token::String("$".to_string());
-                                self.advance();
-                            }
+            // a bare word in command position may be a keyword or `!`, a quoted one is always a word.
+            let kind = match kind {
+                TokenKind::Word if self.is_command_position => {
+                    match remove_line_continuation(self.text(span)).as_ref() {
+                        "!" => TokenKind::Bang,
+                        word => match Keyword::parse(word) {
+                            Some(keyword) => TokenKind::Keyword(keyword),
+                            None => kind,
                         },
                     }
-
-                    break;
                 }
+                _ => kind,
+            };
+
+            // the empty block `{}` is a word, but it ends a block like `}`.
+            self.is_command_position = matches!(
+                kind,
+                TokenKind::Semicolon
+                    | TokenKind::Pipe
+                    | TokenKind::PipeStderr
+                    | TokenKind::Ampersand
+                    | TokenKind::Bang
+                    | TokenKind::LBrace
+                    | TokenKind::RBrace
+                    | TokenKind::Keyword(Keyword::If | Keyword::Else | Keyword::While)
+            ) || (kind == TokenKind::Word && self.text(span) == "{}");
+
+            return Ok(Lexeme::new(kind, span));
+        }
+    }
+
+    /// Read the next lexeme.
+    ///
+    /// At the end of the input, the lexeme is EOF. On error, the position is left at the start of the lexeme.
+    pub fn next_lexeme(&mut self) -> fsh_common::Result<Lexeme> {
+        self.read()
+    }
+
+    pub fn tokenize(&mut self) -> fsh_common::Result<Vec<Token<'static>>> {
+        let (tokens, diagnostics) = self.tokenize_with_location();
+
+        if let Some(diagnostic) = diagnostics.into_iter().next() {
+            Err(diagnostic.into_error())?
+        }
+
+        Ok(tokens.into_iter().map(|(token, _)| token).collect())
+    }
 
-                '"' | '\'' => {
-                    // if let Ok(string) = self.read_string() {
-                    //     if let Some(string) = string {
-                    //         token = Token::String(string);
-                    //     } else {
-                    //         token = Token::EOF;
-                    //     }
-                    // }
+    /// Tokenize the input, with the location of each token.
+    ///
+    /// Errors do not stop the tokenizing. The tokens of the statement where the error occurred are dropped, and tokenizing resumes at the next statement.
+    ///
+    /// # Returns
+    /// The tokens and the diagnostics of the errors.
+    pub fn tokenize_with_location(&mut self) -> (Vec<(Token<'static>, Location)>, Vec<Diagnostic>) {
+        let mut tokens: Vec<(Token, Location)> = Vec::new();
 
-                    match self.read_string() {
-                        Ok(Some(string)) => token = Token::String(string),
-                        Ok(None) => token = Token::EOF,
-                        Err(err) => Err(fsh_common::Error::new(fsh_common::ErrorKind::LexerError, &err))?,
+        let mut diagnostics = Vec::new();
+
+        let mut last = None;
+
+        loop {
+            match self.next_located(last) {
+                Ok((lexeme, location)) => {
+                    tokens.push((self.token(&lexeme).into_owned(), location));
+
+                    last = Some(lexeme.kind);
+
+                    if lexeme.kind == TokenKind::EOF {
+                        break;
                     }
+                }
+                Err(diagnostic) => {
+                    // drop the statement
+                    while let Some((token, _)) = tokens.last() {
+                        if *token == Token::Semicolon {
+                            break;
+                        }
 
-                    break;
+                        tokens.pop();
+                    }
+
+                    last = tokens.last().map(|_| TokenKind::Semicolon);
+
+                    diagnostics.push(diagnostic);
                 }
+            }
+        }
 
-                '0'..='9' => {
-                    // if let Ok(number) = self.read_number() {
-                    //     if let Some(number) = number {
-                    //         token = Token::Number(number);
-                    //     } else {
-                    //         token = Token::EOF;
-                    //     }
-                    // }
+        (tokens, diagnostics)
+    }
 
-                    match self.read_number() {
-                        Ok(Some(number)) => token = Token::Number(number),
-                        Ok(None) => token = Token::EOF,
-                        Err(err) => match self.read_string() {
-                            Ok(Some(string)) => token = Token::String(string),
-                            Ok(None) => token = Token::EOF,
-                            Err(_) => Err(fsh_common::Error::new(fsh_common::ErrorKind::LexerError, &err))?,
-                        },
+    /// Read the next lexeme and its location, after the last lexeme of the statement.
+    ///
+    /// A semicolon after a semicolon is skipped, and so is the end of a line after a pipe, the pipeline continues on the next line.
+    ///
+    /// On error, the input is skipped to the next statement, or to its end if it is incomplete.
+    pub(crate) fn next_located(
+        &mut self,
+        last: Option<TokenKind>,
+    ) -> std::result::Result<(Lexeme, Location), Diagnostic> {
+        loop {
+            self.skip_whitespace();
+
+            let location = self.location(self.position);
+
+            let err = match self.read() {
+                Ok(lexeme)
+                    if lexeme.kind == TokenKind::Semicolon
+                        && matches!(
+                            last,
+                            Some(TokenKind::Semicolon | TokenKind::Pipe | TokenKind::PipeStderr)
+                        ) =>
+                {
+                    continue;
+                }
+                Ok(lexeme) => return Ok((lexeme, location)),
+                Err(err) => err,
+            };
+
+            // an incomplete input runs to the end, otherwise skip to the next statement.
+            if err.kind() == &ErrorKind::Incomplete {
+                self.fill_all();
+                self.position = self.end();
+            } else {
+                while let Some(c) = self.current_char() {
+                    if c == ';' || c == '\n' {
+                        break;
                     }
 
-                    break;
+                    self.advance();
                 }
+            }
 
-                _ => {
-                    // if let Ok(string) = self.read_string() {
-                    //     if let Some(string) = string {
-                    //         token = Token::String(string);
-                    //     } else {
-                    //         token = Token::EOF;
-                    //     }
-                    // }
-
-                    match self.read_string() {
-                        Ok(Some(string)) => token = Token::String(string),
-                        Ok(None) => token = Token::EOF,
-                        Err(err) => {
-                            if self.peek_char().is_some() {
-                                Err(fsh_common::Error::new(fsh_common::ErrorKind::LexerError, &err))?;
-                            } else {
-                                token = Token::EOF;
-                            }
-                        }
-                    }
+            return Err(Diagnostic::new(location, err));
+        }
+    }
+}
 
-                    break;
+impl Debug for Lexer<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Lexer")
+            .field("source", &self.source)
+            .field("position", &self.position)
+            .field("here_document", &self.here_document)
+            .field("comments", &self.comments)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parse a numeric literal written in its canonical form, `-1`, `42` or `1.5`.
+///
+/// The other forms, `007`, `+1` or `1e3`, are words so that commands get the text as written.
+pub(crate) fn parse_number(text: &str) -> Option<TokenKind> {
+    if let Ok(number) = text.parse::<i64>() {
+        return (number.to_string() == text).then_some(TokenKind::Number(number));
+    }
+
+    match text.parse::<f64>() {
+        Ok(number)
+            if number.is_finite()
+                && !text.contains(['e', 'E'])
+                && format!("{number:?}") == text =>
+        {
+            Some(TokenKind::Float(number))
+        }
+        _ => None,
+    }
+}
+
+/// Decode the escapes of an ANSI-C quoted string, the text is between the quotes.
+///
+/// `\xHH` and octal escapes are bytes, together they must be valid UTF-8. An unknown escape is kept as written.
+///
+/// The arguments of a command are strings, so a byte that is not part of a character, like `\xff`, is an error rather than passed as is.
+pub(crate) fn decode_ansi_c(text: &str) -> std::result::Result<String, String> {
+    fn digits(chars: &mut Peekable<Chars>, radix: u32, max: usize) -> Option<u32> {
+        let mut value = None;
+
+        for _ in 0..max {
+            match chars.peek().and_then(|c| c.to_digit(radix)) {
+                Some(digit) => {
+                    value = Some(value.unwrap_or(0) * radix + digit);
+                    chars.next();
                 }
+                None => break,
             }
         }
 
-        Ok(token)
+        value
     }
 
-    pub fn tokenize(&mut self) -> fsh_common::Result<Vec<Token>> {
-        let mut tokens = Vec::new();
+    let mut bytes = Vec::with_capacity(text.len());
 
-        loop {
-            let token = self.read()?;
+    let mut chars = text.chars().peekable();
 
-            if token == Token::Semicolon {
-                if tokens.last() == Some(&Token::Semicolon) {
-                    continue;
+    while let Some(c) = chars.next() {
+        let mut buffer = [0; 4];
+
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        let escape = match chars.next() {
+            Some(escape) => escape,
+            None => {
+                bytes.push(b'\\');
+                break;
+            }
+        };
+
+        match escape {
+            'a' => bytes.push(0x07),
+            'b' => bytes.push(0x08),
+            'e' | 'E' => bytes.push(0x1b),
+            'f' => bytes.push(0x0c),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            't' => bytes.push(b'\t'),
+            'v' => bytes.push(0x0b),
+            '\\' | '\'' | '"' | '?' => bytes.push(escape as u8),
+            'x' => match digits(&mut chars, 16, 2) {
+                Some(byte) => bytes.push(byte as u8),
+                None => bytes.extend_from_slice(b"\\x"),
+            },
+            'u' | 'U' => {
+                let max = if escape == 'u' { 4 } else { 8 };
+
+                match digits(&mut chars, 16, max) {
+                    Some(code) => {
+                        let c = char::from_u32(code)
+                            .ok_or_else(|| format!("invalid unicode escape '\\{escape}{code:x}'"))?;
+
+                        bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                    }
+                    None => {
+                        bytes.push(b'\\');
+                        bytes.push(escape as u8);
+                    }
                 }
             }
+            // up to three octal digits, the escape is the first one.
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap();
+
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+
+                bytes.push(value as u8);
+            }
+            escape => {
+                bytes.push(b'\\');
+                bytes.extend_from_slice(escape.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| {
+        "ANSI-C quote is not valid UTF-8, the escaped bytes must form characters".to_string()
+    })
+}
+
+/// Remove the line continuations of the text, it is only copied if there is any.
+fn remove_line_continuation(text: &str) -> Cow<'_, str> {
+    if text.contains("\\\n") || text.contains("\\\r\n") {
+        Cow::Owned(text.replace("\\\r\n", "").replace("\\\n", ""))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_with_location() {
+        let (tokens, diagnostics) = Lexer::new("echo a\n\n  cat <<EOF\nbody\nEOF\nls").tokenize_with_location();
+
+        assert!(diagnostics.is_empty());
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::String("echo".into()), Location::new(1, 1)),
+                (Token::String("a".into()), Location::new(1, 6)),
+                (Token::Semicolon, Location::new(1, 7)),
+                (Token::String("cat".into()), Location::new(3, 3)),
+                (Token::HereDoc("body\n".into()), Location::new(3, 7)),
+                (Token::Semicolon, Location::new(3, 12)),
+                (Token::String("ls".into()), Location::new(6, 1)),
+                (Token::EOF, Location::new(6, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut lexer = Lexer::new("# head\necho a#b # tail 'quote\necho c");
+
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("a#b".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("c".into()),
+                Token::EOF,
+            ]
+        );
+
+        assert_eq!(
+            lexer.comments(),
+            [
+                (Location::new(1, 1), " head"),
+                (Location::new(2, 10), " tail 'quote"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comment_quoted() {
+        let mut lexer = Lexer::new("echo \"issue #12; done\" '# not'; echo a # x; echo b\necho c");
+
+        assert_eq!(
+            lexer.tokenize().unwrap(),
+            vec![
+                Token::String("echo".into()),
+                Token::Literal("issue #12; done".into()),
+                Token::Literal("# not".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("a".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("c".into()),
+                Token::EOF,
+            ]
+        );
+
+        assert_eq!(lexer.comments(), [(Location::new(1, 40), " x; echo b")]);
+    }
+
+    #[test]
+    fn test_backslash() {
+        // a backslash that does not continue the line does not end the input.
+        let tokens = Lexer::new("echo a\\b \\c\necho d\nrm important").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::String("a\\b".into()),
+                Token::String("\\c".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("d".into()),
+                Token::Semicolon,
+                Token::String("rm".into()),
+                Token::String("important".into()),
+                Token::EOF,
+            ]
+        );
+
+        let err = Lexer::new("echo a\\").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Incomplete);
+    }
+
+    #[test]
+    fn test_newline() {
+        let tokens = Lexer::new("echo 'a\nb'\n\n echo c").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::Literal("a\nb".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("c".into()),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_location_recover() {
+        let (tokens, diagnostics) = Lexer::new("echo $1a\necho ok\necho @x").tokenize_with_location();
+
+        assert_eq!(
+            tokens.into_iter().map(|(token, _)| token).collect::<Vec<Token>>(),
+            vec![
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("ok".into()),
+                Token::Semicolon,
+                Token::EOF,
+            ]
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].location(), &Location::new(1, 6));
+        assert_eq!(diagnostics[0].error().kind(), &ErrorKind::LexerError);
+
+        assert_eq!(diagnostics[1].location(), &Location::new(3, 6));
+        assert_eq!(diagnostics[1].error().kind(), &ErrorKind::LexerError);
+    }
+
+    fn strings(tokens: Vec<Token>) -> Vec<String> {
+        tokens.into_iter().map(|token| token.to_string()).collect()
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let tokens = Lexer::new("echo a \\\nb \\\r\nc ec\\\nho '# \\\nd'\necho e # \\\necho f")
+            .tokenize()
+            .unwrap();
+
+        assert_eq!(
+            strings(tokens),
+            ["echo", "a", "b", "c", "echo", "# d", ";", "echo", "e", ";", "echo", "f", "EOF"]
+        );
+    }
+
+    #[test]
+    fn test_line_continuation_location() {
+        let (tokens, diagnostics) =
+            Lexer::new("echo a \\\nb \\\r\nc\necho d").tokenize_with_location();
+
+        assert!(diagnostics.is_empty());
+
+        // the joined lines keep their numbers.
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::String("echo".into()), Location::new(1, 1)),
+                (Token::String("a".into()), Location::new(1, 6)),
+                (Token::String("b".into()), Location::new(2, 1)),
+                (Token::String("c".into()), Location::new(3, 1)),
+                (Token::Semicolon, Location::new(3, 2)),
+                (Token::String("echo".into()), Location::new(4, 1)),
+                (Token::String("d".into()), Location::new(4, 6)),
+                (Token::EOF, Location::new(4, 7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing_backslash() {
+        for source in ["echo a \\", "echo a \\\r"] {
+            let err = Lexer::new(source).tokenize().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::Incomplete, "{source:?}");
+        }
+
+        // the continued line is empty, the input is complete.
+        let tokens = Lexer::new("echo a \\\n\necho b").tokenize().unwrap();
+
+        assert_eq!(strings(tokens), ["echo", "a", ";", "echo", "b", "EOF"]);
+    }
+
+    #[test]
+    fn test_here_document_multiple() {
+        let (tokens, diagnostics) =
+            Lexer::new("cat <<A <<'B'; echo x\na\nA\nb\nB\necho y").tokenize_with_location();
+
+        assert!(diagnostics.is_empty());
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::String("cat".into()), Location::new(1, 1)),
+                (Token::HereDoc("a\n".into()), Location::new(1, 5)),
+                (Token::HereDoc("b\n".into()), Location::new(1, 9)),
+                (Token::Semicolon, Location::new(1, 14)),
+                (Token::String("echo".into()), Location::new(1, 16)),
+                (Token::String("x".into()), Location::new(1, 21)),
+                (Token::Semicolon, Location::new(1, 22)),
+                (Token::String("echo".into()), Location::new(6, 1)),
+                (Token::String("y".into()), Location::new(6, 6)),
+                (Token::EOF, Location::new(6, 7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_document_quoted() {
+        let tokens = Lexer::new("echo '<<EOF' a#b # <<EOF\necho c").tokenize().unwrap();
+
+        assert_eq!(strings(tokens), ["echo", "<<EOF", "a#b", ";", "echo", "c", "EOF"]);
+    }
+
+    #[test]
+    fn test_here_document_comment() {
+        let tokens = Lexer::new("cat <<EOF # <<X\nbody\nEOF\necho a#b <<X\nX\n").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("cat".into()),
+                Token::HereDoc("body\n".into()),
+                Token::Semicolon,
+                Token::String("echo".into()),
+                Token::String("a#b".into()),
+                Token::HereDoc("".into()),
+                Token::Semicolon,
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_document_incomplete() {
+        for source in ["cat <<EOF", "cat <<EOF\nhello\n", "cat <<"] {
+            let err = Lexer::new(source).tokenize().unwrap_err();
+
+            assert_ne!(err.kind(), &ErrorKind::LexerError, "{source}");
+        }
+
+        let err = Lexer::new("cat <<EOF\nhello\n").tokenize().unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::Incomplete);
+    }
+
+    #[test]
+    fn test_next_lexeme() {
+        let mut lexer = Lexer::new("$x = \"a b\" @2>out");
 
-            tokens.push(token);
+        let mut lexemes = Vec::new();
+
+        loop {
+            let lexeme = lexer.next_lexeme().unwrap();
 
-            if tokens.last() == Some(&Token::EOF) {
+            lexemes.push((lexeme.kind, lexer.text(lexeme.span).to_string()));
+
+            if lexeme.kind == TokenKind::EOF {
                 break;
             }
         }
 
-        Ok(tokens)
+        assert_eq!(
+            lexemes,
+            [
+                (TokenKind::Ident, "$x".to_string()),
+                (TokenKind::Assign, "=".to_string()),
+                (TokenKind::Quoted, "\"a b\"".to_string()),
+                (TokenKind::FD(2), "@2".to_string()),
+                (TokenKind::Gt, ">".to_string()),
+                (TokenKind::Word, "out".to_string()),
+                (TokenKind::EOF, "".to_string()),
+            ]
+        );
+    }
+
+    /// A reader returning the input a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(3);
+
+            buf[..len].copy_from_slice(&self.0[..len]);
+
+            self.0 = &self.0[len..];
+
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let source = "echo 'héllo wörld' # ç\ncat <<EOF\n日本語\nEOF\nls $x";
+
+        let tokens = Lexer::from_reader(Trickle(source.as_bytes()))
+            .tokenize_with_location();
+
+        assert_eq!(tokens, Lexer::new(source).tokenize_with_location());
+    }
+
+    #[test]
+    fn test_discard() {
+        let source = "echo a\n".repeat(1000) + "cat <<EOF # x\nbody\nEOF";
+
+        let mut lexer = Lexer::from_reader(source.as_bytes());
+
+        let (cat, location) = loop {
+            let (lexeme, location) = lexer.next_located(None).unwrap();
+
+            if lexer.text(lexeme.span) == "cat" {
+                break (lexeme, location);
+            }
+        };
+
+        assert_eq!(location, Location::new(1001, 1));
+
+        // the input before the line is discarded, once it is as long as the rest.
+        lexer.discard(cat.span.start);
+
+        assert_eq!(lexer.source, "cat <<EOF # x\nbody\nEOF");
+
+        let (here_document, location) = lexer.next_located(None).unwrap();
+
+        assert_eq!(lexer.token(&here_document), Token::HereDoc("body\n".into()));
+        assert_eq!(location, Location::new(1001, 5));
+
+        assert_eq!(lexer.next_located(None).unwrap().0.kind, TokenKind::Semicolon);
+        assert_eq!(lexer.next_located(None).unwrap().1, Location::new(1003, 4));
+
+        assert_eq!(lexer.comments(), [(Location::new(1001, 11), " x")]);
+    }
+
+    #[test]
+    fn test_from_reader_invalid_utf8() {
+        let err = Lexer::from_reader(&b"echo \xff\xfe"[..]).tokenize().unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_number() {
+        let tokens = Lexer::new("echo 42 -1 1.5 -0.25 1.0 007 1e3 +1 3. - -x 99999999999999999999")
+            .tokenize()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::Number(42),
+                Token::Number(-1),
+                Token::Float(1.5),
+                Token::Float(-0.25),
+                Token::Float(1.0),
+                Token::String("007".into()),
+                Token::String("1e3".into()),
+                Token::String("+1".into()),
+                Token::String("3.".into()),
+                Token::String("-".into()),
+                Token::String("-x".into()),
+                Token::String("99999999999999999999".into()),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword() {
+        let tokens = Lexer::new("if true { echo if } else { 'while' x | fn\nreturn }; $x = for; break&match")
+            .tokenize()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Keyword(Keyword::If),
+                Token::String("true".into()),
+                Token::LBrace,
+                Token::String("echo".into()),
+                Token::String("if".into()),
+                Token::RBrace,
+                Token::Keyword(Keyword::Else),
+                Token::LBrace,
+                Token::Literal("while".into()),
+                Token::String("x".into()),
+                Token::Pipe,
+                Token::Keyword(Keyword::Fn),
+                Token::Semicolon,
+                Token::Keyword(Keyword::Return),
+                Token::RBrace,
+                Token::Semicolon,
+                Token::Ident("x".into()),
+                Token::Assign,
+                Token::String("for".into()),
+                Token::Semicolon,
+                Token::Keyword(Keyword::Break),
+                Token::Ampersand,
+                Token::Keyword(Keyword::Match),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ansi_c_string() {
+        let tokens = Lexer::new(r"echo $'a\tb\e\x41é\101\'' $'*' '$x'")
+            .tokenize()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::Literal("a\tb\x1bA\u{e9}A'".into()),
+                Token::Literal("*".into()),
+                Token::Literal("$x".into()),
+                Token::EOF,
+            ]
+        );
+
+        let err = Lexer::new(r"echo $'a\'").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Incomplete);
+
+        // the escaped bytes of a character, but not a lone byte, the arguments are strings.
+        let tokens = Lexer::new(r"echo $'\xc3\xa9\303\251'").tokenize().unwrap();
+        assert_eq!(tokens[1], Token::Literal("\u{e9}\u{e9}".into()));
+
+        for source in [r"echo $'\xff'", r"echo $'\xc3'", r"echo $'\377'"] {
+            let err = Lexer::new(source).tokenize().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::LexerError, "{source}");
+            assert!(err.message().contains("not valid UTF-8"), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_status_ident() {
+        let tokens = Lexer::new("echo $? $! $PIPESTATUS").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::Ident("?".into()),
+                Token::Ident("!".into()),
+                Token::Ident("PIPESTATUS".into()),
+                Token::EOF,
+            ]
+        );
+
+        let err = Lexer::new("echo $??").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LexerError);
+    }
+
+    #[test]
+    fn test_positional_ident() {
+        let tokens = Lexer::new("echo $0 $12 $# $*").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("echo".into()),
+                Token::Ident("0".into()),
+                Token::Ident("12".into()),
+                Token::Ident("#".into()),
+                Token::Ident("*".into()),
+                Token::EOF,
+            ]
+        );
+
+        let err = Lexer::new("echo $1a").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LexerError);
+    }
+
+    #[test]
+    fn test_key_value() {
+        let tokens = Lexer::new("export ll='ls -la' g=git x=\"a b\" e=\nx=1").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("export".into()),
+                Token::String("ll=ls -la".into()),
+                Token::String("g=git".into()),
+                Token::String("x=a b".into()),
+                Token::String("e=".into()),
+                Token::Semicolon,
+                // a word in command position is not an argument.
+                Token::String("x".into()),
+                Token::Assign,
+                Token::Number(1),
+                Token::EOF,
+            ]
+        );
+
+        let err = Lexer::new("export a='ls").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Incomplete);
+
+        // the value may be a variable, or an ANSI-C quoted string, the name is any word.
+        let tokens = Lexer::new("export P=$HOME c=$1 t=$'a\\tb' --prefix=$HOME a-b='x y'").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("export".into()),
+                Token::KeyIdent("P".into(), "HOME".into()),
+                Token::KeyIdent("c".into(), "1".into()),
+                Token::String("t=a\tb".into()),
+                Token::KeyIdent("--prefix".into(), "HOME".into()),
+                Token::String("a-b=x y".into()),
+                Token::EOF,
+            ]
+        );
+
+        // a value of parts is not joined.
+        let err = Lexer::new("export P=a$HOME").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LexerError);
+
+        let err = Lexer::new("export P=$HOME\"x\"").tokenize().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LexerError);
+
+        // an argument with `=` is one word, `=>` still ends it.
+        let tokens = Lexer::new("ls --color=auto a=b=c; grep ^P= x=>").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("ls".into()),
+                Token::String("--color=auto".into()),
+                Token::String("a=b=c".into()),
+                Token::Semicolon,
+                Token::String("grep".into()),
+                Token::String("^P=".into()),
+                Token::String("x".into()),
+                Token::Assign,
+                Token::Gt,
+                Token::EOF,
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pipe_stderr() {
+        let tokens = Lexer::new("make |& grep error|&cat").tokenize().unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("make".into()),
+                Token::PipeStderr,
+                Token::String("grep".into()),
+                Token::String("error".into()),
+                Token::PipeStderr,
+                Token::String("cat".into()),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_incomplete() {
+        let err = Lexer::new("echo 'hello").tokenize().unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::Incomplete);
+    }
+}
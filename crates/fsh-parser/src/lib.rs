@@ -1,10 +1,15 @@
 mod utils;
 mod parser;
+mod diagnostic;
+mod formatter;
 
 // pub mod
 pub mod lexer;
 pub mod token;
 pub mod lite_parser;
+pub mod investigate;
 
 // pub use 
-pub use parser::*;
\ No newline at end of file
+pub use parser::*;
+pub use diagnostic::*;
+pub use formatter::*;
\ No newline at end of file
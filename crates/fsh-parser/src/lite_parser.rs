@@ -1,6 +1,6 @@
 use super::{
     token::Token,
-    utils::{recursion_split, split},
+    utils::recursion_split,
 };
 use fsh_ast::*;
 use fsh_common::{Error, ErrorKind, Result};
@@ -25,6 +25,7 @@ pub fn parse_ident(token: &Token) -> Result<Expr> {
 pub fn parse_number(token: &Token) -> Result<Expr> {
     match token {
         Token::Number(n) => Ok(Expr::Number(*n)),
+        Token::Float(n) => Ok(Expr::Float(*n)),
         _ => Err(Error::new(ErrorKind::SyntaxError, "Expected number")),
     }
 }
@@ -38,26 +39,32 @@ pub fn parse_fd(token: &Token) -> Result<Expr> {
 }
 
 /// Parse an expression token.
-fn parse_expr(token: &Token, _exclude_tokens: Vec<Token>) -> Result<Expr> {
+fn parse_expr(token: &Token, _exclude_tokens: &[Token]) -> Result<Expr> {
     for tkn in _exclude_tokens {
-        if token == &tkn {
+        if token == tkn {
             Err(Error::new(ErrorKind::SyntaxError, "Expected expr"))?
         }
     }
 
     match token {
         Token::String(s) => Ok(Expr::String(s.to_string())),
+        Token::Literal(s) => Ok(Expr::Literal(s.to_string())),
         Token::Ident(s) => Ok(Expr::Ident(s.to_string())),
+        Token::KeyIdent(k, v) => Ok(Expr::KeyIdent(k.to_string(), v.to_string())),
         Token::Number(n) => Ok(Expr::Number(*n)),
+        Token::Float(n) => Ok(Expr::Float(*n)),
         Token::FD(n) => Ok(Expr::FD(*n)),
-        _ => Err(Error::new(ErrorKind::SyntaxError, "Expected expr"))?,
+        _ => Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected expr, found '{token}'"),
+        ))?,
     }
 }
 
 /// Parse an assign token.
 pub fn parse_assign(tokens: &[Token; 3]) -> Result<Assign> {
     let ident = match &tokens[0] {
-        Token::Ident(s) => Expr::Ident(s.to_owned()),
+        Token::Ident(s) => Expr::Ident(s.to_string()),
         _ => Err(Error::new(ErrorKind::SyntaxError, "Expected ident"))?,
     };
 
@@ -65,7 +72,7 @@ pub fn parse_assign(tokens: &[Token; 3]) -> Result<Assign> {
         return Err(Error::new(ErrorKind::SyntaxError, "Expected assign"));
     }
 
-    let expr = parse_expr(&tokens[2], vec![Token::Ident(String::default())])?;
+    let expr = parse_expr(&tokens[2], &[Token::Ident("".into())])?;
 
     Ok(Assign { ident, expr })
 }
@@ -81,7 +88,7 @@ fn parse_abbreviated_redirect(tokens: &[Token; 2]) -> Result<Redirect> {
         ))?,
     };
 
-    let expr = parse_expr(&tokens[1], Vec::default())?;
+    let expr = parse_expr(&tokens[1], &[])?;
 
     Ok(Redirect {
         left: op.0,
@@ -102,7 +109,7 @@ fn parse_normal_redirect(tokens: &[Token; 3]) -> Result<Redirect> {
             "Expected redirect operator",
         ))?,
     };
-    let right = parse_expr(&tokens[2], Vec::default())?;
+    let right = parse_expr(&tokens[2], &[])?;
 
     Ok(Redirect {
         left,
@@ -124,7 +131,14 @@ pub fn parse_redirect(tokens: &[Token]) -> Result<Redirect> {
 
 /// Parse a command expression.
 fn parse_command_expr(token: &Token) -> Result<Expr> {
-    parse_expr(token, vec![Token::FD(0)])
+    if let Token::Keyword(keyword) = token {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Unexpected keyword '{keyword}', quote it to run it as a command"),
+        ))?
+    }
+
+    parse_expr(token, &[Token::FD(0)])
 }
 
 /// Parse command arguments and redirects.
@@ -140,7 +154,7 @@ fn parse_command_args_and_redirects(tokens: &[Token]) -> Result<(Vec<Expr>, Vec<
             continue;
         }
 
-        match tokens[i] {
+        match &tokens[i] {
             Token::Gt | Token::Lt => {
                 if i + 1 < tokens.len() {
                     let redirect = parse_redirect(&tokens[i..i + 2])?;
@@ -149,10 +163,21 @@ fn parse_command_args_and_redirects(tokens: &[Token]) -> Result<(Vec<Expr>, Vec<
 
                     skip_count = 1;
                 } else {
-                    Err(Error::DUMMY)?;
+                    Err(Error::new(
+                        ErrorKind::SyntaxError,
+                        &format!("Expected redirect target after '{}'", tokens[i]),
+                    ))?;
                 }
             }
 
+            Token::HereDoc(body) => {
+                redirects.push(Redirect {
+                    left: Expr::FD(0),
+                    right: Expr::String(body.to_string()),
+                    operator: RedirectOperator::HereDoc,
+                });
+            }
+
             Token::FD(_) => {
                 if i + 2 < tokens.len() {
                     let redirect = parse_redirect(&tokens[i..i + 3])?;
@@ -161,12 +186,15 @@ fn parse_command_args_and_redirects(tokens: &[Token]) -> Result<(Vec<Expr>, Vec<
 
                     skip_count = 2;
                 } else {
-                    Err(Error::DUMMY)?;
+                    Err(Error::new(
+                        ErrorKind::SyntaxError,
+                        "Expected redirect operator and target after file descriptor",
+                    ))?;
                 }
             }
 
             _ => {
-                let arg = parse_expr(&tokens[i], vec![Token::Gt, Token::Lt, Token::FD(0)])?;
+                let arg = parse_expr(&tokens[i], &[Token::Gt, Token::Lt, Token::FD(0)])?;
                 args.push(arg);
             }
         }
@@ -177,45 +205,60 @@ fn parse_command_args_and_redirects(tokens: &[Token]) -> Result<(Vec<Expr>, Vec<
 
 /// Parse a command.
 pub fn parse_command(tokens: &[Token]) -> Result<Command> {
+    if tokens.is_empty() {
+        Err(Error::new(ErrorKind::SyntaxError, "Expected command"))?
+    }
+
     let expr = parse_command_expr(&tokens[0])?;
 
-    let (tokens, background) = if tokens[1..].contains(&Token::Ampersand) {
-        let (left, _) = split(&Token::Ampersand, &tokens[1..]);
-        (left, true)
-    } else {
-        (tokens[1..].to_vec(), false)
+    let (tokens, background) = match tokens[1..].iter().position(|token| *token == Token::Ampersand) {
+        // the statements are split after `&`, a token after it would be lost.
+        Some(i) if i + 2 < tokens.len() => Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected ';' after '&', found '{}'", tokens[i + 2]),
+        ))?,
+        Some(i) => (&tokens[1..i + 1], true),
+        None => (&tokens[1..], false),
     };
 
-    let (args, redirects) = parse_command_args_and_redirects(&tokens)?;
+    let (args, redirects) = parse_command_args_and_redirects(tokens)?;
 
     Ok(Command {
         expr,
         args,
         redirects,
         background,
+        pipe_stderr: false,
     })
 }
 
 /// Parse a pipe.
+///
+/// A command followed by `|&` pipes its stderr with its stdout.
 pub fn parse_pipe(tokens: &[Token]) -> Result<Pipe> {
-    if tokens.len() == 0 {
-        Err(Error::DUMMY)?
+    if tokens.is_empty() {
+        Err(Error::new(ErrorKind::SyntaxError, "Expected pipe"))?
     }
 
-    if tokens.len() == 1 {
-        if tokens[0] == Token::Pipe {
-            Err(Error::DUMMY)?
-        }
+    if tokens.len() == 1 && matches!(tokens[0], Token::Pipe | Token::PipeStderr) {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected command before '{}'", tokens[0]),
+        ))?
     }
 
     let mut pipe = Pipe::new();
 
-    let command_tokens = recursion_split(&Token::Pipe, tokens);
+    for tokens in recursion_split(&Token::Pipe, tokens) {
+        let commands = recursion_split(&Token::PipeStderr, &tokens);
+
+        for (i, command) in commands.iter().enumerate() {
+            let mut command = parse_command(command)?;
 
-    for command in command_tokens {
-        let command = parse_command(&command)?;
+            command.pipe_stderr = i + 1 < commands.len();
 
-        pipe.push_back(command);
+            pipe.push_back(command);
+        }
     }
 
     Ok(pipe)
@@ -227,10 +270,11 @@ mod tests {
     use std::collections::VecDeque;
 
     use super::*;
+    use crate::token::Keyword;
 
     #[test]
     fn test_parse_string() {
-        let token = Token::String("hello".to_string());
+        let token = Token::String("hello".into());
 
         let expr = parse_string(&token).unwrap();
 
@@ -239,7 +283,7 @@ mod tests {
 
     #[test]
     fn test_parse_ident() {
-        let token = Token::Ident("hello".to_string());
+        let token = Token::Ident("hello".into());
 
         let expr = parse_ident(&token).unwrap();
 
@@ -267,9 +311,9 @@ mod tests {
     #[test]
     fn test_parse_assign() {
         let tokens = [
-            Token::Ident("a".to_string()),
+            Token::Ident("a".into()),
             Token::Assign,
-            Token::String("hello".to_string()),
+            Token::String("hello".into()),
         ];
 
         let assign = parse_assign(&tokens).unwrap();
@@ -281,10 +325,10 @@ mod tests {
     #[test]
     fn test_parse_abbreviated_redirect() {
         let redirect1 =
-            parse_abbreviated_redirect(&[Token::Gt, Token::String("hello".to_string())]).unwrap();
+            parse_abbreviated_redirect(&[Token::Gt, Token::String("hello".into())]).unwrap();
 
         let redirect2 =
-            parse_abbreviated_redirect(&[Token::Lt, Token::String("hello".to_string())]).unwrap();
+            parse_abbreviated_redirect(&[Token::Lt, Token::String("hello".into())]).unwrap();
 
         assert_eq!(redirect1.left, Expr::FD(1));
         assert_eq!(redirect1.right, Expr::String("hello".to_string()));
@@ -298,11 +342,11 @@ mod tests {
     #[test]
     fn test_parse_normal_redirect() {
         let redirect1 =
-            parse_normal_redirect(&[Token::FD(1), Token::Gt, Token::String("hello".to_string())])
+            parse_normal_redirect(&[Token::FD(1), Token::Gt, Token::String("hello".into())])
                 .unwrap();
 
         let redirect2 =
-            parse_normal_redirect(&[Token::FD(0), Token::Lt, Token::String("hello".to_string())])
+            parse_normal_redirect(&[Token::FD(0), Token::Lt, Token::String("hello".into())])
                 .unwrap();
 
         assert_eq!(redirect1.left, Expr::FD(1));
@@ -316,10 +360,10 @@ mod tests {
 
     #[test]
     fn test_parse_redirect() {
-        let redirect1 = parse_redirect(&[Token::Gt, Token::String("hello".to_string())]).unwrap();
+        let redirect1 = parse_redirect(&[Token::Gt, Token::String("hello".into())]).unwrap();
 
         let redirect2 =
-            parse_redirect(&[Token::FD(1), Token::Gt, Token::String("hello".to_string())]).unwrap();
+            parse_redirect(&[Token::FD(1), Token::Gt, Token::String("hello".into())]).unwrap();
 
         assert_eq!(redirect1.left, Expr::FD(1));
         assert_eq!(redirect1.right, Expr::String("hello".to_string()));
@@ -333,11 +377,11 @@ mod tests {
     #[test]
     fn test_parse_command_expr() {
         assert_eq!(
-            parse_command_expr(&Token::String("hello".to_string())).unwrap(),
+            parse_command_expr(&Token::String("hello".into())).unwrap(),
             Expr::String("hello".to_string())
         );
         assert_eq!(
-            parse_command_expr(&Token::Ident("hello".to_string())).unwrap(),
+            parse_command_expr(&Token::Ident("hello".into())).unwrap(),
             Expr::Ident("hello".to_string())
         );
         assert_eq!(
@@ -349,15 +393,16 @@ mod tests {
     #[test]
     fn test_parse_command_expr_error() {
         assert!(parse_command_expr(&Token::FD(0)).is_err());
+        assert!(parse_command_expr(&Token::Keyword(Keyword::If)).is_err());
     }
 
     #[test]
     fn test_parse_command_args_and_redirects() {
         let tokens = [
-            Token::String("hello".to_string()),
-            Token::String("world".to_string()),
+            Token::String("hello".into()),
+            Token::String("world".into()),
             Token::Gt,
-            Token::String("file".to_string()),
+            Token::String("file".into()),
         ];
 
         let (args, redirects) = parse_command_args_and_redirects(&tokens).unwrap();
@@ -374,23 +419,44 @@ mod tests {
         assert_eq!(redirects[0].operator, RedirectOperator::Gt);
     }
 
+    #[test]
+    fn test_parse_command_args_and_here_document() {
+        let tokens = [
+            Token::String("-n".into()),
+            Token::HereDoc("hello\n".into()),
+        ];
+
+        let (args, redirects) = parse_command_args_and_redirects(&tokens).unwrap();
+
+        assert_eq!(args, vec![Expr::String("-n".to_string())]);
+
+        assert_eq!(
+            redirects,
+            vec![Redirect {
+                left: Expr::FD(0),
+                right: Expr::String("hello\n".to_string()),
+                operator: RedirectOperator::HereDoc
+            }]
+        );
+    }
+
     #[test]
     fn test_parse_command() {
         let command1 = parse_command(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
-            Token::String("world".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
+            Token::String("world".into()),
         ]);
 
         let command2 = parse_command(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
-            Token::String("world".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
+            Token::String("world".into()),
             Token::Gt,
-            Token::String("file".to_string()),
+            Token::String("file".into()),
             Token::FD(2),
             Token::Gt,
-            Token::String("file2".to_string()),
+            Token::String("file2".into()),
         ]);
 
         assert_eq!(
@@ -402,7 +468,8 @@ mod tests {
                     Expr::String("world".to_string())
                 ],
                 redirects: Vec::default(),
-                background: false
+                background: false,
+                pipe_stderr: false
             }
         );
 
@@ -426,7 +493,8 @@ mod tests {
                         operator: RedirectOperator::Gt
                     }
                 ],
-                background: false
+                background: false,
+                pipe_stderr: false
             }
         );
     }
@@ -434,21 +502,21 @@ mod tests {
     #[test]
     fn test_parse_command_background() {
         let command1 = parse_command(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
-            Token::String("world".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
+            Token::String("world".into()),
             Token::Ampersand,
         ]);
 
         let command2 = parse_command(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
-            Token::String("world".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
+            Token::String("world".into()),
             Token::Gt,
-            Token::String("file".to_string()),
+            Token::String("file".into()),
             Token::FD(2),
             Token::Gt,
-            Token::String("file2".to_string()),
+            Token::String("file2".into()),
             Token::Ampersand,
         ]);
 
@@ -461,7 +529,8 @@ mod tests {
                     Expr::String("world".to_string())
                 ],
                 redirects: Vec::default(),
-                background: true
+                background: true,
+                pipe_stderr: false
             }
         );
 
@@ -485,7 +554,8 @@ mod tests {
                         operator: RedirectOperator::Gt
                     }
                 ],
-                background: true
+                background: true,
+                pipe_stderr: false
             }
         );
     }
@@ -493,26 +563,26 @@ mod tests {
     #[test]
     fn test_parse_pipe() {
         let pipe1 = parse_pipe(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
             Token::Pipe,
-            Token::String("cat".to_string()),
-            Token::String("-b".to_string()),
+            Token::String("cat".into()),
+            Token::String("-b".into()),
         ]);
 
         let pipe2 = parse_pipe(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
             Token::Pipe,
-            Token::String("cat".to_string()),
-            Token::String("-b".to_string()),
+            Token::String("cat".into()),
+            Token::String("-b".into()),
             Token::Pipe,
-            Token::String("rev".to_string()),
+            Token::String("rev".into()),
         ]);
 
         let pipe3 = parse_pipe(&[
-            Token::String("echo".to_string()),
-            Token::String("hello".to_string()),
+            Token::String("echo".into()),
+            Token::String("hello".into()),
             Token::Pipe,
         ]);
 
@@ -524,13 +594,15 @@ mod tests {
                         expr: Expr::String("echo".to_string()),
                         args: vec![Expr::String("hello".to_string())],
                         redirects: Vec::default(),
-                        background: false
+                        background: false,
+                        pipe_stderr: false
                     },
                     Command {
                         expr: Expr::String("cat".to_string()),
                         args: vec![Expr::String("-b".to_string())],
                         redirects: Vec::default(),
-                        background: false
+                        background: false,
+                        pipe_stderr: false
                     }
                 ]
                 .iter()
@@ -547,19 +619,22 @@ mod tests {
                         expr: Expr::String("echo".to_string()),
                         args: vec![Expr::String("hello".to_string())],
                         redirects: Vec::default(),
-                        background: false
+                        background: false,
+                        pipe_stderr: false
                     },
                     Command {
                         expr: Expr::String("cat".to_string()),
                         args: vec![Expr::String("-b".to_string())],
                         redirects: Vec::default(),
-                        background: false
+                        background: false,
+                        pipe_stderr: false
                     },
                     Command {
                         expr: Expr::String("rev".to_string()),
                         args: Vec::default(),
                         redirects: Vec::default(),
-                        background: false
+                        background: false,
+                        pipe_stderr: false
                     }
                 ]
                 .iter()
@@ -575,7 +650,8 @@ mod tests {
                     expr: Expr::String("echo".to_string()),
                     args: vec![Expr::String("hello".to_string())],
                     redirects: Vec::default(),
-                    background: false
+                    background: false,
+                    pipe_stderr: false
                 }]
                 .iter()
                 .cloned()
@@ -588,5 +664,38 @@ mod tests {
     fn test_parse_pipe_error() {
         assert!(parse_pipe(&[]).is_err());
         assert!(parse_pipe(&[Token::Pipe]).is_err());
+        assert!(parse_pipe(&[Token::Pipe, Token::String("cat".into())]).is_err());
+        assert!(parse_pipe(&[Token::PipeStderr]).is_err());
+    }
+
+    #[test]
+    fn test_parse_pipe_stderr() {
+        let pipe = parse_pipe(&[
+            Token::String("make".into()),
+            Token::PipeStderr,
+            Token::String("grep".into()),
+            Token::String("error".into()),
+            Token::Pipe,
+            Token::String("wc".into()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            pipe.commands()
+                .iter()
+                .map(|command| command.pipe_stderr)
+                .collect::<Vec<bool>>(),
+            [true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_parse_command_error() {
+        assert!(parse_command(&[]).is_err());
+
+        let err = parse_command(&[Token::String("echo".into()), Token::Gt]).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::SyntaxError);
+        assert_eq!(err.message(), "Expected redirect target after '>'");
     }
 }
\ No newline at end of file
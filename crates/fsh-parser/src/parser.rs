@@ -1,53 +1,1078 @@
+use super::diagnostic::{Diagnostic, Location};
 use super::lexer::Lexer;
 use super::lite_parser::{parse_assign, parse_command, parse_pipe};
-use super::token::Token;
-use super::utils::recursion_split;
+use super::token::{Keyword, Lexeme, Token, TokenKind};
 use fsh_ast::*;
 
-use fsh_common::Result;
+use fsh_common::{Error, ErrorKind, Result};
+use std::io::Read;
 
 #[derive(Debug)]
-pub struct Parser(Lexer, Ast);
+pub struct Parser<'a>(Lexer<'a>, Ast, Vec<Location>, Vec<Location>);
 
-impl Parser {
-    pub fn new(input: &str) -> Self {
-        Self(Lexer::new(input), Ast::new())
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self(Lexer::new(input), Ast::new(), Vec::new(), Vec::new())
     }
 
+    /// Create a parser reading the input from the reader.
+    pub fn from_reader(reader: impl Read + 'a) -> Self {
+        Self(Lexer::from_reader(reader), Ast::new(), Vec::new(), Vec::new())
+    }
+
+    /// Get the locations of the parsed statements.
+    ///
+    /// The locations are in the same order as the statements of the AST.
+    pub fn locations(&self) -> &[Location] {
+        &self.2
+    }
+
+    /// Get the locations of the items inside blocks: the arms, the statements and the closing braces.
+    ///
+    /// The locations are in order of the source, which is also the order of a depth-first walk of the AST.
+    pub fn block_locations(&self) -> &[Location] {
+        &self.3
+    }
+
+    /// Get the comments of the parsed input.
+    pub fn comments(&self) -> Vec<(Location, &str)> {
+        self.0.comments()
+    }
+
+    /// Check if the whole input has been parsed.
+    pub fn is_consumed(&mut self) -> bool {
+        self.0.is_consumed()
+    }
+
+    /// Parse the input.
+    ///
+    /// # Errors
+    /// The first error found in the input. to get all errors, use `parse_with_diagnostics`.
     pub fn parse(&mut self) -> Result<Ast> {
+        let (ast, diagnostics) = self.diagnose();
+
+        if let Some(diagnostic) = diagnostics.into_iter().next() {
+            Err(diagnostic.into_error())?
+        }
+
+        Ok(ast)
+    }
+
+    /// Parse the input, recovering from errors at statement boundaries.
+    ///
+    /// A statement with an error is left out of the AST, and parsing resumes at the next statement.
+    ///
+    /// The input is a whole script, no more lines follow it, so an incomplete input is a syntax error.
+    ///
+    /// # Returns
+    /// The partial AST and the diagnostics of all errors, in order of location.
+    pub fn parse_with_diagnostics(&mut self) -> (Ast, Vec<Diagnostic>) {
+        let (ast, diagnostics) = self.diagnose();
+
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| match diagnostic.error().kind() {
+                ErrorKind::Incomplete => Diagnostic::new(
+                    *diagnostic.location(),
+                    Error::new(ErrorKind::SyntaxError, diagnostic.error().message()),
+                ),
+                _ => diagnostic,
+            })
+            .collect();
+
+        (ast, diagnostics)
+    }
+
+    /// Parse the input and get the diagnostics of all errors, an incomplete input is reported as `Incomplete`.
+    ///
+    /// The input is parsed a statement at a time, the lexemes of a statement are converted to tokens when it ends,
+    /// and the input before it is discarded.
+    fn diagnose(&mut self) -> (Ast, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+
+        let mut statement = PendingStatement::default();
+
+        // the pipe ending the statements before, a pipe at the end of the input is not followed by a command.
+        let mut pipe = None;
+
+        let mut last = None;
+
+        loop {
+            let (lexeme, location) = match self.0.next_located(last) {
+                Ok(located) => located,
+                Err(diagnostic) => {
+                    statement.drop_to_semicolon();
+
+                    last = Some(TokenKind::Semicolon);
+
+                    diagnostics.push(diagnostic);
+
+                    continue;
+                }
+            };
+
+            last = Some(lexeme.kind);
+
+            if lexeme.kind == TokenKind::EOF {
+                break;
+            }
+
+            if statement.is_ended_by(&lexeme) {
+                pipe = statement.pipe(pipe);
+
+                self.parse_pending(&mut statement, &mut diagnostics);
 
-        let mut tokens = self.0.tokenize()?;
+                self.0.discard(lexeme.span.start);
+            }
+
+            if lexeme.kind == TokenKind::RBrace && statement.braces.is_empty() {
+                diagnostics.push(Diagnostic::new(
+                    location,
+                    Error::new(ErrorKind::SyntaxError, "unexpected '}'"),
+                ));
+            }
+
+            statement.push(lexeme, location);
+        }
+
+        if let Some(location) = statement.pipe(pipe) {
+            diagnostics.push(Diagnostic::new(
+                location,
+                Error::new(ErrorKind::Incomplete, "pipe is not followed by a command"),
+            ));
+        }
 
-        if tokens.pop() != Some(Token::EOF){
-            //
+        for location in &statement.braces {
+            diagnostics.push(Diagnostic::new(
+                *location,
+                Error::new(ErrorKind::Incomplete, "brace is not closed"),
+            ));
         }
 
-        let entries = recursion_split(&Token::Semicolon, &tokens);
+        self.parse_pending(&mut statement, &mut diagnostics);
+
+        diagnostics.sort_by_key(|diagnostic| *diagnostic.location());
 
-        for tokens in entries {
-            if tokens.is_empty() {
-                continue;
+        (std::mem::take(&mut self.1), diagnostics)
+    }
+
+    /// Parse the pending statement and clear it.
+    fn parse_pending(&mut self, statement: &mut PendingStatement, diagnostics: &mut Vec<Diagnostic>) {
+        let lexemes = &statement.lexemes[..statement.lexemes.len() - statement.semicolons];
+
+        if let Some((_, location)) = lexemes.first() {
+            let tokens = lexemes
+                .iter()
+                .map(|(lexeme, location)| (self.0.token(lexeme), *location))
+                .collect::<Vec<_>>();
+
+            let mut block_locations = Vec::new();
+
+            match parse_statement(&tokens, &mut block_locations) {
+                Ok(ast) => {
+                    self.1.push_back(ast);
+                    self.2.push(*location);
+                    self.3.append(&mut block_locations);
+                }
+                Err(err) => diagnostics.push(Diagnostic::new(*location, err)),
             }
+        }
+
+        *statement = PendingStatement::default();
+    }
+}
+
+/// The lexemes of the statement being read, a statement ends at a semicolon outside of braces.
+#[derive(Debug, Default)]
+struct PendingStatement {
+    lexemes: Vec<(Lexeme, Location)>,
+    /// The braces that are not closed yet.
+    braces: Vec<Location>,
+    /// The number of semicolons at the end, outside of braces.
+    semicolons: usize,
+    /// If the statement is a background job, `&` outside of braces and the semicolons after it end it.
+    is_background: bool,
+}
+
+impl PendingStatement {
+    fn push(&mut self, lexeme: Lexeme, location: Location) {
+        match lexeme.kind {
+            TokenKind::LBrace => self.braces.push(location),
+            TokenKind::RBrace => {
+                self.braces.pop();
+            }
+            _ => {}
+        }
+
+        match lexeme.kind {
+            TokenKind::Semicolon if self.braces.is_empty() => self.semicolons += 1,
+            kind => {
+                self.semicolons = 0;
+                self.is_background = kind == TokenKind::Ampersand && self.braces.is_empty();
+            }
+        }
+
+        self.lexemes.push((lexeme, location));
+    }
+
+    /// Check if the statement ends before the lexeme, after `;` or `&`.
+    ///
+    /// A block followed by `catch` or `finally` on the next line continues the statement.
+    fn is_ended_by(&self, lexeme: &Lexeme) -> bool {
+        (self.semicolons > 0 || self.is_background)
+            && !matches!(
+                lexeme.kind,
+                TokenKind::Semicolon | TokenKind::Keyword(Keyword::Catch | Keyword::Finally)
+            )
+    }
+
+    /// Get the location of the pipe at the end of the input read so far, if any.
+    ///
+    /// `pipe` is the one ending the statements before, if the statement has nothing but semicolons.
+    fn pipe(&self, pipe: Option<Location>) -> Option<Location> {
+        match self
+            .lexemes
+            .iter()
+            .rev()
+            .find(|(lexeme, _)| lexeme.kind != TokenKind::Semicolon)
+        {
+            Some((lexeme, location)) => {
+                matches!(lexeme.kind, TokenKind::Pipe | TokenKind::PipeStderr).then_some(*location)
+            }
+            None => pipe,
+        }
+    }
+
+    /// Drop the lexemes after the last semicolon, the statement where an error occurred.
+    fn drop_to_semicolon(&mut self) {
+        while let Some((lexeme, _)) = self.lexemes.last() {
+            if lexeme.kind == TokenKind::Semicolon {
+                break;
+            }
+
+            self.lexemes.pop();
+        }
+
+        let lexemes = std::mem::take(&mut self.lexemes);
+
+        *self = Self::default();
+
+        for (lexeme, location) in lexemes {
+            self.push(lexeme, location);
+        }
+    }
+}
+
+/// Split the tokens into statements at the semicolons outside of braces, and after `&`.
+///
+/// A block followed by `catch` or `finally` on the next line continues the statement.
+fn split_statements<'t, 'a>(tokens: &'t [(Token<'a>, Location)]) -> Vec<&'t [(Token<'a>, Location)]> {
+    let mut statements = Vec::new();
+
+    let mut depth = 0usize;
+
+    let mut start = 0;
+
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::LBrace => depth += 1,
+            Token::RBrace => depth = depth.saturating_sub(1),
+            Token::Semicolon if depth == 0 && is_continued(&tokens[i..]) => {}
+            Token::Semicolon if depth == 0 => {
+                if start < i {
+                    statements.push(&tokens[start..i]);
+                }
+
+                start = i + 1;
+            }
+            // `&` is kept, the statement is a background job.
+            Token::Ampersand if depth == 0 => {
+                statements.push(&tokens[start..=i]);
+
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if start < tokens.len() {
+        statements.push(&tokens[start..]);
+    }
+
+    statements
+}
+
+/// Escape the glob characters, so that the pattern only matches the string.
+fn escape_glob(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '*' | '?' | '[' | ']' => format!("[{c}]"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Check if the statement continues after the semicolons, with `catch` or `finally`.
+fn is_continued(tokens: &[(Token, Location)]) -> bool {
+    matches!(
+        tokens.iter().find(|(token, _)| *token != Token::Semicolon),
+        Some((Token::Keyword(Keyword::Catch | Keyword::Finally), _))
+    )
+}
+
+/// Find the index of the brace closing the brace at the index.
+fn closing_brace(tokens: &[(Token, Location)], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (i, (token, _)) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LBrace => depth += 1,
+            Token::RBrace => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parse a statement, that is, the tokens between semicolons.
+///
+/// The locations of the items inside its blocks are pushed to `block_locations`.
+fn parse_statement(
+    tokens: &[(Token, Location)],
+    block_locations: &mut Vec<Location>,
+) -> Result<Ast> {
+    if let Some((Token::Bang, _)) = tokens.first() {
+        if tokens.len() == 1 {
+            Err(Error::new(
+                ErrorKind::SyntaxError,
+                "Expected pipeline after '!'",
+            ))?
+        }
+
+        return match parse_statement(&tokens[1..], block_locations)? {
+            ast @ (Ast::Pipe(_) | Ast::Statement(Statement::Command(_)) | Ast::Negated(_)) => {
+                Ok(Ast::Negated(Box::new(ast)))
+            }
+            _ => Err(Error::new(
+                ErrorKind::SyntaxError,
+                "Only a pipeline can be negated with '!'",
+            )),
+        };
+    }
+
+    if let Some((Token::Keyword(Keyword::Import), _)) = tokens.first() {
+        return Ok(Ast::Statement(Statement::Import(parse_import(tokens)?)));
+    }
+
+    if let Some((Token::Keyword(Keyword::Try), _)) = tokens.first() {
+        return Ok(Ast::Statement(Statement::Try(parse_try(
+            tokens,
+            block_locations,
+        )?)));
+    }
+
+    if let Some((Token::Keyword(Keyword::Match), _)) = tokens.first() {
+        return Ok(Ast::Statement(Statement::Match(parse_match(
+            tokens,
+            block_locations,
+        )?)));
+    }
+
+    let tokens = tokens
+        .iter()
+        .map(|(token, _)| token.to_owned())
+        .collect::<Vec<Token>>();
+
+    if tokens.contains(&Token::Pipe) || tokens.contains(&Token::PipeStderr) {
+        return Ok(Ast::Pipe(parse_pipe(&tokens)?));
+    }
+
+    if tokens.contains(&Token::Assign) && tokens.len() == 3 {
+        return Ok(Ast::Statement(Statement::Assign(parse_assign(
+            tokens.as_slice().try_into().unwrap(),
+        )?)));
+    }
+
+    Ok(Ast::Statement(Statement::Command(parse_command(&tokens)?)))
+}
+
+/// Parse a block, the statements between braces.
+fn parse_block(tokens: &[(Token, Location)], block_locations: &mut Vec<Location>) -> Result<Ast> {
+    let mut block = Ast::new();
+
+    for statement in split_statements(tokens) {
+        block_locations.push(statement[0].1);
+
+        block.push_back(parse_statement(statement, block_locations)?);
+    }
+
+    Ok(block)
+}
+
+/// Parse an import statement, `import PATH`.
+fn parse_import(tokens: &[(Token, Location)]) -> Result<Import> {
+    let path = match tokens {
+        [_] => Err(Error::new(
+            ErrorKind::SyntaxError,
+            "Expected path after 'import'",
+        ))?,
+        [_, (Token::String(s), _)] => Expr::String(s.to_string()),
+        [_, (Token::Literal(s), _)] => Expr::Literal(s.to_string()),
+        [_, (Token::Ident(s), _)] => Expr::Ident(s.to_string()),
+        [_, (token, _)] => Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected path after 'import', found '{token}'"),
+        ))?,
+        _ => Err(Error::new(
+            ErrorKind::SyntaxError,
+            "Expected ';' after the import path",
+        ))?,
+    };
+
+    Ok(Import { path })
+}
+
+/// Parse the block starting at the index, `{ STATEMENTS }` or the empty block `{}`.
+///
+/// # Returns
+/// The block and the index of its closing brace.
+fn parse_braced_block(
+    tokens: &[(Token, Location)],
+    start: usize,
+    after: &str,
+    block_locations: &mut Vec<Location>,
+) -> Result<(Ast, usize)> {
+    match tokens.get(start) {
+        Some((Token::String(s), location)) if s == "{}" => {
+            block_locations.push(*location);
+
+            Ok((Ast::new(), start))
+        }
+        Some((Token::LBrace, _)) => {
+            let end = closing_brace(tokens, start)
+                .ok_or_else(|| Error::new(ErrorKind::Incomplete, "brace is not closed"))?;
+
+            let block = parse_block(&tokens[start + 1..end], block_locations)?;
+
+            block_locations.push(tokens[end].1);
+
+            Ok((block, end))
+        }
+        Some((token, _)) => Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected '{{' after {after}, found '{token}'"),
+        )),
+        None => Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected '{{' after {after}"),
+        )),
+    }
+}
+
+/// Parse a try statement, `try { STATEMENTS } catch $IDENT { STATEMENTS } finally { STATEMENTS }`.
+fn parse_try(tokens: &[(Token, Location)], block_locations: &mut Vec<Location>) -> Result<Try> {
+    let (body, end) = parse_braced_block(tokens, 1, "'try'", block_locations)?;
+
+    let mut i = end + 1;
+
+    let skip_semicolons = |mut i: usize| {
+        while tokens.get(i).map(|(token, _)| token) == Some(&Token::Semicolon) {
+            i += 1;
+        }
+
+        i
+    };
+
+    let mut catch = None;
 
-            if tokens.contains(&Token::Pipe) {
-                self.1.push_back(Ast::Pipe(parse_pipe(&tokens)?));
+    if let Some((Token::Keyword(Keyword::Catch), _)) = tokens.get(skip_semicolons(i)) {
+        i = skip_semicolons(i) + 1;
 
-                continue;
+        let ident = match tokens.get(i) {
+            Some((Token::Ident(s), _)) => {
+                i += 1;
+
+                Some(Expr::Ident(s.to_string()))
             }
+            _ => None,
+        };
+
+        let (body, end) = parse_braced_block(tokens, i, "'catch'", block_locations)?;
+
+        i = end + 1;
+
+        catch = Some(Catch {
+            ident,
+            body: Box::new(body),
+        });
+    }
+
+    let mut finally = None;
+
+    if let Some((Token::Keyword(Keyword::Finally), _)) = tokens.get(skip_semicolons(i)) {
+        i = skip_semicolons(i) + 1;
+
+        let (body, end) = parse_braced_block(tokens, i, "'finally'", block_locations)?;
+
+        i = end + 1;
+
+        finally = Some(Box::new(body));
+    }
+
+    if catch.is_none() && finally.is_none() {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            "Expected 'catch' or 'finally' after the try block",
+        ))?
+    }
+
+    if let Some((token, _)) = tokens.get(i) {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected ';' after '}}', found '{token}'"),
+        ))?
+    }
+
+    Ok(Try {
+        body: Box::new(body),
+        catch,
+        finally,
+    })
+}
+
+/// Parse a match statement, `match EXPR { PATTERN | PATTERN => { STATEMENTS } ... }`.
+fn parse_match(tokens: &[(Token, Location)], block_locations: &mut Vec<Location>) -> Result<Match> {
+    let expr = match tokens.get(1) {
+        Some((Token::String(s), _)) => Expr::String(s.to_string()),
+        Some((Token::Literal(s), _)) => Expr::Literal(s.to_string()),
+        Some((Token::Ident(s), _)) => Expr::Ident(s.to_string()),
+        Some((Token::Number(n), _)) => Expr::Number(*n),
+        Some((Token::Float(n), _)) => Expr::Float(*n),
+        _ => Err(Error::new(
+            ErrorKind::SyntaxError,
+            "Expected expr after 'match'",
+        ))?,
+    };
+
+    if tokens.get(2).map(|(token, _)| token) != Some(&Token::LBrace) {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            "Expected '{' after the match expr",
+        ))?
+    }
+
+    let close = closing_brace(tokens, 2)
+        .ok_or_else(|| Error::new(ErrorKind::Incomplete, "brace is not closed"))?;
+
+    if close + 1 < tokens.len() {
+        Err(Error::new(
+            ErrorKind::SyntaxError,
+            &format!("Expected ';' after '}}', found '{}'", tokens[close + 1].0),
+        ))?
+    }
+
+    let mut arms = Vec::new();
 
-            if tokens.contains(&Token::Assign) && tokens.len() == 3 {
-                self.1
-                    .push_back(Ast::Statement(Statement::Assign(parse_assign(
-                        &tokens.try_into().unwrap(),
-                    )?)));
+    let mut i = 3;
 
-                continue;
+    loop {
+        while tokens[i].0 == Token::Semicolon {
+            i += 1;
+        }
+
+        if i == close {
+            break;
+        }
+
+        block_locations.push(tokens[i].1);
+
+        let mut patterns = Vec::new();
+
+        loop {
+            let pattern = match &tokens[i].0 {
+                Token::String(s) if s == "_" => Pattern::Wildcard,
+                Token::String(s) if s == "~" => {
+                    i += 1;
+
+                    match &tokens[i].0 {
+                        Token::String(regex) | Token::Literal(regex) => {
+                            Pattern::Regex(regex.to_string())
+                        }
+                        token => Err(Error::new(
+                            ErrorKind::SyntaxError,
+                            &format!("Expected regex after '~', found '{token}'"),
+                        ))?,
+                    }
+                }
+                Token::String(s) => Pattern::Glob(s.to_string()),
+                Token::Literal(s) => Pattern::Glob(escape_glob(s)),
+                Token::Number(n) => Pattern::Glob(n.to_string()),
+                Token::Float(n) => Pattern::Glob(format!("{n:?}")),
+                Token::Keyword(keyword) => Pattern::Glob(keyword.as_str().to_string()),
+                token => Err(Error::new(
+                    ErrorKind::SyntaxError,
+                    &format!("Expected pattern, found '{token}'"),
+                ))?,
+            };
+
+            patterns.push(pattern);
+
+            i += 1;
+
+            if tokens[i].0 != Token::Pipe {
+                break;
             }
 
-            self.1
-                .push_back(Ast::Statement(Statement::Command(parse_command(&tokens)?)));
+            i += 1;
+        }
+
+        if (&tokens[i].0, tokens.get(i + 1).map(|(token, _)| token))
+            != (&Token::Assign, Some(&Token::Gt))
+        {
+            Err(Error::new(
+                ErrorKind::SyntaxError,
+                &format!("Expected '=>' after pattern, found '{}'", tokens[i].0),
+            ))?
         }
 
-        Ok(self.1.to_owned())
+        let (body, end) = parse_braced_block(tokens, i + 2, "'=>'", block_locations)?;
+
+        i = end + 1;
+
+        arms.push(MatchArm { patterns, body });
+    }
+
+    block_locations.push(tokens[close].1);
+
+    Ok(Match { expr, arms })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_incomplete(input: &str) -> bool {
+        matches!(Parser::new(input).parse(), Err(err) if err.kind() == &ErrorKind::Incomplete)
+    }
+
+    #[test]
+    fn test_parse_incomplete_quote() {
+        assert!(is_incomplete("echo \"hello"));
+        assert!(is_incomplete("echo 'hello"));
+        assert!(!is_incomplete("echo \"hello\nworld\""));
+    }
+
+    #[test]
+    fn test_parse_incomplete_pipe() {
+        assert!(is_incomplete("echo hello |"));
+        assert!(is_incomplete("echo hello |\n"));
+        assert!(!is_incomplete("echo hello |\ncat"));
+    }
+
+    #[test]
+    fn test_parse_incomplete_brace() {
+        assert!(is_incomplete("{"));
+        assert!(is_incomplete("{ echo hello"));
+
+        let err = Parser::new("}").parse().unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::SyntaxError);
+    }
+
+    #[test]
+    fn test_parse_incomplete_here_document() {
+        assert!(is_incomplete("cat <<EOF"));
+        assert!(is_incomplete("cat <<EOF\nhello"));
+        assert!(!is_incomplete("cat <<EOF\nhello\nEOF"));
+    }
+
+    #[test]
+    fn test_parse_incomplete_line_continuation() {
+        assert!(is_incomplete("echo hello \\"));
+        assert!(!is_incomplete("echo hello \\\nworld"));
+    }
+
+    #[test]
+    fn test_parse_with_diagnostics_incomplete() {
+        // a script has no following lines, an unterminated quote is a syntax error.
+        let (_, diagnostics) = Parser::new("echo a\necho \"b").parse_with_diagnostics();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location(), &Location::new(2, 6));
+        assert_eq!(diagnostics[0].error().kind(), &ErrorKind::SyntaxError);
+
+        let (_, diagnostics) = Parser::new("echo a |\n").parse_with_diagnostics();
+
+        assert_eq!(diagnostics[0].error().kind(), &ErrorKind::SyntaxError);
+    }
+
+    #[test]
+    fn test_parse_from_reader() {
+        let script = "echo a; try {\n  echo b\n}\ncatch { echo c }\n".repeat(10_000) + "echo \"d";
+
+        let mut parser = Parser::from_reader(script.as_bytes());
+
+        // the input is read in chunks, and discarded as the statements are parsed.
+        let (_, diagnostics) = parser.parse_with_diagnostics();
+
+        assert_eq!(parser.locations().len(), 20_000);
+        assert_eq!(parser.locations()[19_999], Location::new(39_997, 9));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location(), &Location::new(40_001, 6));
+    }
+
+    #[test]
+    fn test_parse_with_diagnostics() {
+        let (ast, diagnostics) =
+            Parser::new("echo a >\necho b\n| cat\necho $1a\necho c @1").parse_with_diagnostics();
+
+        assert_eq!(
+            ast,
+            Ast::Semicolon(
+                [Ast::Statement(Statement::Command(Command {
+                    expr: Expr::String("echo".to_string()),
+                    args: vec![Expr::String("b".to_string())],
+                    redirects: Vec::default(),
+                    background: false,
+                    pipe_stderr: false
+                }))]
+                .into()
+            )
+        );
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (*diagnostic.location(), diagnostic.error().kind()))
+                .collect::<Vec<(Location, &ErrorKind)>>(),
+            vec![
+                (Location::new(1, 1), &ErrorKind::SyntaxError),
+                (Location::new(3, 1), &ErrorKind::SyntaxError),
+                (Location::new(4, 6), &ErrorKind::LexerError),
+                (Location::new(5, 1), &ErrorKind::SyntaxError),
+            ]
+        );
+
+        for diagnostic in diagnostics {
+            assert!(!diagnostic.error().message().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_locations() {
+        let mut parser = Parser::new("echo a\n\n  $x = 1; | cat; ls");
+
+        parser.parse_with_diagnostics();
+
+        assert_eq!(
+            parser.locations(),
+            [
+                Location::new(1, 1),
+                Location::new(3, 3),
+                Location::new(3, 18)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_first_error() {
+        let err = Parser::new("| cat\necho $1").parse().unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::SyntaxError);
+    }
+
+    #[test]
+    fn test_parse_multi_line_pipe() {
+        let ast = Parser::new("echo hello |\ncat").parse().unwrap();
+
+        assert_eq!(
+            ast,
+            Ast::Semicolon(
+                [Ast::Pipe(Pipe::from(
+                    [
+                        Command {
+                            expr: Expr::String("echo".to_string()),
+                            args: vec![Expr::String("hello".to_string())],
+                            redirects: Vec::default(),
+                            background: false,
+                            pipe_stderr: false
+                        },
+                        Command {
+                            expr: Expr::String("cat".to_string()),
+                            args: Vec::default(),
+                            redirects: Vec::default(),
+                            background: false,
+                            pipe_stderr: false
+                        }
+                    ]
+                    .as_slice()
+                ))]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_here_document() {
+        let ast = Parser::new("cat <<EOF\nhello\n\n# world\nEOF\necho done")
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            ast,
+            Ast::Semicolon(
+                [
+                    Ast::Statement(Statement::Command(Command {
+                        expr: Expr::String("cat".to_string()),
+                        args: Vec::default(),
+                        redirects: vec![Redirect {
+                            left: Expr::FD(0),
+                            right: Expr::String("hello\n\n# world\n".to_string()),
+                            operator: RedirectOperator::HereDoc
+                        }],
+                        background: false,
+                        pipe_stderr: false
+                    })),
+                    Ast::Statement(Statement::Command(Command {
+                        expr: Expr::String("echo".to_string()),
+                        args: vec![Expr::String("done".to_string())],
+                        redirects: Vec::default(),
+                        background: false,
+                        pipe_stderr: false
+                    }))
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let mut parser = Parser::new("match $x {\n  a | *.rs => { echo a; ls }\n  ~ '^v' => {}\n  _ => {\n    echo b\n  }\n}");
+
+        let ast = parser.parse().unwrap();
+
+        let command = |name: &str| {
+            Ast::Statement(Statement::Command(Command {
+                expr: Expr::String(name.to_string()),
+                args: Vec::default(),
+                redirects: Vec::default(),
+                background: false,
+                pipe_stderr: false,
+            }))
+        };
+
+        assert_eq!(
+            ast,
+            Ast::Semicolon(
+                [Ast::Statement(Statement::Match(Match {
+                    expr: Expr::Ident("x".to_string()),
+                    arms: vec![
+                        MatchArm {
+                            patterns: vec![
+                                Pattern::Glob("a".to_string()),
+                                Pattern::Glob("*.rs".to_string())
+                            ],
+                            body: Ast::Semicolon(
+                                [
+                                    Ast::Statement(Statement::Command(Command {
+                                        expr: Expr::String("echo".to_string()),
+                                        args: vec![Expr::String("a".to_string())],
+                                        redirects: Vec::default(),
+                                        background: false,
+                                        pipe_stderr: false
+                                    })),
+                                    command("ls")
+                                ]
+                                .into()
+                            )
+                        },
+                        MatchArm {
+                            patterns: vec![Pattern::Regex("^v".to_string())],
+                            body: Ast::new()
+                        },
+                        MatchArm {
+                            patterns: vec![Pattern::Wildcard],
+                            body: Ast::Semicolon(
+                                [Ast::Statement(Statement::Command(Command {
+                                    expr: Expr::String("echo".to_string()),
+                                    args: vec![Expr::String("b".to_string())],
+                                    redirects: Vec::default(),
+                                    background: false,
+                                    pipe_stderr: false
+                                }))]
+                                .into()
+                            )
+                        }
+                    ]
+                }))]
+                .into()
+            )
+        );
+
+        assert_eq!(parser.locations(), [Location::new(1, 1)]);
+
+        assert_eq!(
+            parser.block_locations(),
+            [
+                Location::new(2, 3),
+                Location::new(2, 17),
+                Location::new(2, 25),
+                Location::new(2, 28),
+                Location::new(3, 3),
+                Location::new(3, 13),
+                Location::new(4, 3),
+                Location::new(5, 5),
+                Location::new(6, 3),
+                Location::new(7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_match_error() {
+        for input in [
+            "match { _ => {} }",
+            "match $x _ => {}",
+            "match $x { _ {} }",
+            "match $x { _ => echo }",
+            "match $x { ~ => {} }",
+            "match $x { | => {} }",
+            "match $x { _ => {} } echo",
+        ] {
+            let err = Parser::new(input).parse().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::SyntaxError, "{input}");
+        }
+
+        assert!(is_incomplete("match $x {\n  _ => {"));
+        assert!(is_incomplete("match $x {\n  _ => {}"));
+    }
+
+    #[test]
+    fn test_parse_negated() {
+        let command = |name: &str, args: &[&str]| Command {
+            expr: Expr::String(name.to_string()),
+            args: args.iter().map(|arg| Expr::String(arg.to_string())).collect(),
+            redirects: Vec::default(),
+            background: false,
+            pipe_stderr: false,
+        };
+
+        assert_eq!(
+            Parser::new("! grep a | wc; ! ! true; echo !; '!' x").parse().unwrap(),
+            Ast::Semicolon(
+                [
+                    Ast::Negated(Box::new(Ast::Pipe(Pipe::from(
+                        [command("grep", &["a"]), command("wc", &[])].as_slice()
+                    )))),
+                    Ast::Negated(Box::new(Ast::Negated(Box::new(Ast::Statement(
+                        Statement::Command(command("true", &[]))
+                    ))))),
+                    Ast::Statement(Statement::Command(command("echo", &["!"]))),
+                    Ast::Statement(Statement::Command(Command {
+                        expr: Expr::Literal("!".to_string()),
+                        ..command("", &["x"])
+                    })),
+                ]
+                .into()
+            )
+        );
+
+        for input in ["!", "! $x = 1", "! match $x { _ => {} }"] {
+            let err = Parser::new(input).parse().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::SyntaxError, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_import() {
+        assert_eq!(
+            Parser::new("import \"lib/git.fsh\"; import $lib").parse().unwrap(),
+            Ast::Semicolon(
+                [
+                    Ast::Statement(Statement::Import(Import {
+                        path: Expr::Literal("lib/git.fsh".to_string())
+                    })),
+                    Ast::Statement(Statement::Import(Import {
+                        path: Expr::Ident("lib".to_string())
+                    })),
+                ]
+                .into()
+            )
+        );
+
+        for input in ["import", "import a b", "import | cat"] {
+            let err = Parser::new(input).parse().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::SyntaxError, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_try() {
+        let command = |name: &str| {
+            Ast::Semicolon(
+                [Ast::Statement(Statement::Command(Command {
+                    expr: Expr::String(name.to_string()),
+                    args: Vec::default(),
+                    redirects: Vec::default(),
+                    background: false,
+                    pipe_stderr: false,
+                }))]
+                .into(),
+            )
+        };
+
+        let mut parser = Parser::new("try {\n  a\n}\ncatch $err {\n  b\n}\n\nfinally { c }\ntry {} catch {}");
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            Ast::Semicolon(
+                [
+                    Ast::Statement(Statement::Try(Try {
+                        body: Box::new(command("a")),
+                        catch: Some(Catch {
+                            ident: Some(Expr::Ident("err".to_string())),
+                            body: Box::new(command("b"))
+                        }),
+                        finally: Some(Box::new(command("c")))
+                    })),
+                    Ast::Statement(Statement::Try(Try {
+                        body: Box::new(Ast::new()),
+                        catch: Some(Catch {
+                            ident: None,
+                            body: Box::new(Ast::new())
+                        }),
+                        finally: None
+                    }))
+                ]
+                .into()
+            )
+        );
+
+        assert_eq!(parser.locations(), [Location::new(1, 1), Location::new(9, 1)]);
+
+        for input in [
+            "try { a }",
+            "try a",
+            "try { a } catch",
+            "try { a } catch err { b }",
+            "try { a } finally { b } catch { c }",
+            "try { a } finally { b } c",
+        ] {
+            let err = Parser::new(input).parse().unwrap_err();
+
+            assert_eq!(err.kind(), &ErrorKind::SyntaxError, "{input}");
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,59 +1,248 @@
-use std::fmt::{Display, Formatter, Result};
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter, Result},
+};
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
-pub enum Token {
+/// A reserved word, it is only a keyword in command position.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Keyword {
+    If,
+    Else,
+    While,
+    For,
+    Fn,
+    Return,
+    Break,
+    Continue,
+    Match,
+    Try,
+    Catch,
+    Finally,
+    Import,
+}
+
+impl Keyword {
+    pub const ALL: [Keyword; 13] = [
+        Keyword::If,
+        Keyword::Else,
+        Keyword::While,
+        Keyword::For,
+        Keyword::Fn,
+        Keyword::Return,
+        Keyword::Break,
+        Keyword::Continue,
+        Keyword::Match,
+        Keyword::Try,
+        Keyword::Catch,
+        Keyword::Finally,
+        Keyword::Import,
+    ];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::For => "for",
+            Keyword::Fn => "fn",
+            Keyword::Return => "return",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
+            Keyword::Match => "match",
+            Keyword::Try => "try",
+            Keyword::Catch => "catch",
+            Keyword::Finally => "finally",
+            Keyword::Import => "import",
+        }
+    }
+
+    /// Get the keyword of the word, if it is reserved.
+    pub fn parse(word: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|keyword| keyword.as_str() == word)
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A token, the text borrows the source where it is written as is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
     EOF,
     Pipe,           // |
+    PipeStderr,     // |&
     Assign,         // =
     Gt,             // >
     Lt,             // <
     Semicolon,      // ;
     Dollar,         // $
     Ampersand,      // &
-    String(String), // hello
-    Ident(String),  // $a , &b
-    Number(usize),  // 0 ~ 9
+    Bang,           // !
+    LBrace,         // {
+    RBrace,         // }
+    Keyword(Keyword), // if, while, ...
+    HereDoc(Cow<'a, str>), // <<EOF ... EOF
+    String(Cow<'a, str>), // hello
+    Literal(Cow<'a, str>), // 'a b' "a b" $'a\tb', quoted, never globbed
+    Ident(Cow<'a, str>),  // $a , &b
+    KeyIdent(Cow<'a, str>, Cow<'a, str>), // a=$b, an argument, the name and the variable
+    Number(i64),    // -1, 0, 42
+    Float(f64),     // -1.5, 0.25
     FD(i32),        // 0 ~ 9 with negative
 }
 
-impl Token {
+impl Token<'_> {
     pub fn len(&self) -> usize {
         match self {
             Token::EOF => 0,
             Token::Pipe => 1,
+            Token::PipeStderr => 2,
             Token::Assign => 1,
             Token::Gt => 1,
             Token::Lt => 1,
             Token::Semicolon => 1,
             Token::Dollar => 1,
             Token::Ampersand => 1,
+            Token::Bang => 1,
+            Token::LBrace => 1,
+            Token::RBrace => 1,
+            Token::Keyword(v) => v.as_str().len(),
+            Token::HereDoc(v) => v.len(),
             Token::String(v) => v.len(),
+            Token::Literal(v) => v.len(),
             Token::Ident(v) => v.len(),
+            Token::KeyIdent(k, v) => k.len() + v.len() + 2,
             Token::Number(v) => v.to_string().len(),
+            Token::Float(v) => format!("{v:?}").len(),
             Token::FD(v) => v.to_string().len(),
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy the borrowed text, so that the token outlives the source.
+    pub fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::EOF => Token::EOF,
+            Token::Pipe => Token::Pipe,
+            Token::PipeStderr => Token::PipeStderr,
+            Token::Assign => Token::Assign,
+            Token::Gt => Token::Gt,
+            Token::Lt => Token::Lt,
+            Token::Semicolon => Token::Semicolon,
+            Token::Dollar => Token::Dollar,
+            Token::Ampersand => Token::Ampersand,
+            Token::Bang => Token::Bang,
+            Token::LBrace => Token::LBrace,
+            Token::RBrace => Token::RBrace,
+            Token::Keyword(v) => Token::Keyword(v),
+            Token::HereDoc(v) => Token::HereDoc(Cow::Owned(v.into_owned())),
+            Token::String(v) => Token::String(Cow::Owned(v.into_owned())),
+            Token::Literal(v) => Token::Literal(Cow::Owned(v.into_owned())),
+            Token::Ident(v) => Token::Ident(Cow::Owned(v.into_owned())),
+            Token::KeyIdent(k, v) => Token::KeyIdent(Cow::Owned(k.into_owned()), Cow::Owned(v.into_owned())),
+            Token::Number(v) => Token::Number(v),
+            Token::Float(v) => Token::Float(v),
+            Token::FD(v) => Token::FD(v),
+        }
+    }
 }
 
-impl Display for Token {
+impl Display for Token<'_> {
     fn fmt(&self, tkn: &mut Formatter) -> Result {
         match self {
             Token::EOF => write!(tkn, "EOF"),
             Token::Pipe => write!(tkn, "|"),
+            Token::PipeStderr => write!(tkn, "|&"),
             Token::Assign => write!(tkn, "="),
             Token::Gt => write!(tkn, ">"),
             Token::Lt => write!(tkn, "<"),
             Token::Semicolon => write!(tkn, ";"),
             Token::Dollar => write!(tkn, "$"),
             Token::Ampersand => write!(tkn, "&"),
+            Token::Bang => write!(tkn, "!"),
+            Token::LBrace => write!(tkn, "{{"),
+            Token::RBrace => write!(tkn, "}}"),
+            Token::Keyword(v) => write!(tkn, "{v}"),
+            Token::HereDoc(v) => write!(tkn, "{v}"),
             Token::String(v) => write!(tkn, "{v}"),
+            Token::Literal(v) => write!(tkn, "{v}"),
             Token::Ident(v) => write!(tkn, "{v}"),
+            Token::KeyIdent(k, v) => write!(tkn, "{k}=${v}"),
             Token::Number(v) => write!(tkn, "{v}"),
+            Token::Float(v) => write!(tkn, "{v:?}"),
             Token::FD(v) => write!(tkn, "{v}"),
         }
     }
 }
 
+/// A byte range of the source.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// The kind of a lexeme, the values of the tokens that hold text are left in the source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    EOF,
+    Pipe,
+    PipeStderr,
+    Assign,
+    Gt,
+    Lt,
+    Semicolon,
+    Ampersand,
+    Bang,
+    LBrace,
+    RBrace,
+    Keyword(Keyword),
+    HereDoc(Span), // the span of the body
+    Word,          // hello
+    Quoted,        // 'hello' "hello", the span includes the quotes
+    KeyValue(Span), // a=b a='b c', an argument, the span of the value
+    AnsiC,         // $'hello\n', the span includes the $ and the quotes
+    Ident,         // $a, the span includes the $
+    Number(i64),
+    Float(f64),
+    FD(i32),
+}
+
+/// A token as read by the lexer, it does not copy the text of the source.
+///
+/// Use `Lexer::text` to get the text, or `Lexer::token` to get a `Token`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lexeme {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Lexeme {
+    pub const fn new(kind: TokenKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,14 +251,19 @@ mod tests {
     fn test_token_len() {
         assert_eq!(Token::EOF.len(), 0);
         assert_eq!(Token::Pipe.len(), 1);
+        assert_eq!(Token::PipeStderr.len(), 2);
         assert_eq!(Token::Assign.len(), 1);
         assert_eq!(Token::Gt.len(), 1);
         assert_eq!(Token::Lt.len(), 1);
         assert_eq!(Token::Semicolon.len(), 1);
         assert_eq!(Token::Dollar.len(), 1);
         assert_eq!(Token::Ampersand.len(), 1);
-        assert_eq!(Token::String("hello".to_string()).len(), 5);
-        assert_eq!(Token::Ident("a".to_string()).len(), 1);
+        assert_eq!(Token::LBrace.len(), 1);
+        assert_eq!(Token::RBrace.len(), 1);
+        assert_eq!(Token::Keyword(Keyword::While).len(), 5);
+        assert_eq!(Token::HereDoc("hello\n".into()).len(), 6);
+        assert_eq!(Token::String("hello".into()).len(), 5);
+        assert_eq!(Token::Ident("a".into()).len(), 1);
         assert_eq!(Token::Number(1).len(), 1);
         assert_eq!(Token::FD(1).len(), 1);
     }
@@ -78,15 +272,30 @@ mod tests {
     fn test_token_display() {
         assert_eq!(format!("{}", Token::EOF), "EOF");
         assert_eq!(format!("{}", Token::Pipe), "|");
+        assert_eq!(format!("{}", Token::PipeStderr), "|&");
         assert_eq!(format!("{}", Token::Assign), "=");
         assert_eq!(format!("{}", Token::Gt), ">");
         assert_eq!(format!("{}", Token::Lt), "<");
         assert_eq!(format!("{}", Token::Semicolon), ";");
         assert_eq!(format!("{}", Token::Dollar), "$");
         assert_eq!(format!("{}", Token::Ampersand), "&");
-        assert_eq!(format!("{}", Token::String("hello".to_string())), "hello");
-        assert_eq!(format!("{}", Token::Ident("a".to_string())), "a");
+        assert_eq!(format!("{}", Token::LBrace), "{");
+        assert_eq!(format!("{}", Token::RBrace), "}");
+        assert_eq!(format!("{}", Token::Keyword(Keyword::Fn)), "fn");
+        assert_eq!(format!("{}", Token::HereDoc("hello\n".into())), "hello\n");
+        assert_eq!(format!("{}", Token::String("hello".into())), "hello");
+        assert_eq!(format!("{}", Token::Ident("a".into())), "a");
         assert_eq!(format!("{}", Token::Number(1)), "1");
         assert_eq!(format!("{}", Token::FD(1)), "1");
     }
+
+    #[test]
+    fn test_keyword_parse() {
+        for keyword in Keyword::ALL {
+            assert_eq!(Keyword::parse(keyword.as_str()), Some(keyword));
+        }
+
+        assert_eq!(Keyword::parse("echo"), None);
+        assert_eq!(Keyword::parse("If"), None);
+    }
 }
@@ -1,56 +1,7 @@
 use super::token::Token;
 
-
-/// Remove comments from the input.
-/// 
-/// The comment is a line starting with a (#) character. end with a semicolon (;) or newline character.
-pub(super) fn remove_comment(input: &str) -> String {
-    let mut result = String::with_capacity(input.len() + 1024);
-
-    let mut is_comment = false;
-
-    for c in input.chars() {
-        if c == '#' {
-            is_comment = true;
-        }
-
-        if is_comment == false {
-            result.push(c);
-        }
-
-        if c == '\n' || c == '\r' || c == ';' {
-            is_comment = false;
-        }
-    }
-
-    result
-}
-
-/// Remove empty lines from the input.
-/// 
-/// The empty line is a line that contains only whitespace characters.
-pub(super) fn remove_empty_line(input: &str) -> String {
-    let mut result = String::with_capacity(input.len() + 1024);
-
-    for line in input.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-
-        result.push_str(line);
-        result.push('\n');
-    }
-
-    result
-}
-
-/// Replace the newline character with a semicolon.
-pub(super) fn replace_line_with_semicolon(input: &str) -> String {
-    input.replace("\n", ";")
-}
-
 /// Split the tokens into multiple parts, the split position is the place token.
-pub(super) fn recursion_split(place: &Token, tokens: &[Token]) -> Vec<Vec<Token>> {
+pub(super) fn recursion_split<'a>(place: &Token, tokens: &[Token<'a>]) -> Vec<Vec<Token<'a>>> {
     let mut result = Vec::new();
 
     let (left, right) = split(place, tokens);
@@ -69,7 +20,7 @@ pub(super) fn recursion_split(place: &Token, tokens: &[Token]) -> Vec<Vec<Token>
 ///
 /// For example,
 ///
-/// consider a token vec `[Token::String("A".to_string()), Token::Semicolon, Token::String("B".to_string())]`.
+/// consider a token vec `[Token::String("A".into()), Token::Semicolon, Token::String("B".into())]`.
 ///
 /// If the split position is Token::Semicolon, the "Left" will be `[Token::String("A")]` and the "Right" will be `[Token::String("B")]`.
 ///  
@@ -85,8 +36,8 @@ pub(super) fn recursion_split(place: &Token, tokens: &[Token]) -> Vec<Vec<Token>
 ///
 /// **!Please refer to the unit tests for details.!**
 ///
-pub(super) fn split(place: &Token, tokens: &[Token]) -> (Vec<Token>, Vec<Token>) {
-    if tokens.contains(place) == false {
+pub(super) fn split<'a>(place: &Token, tokens: &[Token<'a>]) -> (Vec<Token<'a>>, Vec<Token<'a>>) {
+    if !tokens.contains(place) {
         return (tokens.to_vec(), Vec::default());
     }
 
@@ -110,35 +61,27 @@ pub(super) fn split(place: &Token, tokens: &[Token]) -> (Vec<Token>, Vec<Token>)
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_replace_line_with_semicolon() {
-        let input = "a\nb\nc";
-        let expected = "a;b;c";
-
-        assert_eq!(replace_line_with_semicolon(input), expected);
-    }
-
     #[test]
     fn test_split() {
         let tokens = vec![
-            Token::String("A".to_string()),
+            Token::String("A".into()),
             Token::Semicolon,
-            Token::String("B".to_string()),
+            Token::String("B".into()),
         ];
 
         let place = &Token::Semicolon;
 
         let (left, right) = split(place, &tokens);
 
-        assert_eq!(left, vec![Token::String("A".to_string())]);
-        assert_eq!(right, vec![Token::String("B".to_string())]);
+        assert_eq!(left, vec![Token::String("A".into())]);
+        assert_eq!(right, vec![Token::String("B".into())]);
     }
 
     #[test]
     fn test_split_not_found() {
         let tokens = vec![
-            Token::String("A".to_string()),
-            Token::String("B".to_string()),
+            Token::String("A".into()),
+            Token::String("B".into()),
         ];
 
         let place = &Token::Semicolon;
@@ -148,8 +91,8 @@ mod tests {
         assert_eq!(
             left,
             vec![
-                Token::String("A".to_string()),
-                Token::String("B".to_string())
+                Token::String("A".into()),
+                Token::String("B".into())
             ]
         );
         assert_eq!(right, Vec::default());
@@ -158,11 +101,11 @@ mod tests {
     #[test]
     fn test_recursion_split() {
         let tokens = vec![
-            Token::String("A".to_string()),
+            Token::String("A".into()),
             Token::Semicolon,
-            Token::String("B".to_string()),
+            Token::String("B".into()),
             Token::Semicolon,
-            Token::String("C".to_string()),
+            Token::String("C".into()),
         ];
 
         let place = &Token::Semicolon;
@@ -170,26 +113,8 @@ mod tests {
         let result = recursion_split(place, &tokens);
 
         assert_eq!(result.len(), 3);
-        assert_eq!(result[0], vec![Token::String("A".to_string())]);
-        assert_eq!(result[1], vec![Token::String("B".to_string())]);
-        assert_eq!(result[2], vec![Token::String("C".to_string())]);
-    }
-
-    #[test]
-    fn test_remove_comment() {
-        let input = "hello world;\n# this is a comment\nhello world;";
-
-        let expected = "hello world;\nhello world;";
-
-        assert_eq!(remove_comment(input), expected);
-    }
-
-    #[test]
-    fn test_remove_empty_line() {
-        let input = "hello world;\n\nhello world;\n";
-
-        let expected_1 = "hello world;\nhello world;\n";
-
-        assert_eq!(remove_empty_line(input), expected_1);
+        assert_eq!(result[0], vec![Token::String("A".into())]);
+        assert_eq!(result[1], vec![Token::String("B".into())]);
+        assert_eq!(result[2], vec![Token::String("C".into())]);
     }
 }
@@ -4,18 +4,19 @@ pub(super) enum Cursor {
     Backspace,
     Left,
     Right,
+    #[allow(dead_code)]
     ClearLine,
 }
 
 impl Cursor {
     pub(super) fn get_esc_code(&self) -> String {
-        return match &self {
+        match &self {
             Cursor::Move(position) => format!("\x1b[{position}G"),
             Cursor::Backspace => format!("\x08{}", " "),
-            Cursor::Left => format!("\x1b[1D"),
-            Cursor::Right => format!("\x1b[1C"),
-            Cursor::ClearLine => format!("\x1b[2K"),
-        };
+            Cursor::Left => "\x1b[1D".to_string(),
+            Cursor::Right => "\x1b[1C".to_string(),
+            Cursor::ClearLine => "\x1b[2K".to_string(),
+        }
     }
 }
 
@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter, Result};
+
 pub(super) struct Line(usize, Vec<u8>);
 
 impl Line {
@@ -28,15 +30,20 @@ impl Line {
         self.0 += 1;
     }
 
-    // pub(super) fn clear(&mut self) {
-    //     self.0 = 0;
-    //     self.1.clear();
-    // }
+    #[allow(dead_code)]
+    pub(super) fn clear(&mut self) {
+        self.0 = 0;
+        self.1.clear();
+    }
 
     pub(super) fn len(&self) -> usize {
         self.1.len()
     }
 
+    pub(super) fn is_empty(&self) -> bool {
+        self.1.is_empty()
+    }
+
     pub(super) fn position(&self) -> usize {
         self.0
     }
@@ -46,9 +53,9 @@ impl Line {
     // }
 }
 
-impl ToString for Line {
-    fn to_string(&self) -> String {
-        String::from_utf8_lossy(&self.1).to_string()
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.1))
     }
 }
 
@@ -135,7 +142,7 @@ mod tests {
         line.insert(b'l');
         line.insert(b'o');
 
-        // line.clear();
+        line.clear();
 
         assert_eq!(line.0, 0);
         assert_eq!(line.1, vec![]);
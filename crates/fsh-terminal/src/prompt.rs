@@ -53,7 +53,7 @@ fn get_host_name() -> String {
 }
 
 fn decode(source: impl Into<String>) -> String {
-    let mut string = String::from(source.into());
+    let mut string = source.into();
 
     if string.contains(USER_NAME) {
         string = string.replace(USER_NAME, &get_user_name());
@@ -7,13 +7,13 @@ use fsh_common::{
     Result,
 };
 
-use libc::exit;
 use std::io;
 use std::io::Write;
 
 pub struct Terminal {
     termios: libc::termios,
     prompt: String,
+    continuation_prompt: String,
     // history: Option<History>,
 }
 
@@ -23,19 +23,17 @@ impl Terminal {
     /// 
     /// # Example
     /// ```
-    /// use flat_terminal::Terminal;
+    /// use fsh_terminal::Terminal;
     /// 
     /// let mut terminal = Terminal::new();
     /// 
     /// terminal.set_prompt("> ");
-    /// 
-    /// // History is disabled if there is no history.
-    /// terminal.set_history(History::new());
     /// ```
     pub fn new() -> Self {
         Self {
             termios: termios(),
             prompt: String::new(),
+            continuation_prompt: String::new(),
             // history: None,
         }
     }
@@ -45,6 +43,13 @@ impl Terminal {
         self.prompt = prompt.into();
     }
 
+    /// Set the continuation prompt
+    ///
+    /// The continuation prompt is displayed while reading the following lines of an incomplete input. (PS2 equivalent)
+    pub fn set_continuation_prompt(&mut self, prompt: impl Into<String>) {
+        self.continuation_prompt = prompt.into();
+    }
+
     // Set the history
     // 
     // History is disabled if there is no history.
@@ -53,10 +58,15 @@ impl Terminal {
     // }
 
     /// Read a line
+    ///
+    /// # Errors
+    /// `ErrorKind::Interrupted` - If the line is discarded by Ctrl-C.
+    ///
+    /// `ErrorKind::EndOfInput` - If the input ends, or Ctrl-D is pressed on an empty line.
     /// 
     /// # Example
-    /// ```
-    /// use flat_terminal::Terminal;
+    /// ```no_run
+    /// use fsh_terminal::Terminal;
     /// 
     /// let mut terminal = Terminal::new();
     /// 
@@ -65,6 +75,50 @@ impl Terminal {
     /// let line = terminal.read_line();
     /// ```
     pub fn read_line(&mut self) -> Result<String> {
+        let prompt = self.prompt.to_owned();
+
+        self.read_line_with_prompt(&prompt)
+    }
+
+    /// Read lines until the input is complete
+    ///
+    /// The first line is read with the prompt, and the following lines are read with the continuation prompt.
+    ///
+    /// Lines are joined with a newline character (\n) and passed to `is_complete`, reading stops when it returns true.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use fsh_terminal::Terminal;
+    ///
+    /// let mut terminal = Terminal::new();
+    ///
+    /// terminal.set_prompt("$ ");
+    ///
+    /// terminal.set_continuation_prompt("> ");
+    ///
+    /// // read until the double quotes are closed.
+    /// let source = terminal.read_until(|source| source.matches('"').count() % 2 == 0);
+    /// ```
+    pub fn read_until<F>(&mut self, is_complete: F) -> Result<String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut source = self.read_line()?;
+
+        while !is_complete(&source) {
+            let prompt = self.continuation_prompt.to_owned();
+
+            let line = self.read_line_with_prompt(&prompt)?;
+
+            source.push('\n');
+
+            source.push_str(&line);
+        }
+
+        Ok(source)
+    }
+
+    fn read_line_with_prompt(&mut self, prompt: &str) -> Result<String> {
         self.set_raw_mode();
 
         let mut stdout = io::stdout().lock();
@@ -72,7 +126,7 @@ impl Terminal {
         let mut line = Line::new();
 
         stdout
-            .write_all(format!("{}", self.prompt).as_bytes())
+            .write_all(prompt.as_bytes())
             .map_err(|_| Error::new(ErrorKind::Other, "Failed to write to stdout"))?;
 
         loop {
@@ -82,16 +136,40 @@ impl Terminal {
 
             let ch = match get_char() {
                 Some(ch) => ch,
-                None => continue,
+                // the end of the input ends the last line, an empty line ends the input.
+                None if !line.is_empty() => break,
+                None => {
+                    self.unset_raw_mode();
+
+                    Err(Error::new(ErrorKind::EndOfInput, "End of input"))?
+                }
             };
 
             match ch {
-                3 => {
+                // Ctrl-D, on an empty line the input ends
+                4 if line.is_empty() => {
+                    self.unset_raw_mode();
+
                     stdout.write_all(b"\n").map_err(|_| {
                         Error::new(ErrorKind::Other, "Failed to write to stdout")
                     })?;
+
+                    Err(Error::new(ErrorKind::EndOfInput, "End of input"))?
+                }
+
+                // Ctrl-C, the line is discarded
+                3 => {
                     self.unset_raw_mode();
-                    unsafe { exit(0) };
+
+                    stdout.write_all(b"^C\n").map_err(|_| {
+                        Error::new(ErrorKind::Other, "Failed to write to stdout")
+                    })?;
+
+                    stdout
+                        .flush()
+                        .map_err(|_| Error::new(ErrorKind::Other, "Failed to flush stdout"))?;
+
+                    Err(Error::new(ErrorKind::Interrupted, "Interrupted"))?
                 }
 
                 // Enter
@@ -187,8 +265,7 @@ impl Terminal {
 
                                 stdout
                                     .write_all(
-                                        format!("{}", ascii::Cursor::Right.get_esc_code())
-                                            .as_bytes(),
+                                        ascii::Cursor::Right.get_esc_code().as_bytes(),
                                     )
                                     .map_err(|_| {
                                         Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -201,8 +278,7 @@ impl Terminal {
                             if line.position() > 0 {
                                 stdout
                                     .write_all(
-                                        format!("{}", ascii::Cursor::Left.get_esc_code())
-                                            .as_bytes(),
+                                        ascii::Cursor::Left.get_esc_code().as_bytes(),
                                     )
                                     .map_err(|_| {
                                         Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -218,7 +294,7 @@ impl Terminal {
 
                 // Backspace
                 127 => {
-                    if line.position() <= 0 {
+                    if line.position() == 0 {
                         continue;
                     }
 
@@ -226,8 +302,7 @@ impl Terminal {
                         if i != 0 {
                             stdout
                                 .write_all(
-                                    format!("{}", ascii::Cursor::Backspace.get_esc_code())
-                                        .as_bytes(),
+                                    ascii::Cursor::Backspace.get_esc_code().as_bytes(),
                                 )
                                 .map_err(|_| {
                                     Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -236,28 +311,27 @@ impl Terminal {
                     }
 
                     stdout
-                        .write_all(format!("\r{}{}", self.prompt, line.to_string()).as_bytes())
+                        .write_all(format!("\r{}{}", prompt, line).as_bytes())
                         .map_err(|_| Error::new(ErrorKind::Other, "Failed to write to stdout"))?;
 
                     line.backspace();
 
                     stdout
                         .write_all(
-                            format!("{}", ascii::Cursor::Backspace.get_esc_code()).as_bytes(),
+                            ascii::Cursor::Backspace.get_esc_code().as_bytes(),
                         )
                         .map_err(|_| Error::new(ErrorKind::Other, "Failed to write to stdout"))?;
 
                     stdout
-                        .write_all(format!("\r{}{}", self.prompt, line.to_string(),).as_bytes())
+                        .write_all(format!("\r{}{}", prompt, line).as_bytes())
                         .map_err(|_| Error::new(ErrorKind::Other, "Failed to write to stdout"))?;
 
                     if line.position() < line.len() {
-                        let move_position = self.prompt.len() + line.position() - 1;
+                        let move_position = prompt.len() + line.position() - 1;
 
                         stdout
                             .write_all(
-                                format!("{}", ascii::Cursor::Move(move_position).get_esc_code())
-                                    .as_bytes(),
+                                ascii::Cursor::Move(move_position).get_esc_code().as_bytes(),
                             )
                             .map_err(|_| {
                                 Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -272,8 +346,7 @@ impl Terminal {
                         if i != 0 {
                             stdout
                                 .write_all(
-                                    format!("{}", ascii::Cursor::Backspace.get_esc_code())
-                                        .as_bytes(),
+                                    ascii::Cursor::Backspace.get_esc_code().as_bytes(),
                                 )
                                 .map_err(|_| {
                                     Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -282,7 +355,7 @@ impl Terminal {
                     }
 
                     stdout
-                        .write_all(format!("\r{}{}", self.prompt, line.to_string()).as_bytes())
+                        .write_all(format!("\r{}{}", prompt, line).as_bytes())
                         .map_err(|_| Error::new(ErrorKind::Other, "Failed to write to stdout"))?;
 
                     if line.position() < line.len() {
@@ -290,8 +363,7 @@ impl Terminal {
 
                         stdout
                             .write_all(
-                                format!("{}", ascii::Cursor::Move(move_position).get_esc_code())
-                                    .as_bytes(),
+                                ascii::Cursor::Move(move_position).get_esc_code().as_bytes(),
                             )
                             .map_err(|_| {
                                 Error::new(ErrorKind::Other, "Failed to write to stdout")
@@ -326,7 +398,7 @@ impl Terminal {
 
         let mut raw = self.termios;
 
-        raw.c_lflag = raw.c_lflag & !(libc::ICANON | libc::ECHO | libc::IEXTEN | libc::ISIG);
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN | libc::ISIG);
         // raw.c_lflag = raw.c_lflag & !(libc::ICANON | libc::ECHO );
 
         raw.c_cc[libc::VTIME] = 0;
@@ -347,17 +419,29 @@ impl Terminal {
     }
 }
 
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[inline]
+/// Read a byte from the stdin, `None` at the end of the input.
 fn get_char() -> Option<u8> {
     let code = [0; 1];
 
-    let n = unsafe { libc::read(0, code.as_ptr() as *mut libc::c_void, 1) };
+    loop {
+        let n = unsafe { libc::read(0, code.as_ptr() as *mut libc::c_void, 1) };
 
-    if n <= 0 {
-        return None;
-    }
+        if n > 0 {
+            return Some(code[0]);
+        }
 
-    Some(code[0])
+        // a signal of a job interrupts the read, the input goes on.
+        if n == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
 }
 
 #[inline]